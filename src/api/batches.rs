@@ -0,0 +1,630 @@
+//! Batch jobs API over JSONL files
+//!
+//! A batch reads one request per line from a JSONL file in the batch directory
+//! (EXSA_BATCH_DIR), either uploaded or already on disk, and writes one result
+//! per line to an output JSONL next to it. Lines use the OpenAI Batch format
+//! (`{"custom_id", "method", "url", "body"}`) or are a bare request body; `url`
+//! (or the shape of the body) picks chat completions or `/v1/generate`.
+//!
+//! Every request runs at background priority, so interactive traffic keeps
+//! going first while a batch drains.
+
+use crate::api::handlers::{prepare_chat, prepare_generate, PreparedRequest};
+use crate::api::openai::{ChatCompletionRequest, ChatCompletionResponse};
+use crate::api::priority::client_id;
+use crate::api::schema::{AppState, GenerateRequest};
+use crate::inference::reasoning::prompt_opens_reasoning;
+use crate::inference::templates::ChatMessage;
+use crate::inference::{BatchJob, BatchJobHandle, Priority, ReasoningParser};
+use crate::utils::error::{ExsaError, Result};
+use axum::{
+    extract::{Json, Multipart, Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Component, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Fair-share identity of batch requests made without an API key
+const BATCH_USER: &str = "batch";
+
+/// Times a line is resubmitted after expiring in the queue before it fails
+const MAX_EXPIRED_RESUBMITS: u32 = 3;
+
+/// Requests of one batch kept in the queue at a time (EXSA_BATCH_CONCURRENCY)
+fn batch_concurrency() -> usize {
+    std::env::var("EXSA_BATCH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(2)
+}
+
+/// Largest accepted upload in bytes (EXSA_BATCH_UPLOAD_MAX_MB, default 512)
+pub fn upload_limit() -> usize {
+    std::env::var("EXSA_BATCH_UPLOAD_MAX_MB")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(512)
+        .saturating_mul(1024 * 1024)
+}
+
+/// Resolve the batch directory (EXSA_BATCH_DIR, default ./batches), creating it if needed
+pub fn resolve_batch_dir() -> Result<PathBuf> {
+    let dir = std::env::var("EXSA_BATCH_DIR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("batches"));
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::canonicalize(&dir)
+        .map_err(|_| ExsaError::InvalidParameters("EXSA_BATCH_DIR is invalid".to_string()))
+}
+
+/// Relative path inside the (canonical) batch directory: no `..`, no absolute
+/// paths, and no symlinks leading out of it
+fn batch_path(batch_dir: &std::path::Path, raw: &str) -> Result<PathBuf> {
+    let outside = || {
+        ExsaError::InvalidParameters(format!(
+            "Batch file must be a relative path inside the batch directory: {}",
+            raw
+        ))
+    };
+    let p = std::path::Path::new(raw.trim());
+    if p.as_os_str().is_empty() || !p.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(outside());
+    }
+
+    // Resolve symlinks through the deepest part that exists (an output file may not yet)
+    let joined = batch_dir.join(p);
+    let mut existing = joined.as_path();
+    let mut rest = Vec::new();
+    let resolved = loop {
+        match std::fs::canonicalize(existing) {
+            Ok(real) => break rest.iter().rev().fold(real, |path, part| path.join(part)),
+            Err(_) => {
+                rest.extend(existing.file_name());
+                existing = existing.parent().ok_or_else(outside)?;
+            }
+        }
+    };
+    if !resolved.starts_with(batch_dir) {
+        return Err(outside());
+    }
+    Ok(resolved)
+}
+
+/// Default output file for an input: `<stem>-<unix time>.output.jsonl`
+fn default_output_file(input_file: &str) -> String {
+    let stem = input_file
+        .strip_suffix(".jsonl")
+        .or_else(|| input_file.strip_suffix(".json"))
+        .unwrap_or(input_file);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!("{}-{}.output.jsonl", stem, now)
+}
+
+/// Request to start a batch over a file already in the batch directory
+#[derive(Debug, Deserialize)]
+pub struct CreateBatchRequest {
+    /// Input JSONL, relative to the batch directory
+    pub input_file: String,
+
+    /// Output JSONL, relative to the batch directory (default: `<input>-<time>.output.jsonl`)
+    #[serde(default)]
+    pub output_file: Option<String>,
+
+    /// Model for lines that don't name one (default model when omitted)
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// A parsed input line
+enum BatchRequest {
+    Chat(Box<ChatCompletionRequest>),
+    Generate(Box<GenerateRequest>),
+}
+
+/// One line of the output file
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    /// Request id (as in `/v1/requests/:id`), empty if the line was never submitted
+    id: String,
+    custom_id: String,
+    response: Option<BatchResponse>,
+    error: Option<BatchError>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchResponse {
+    status_code: u16,
+    body: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchError {
+    status_code: u16,
+    message: String,
+}
+
+impl BatchError {
+    fn from_error(e: ExsaError) -> Self {
+        let message = e.to_string();
+        Self {
+            status_code: e.into_response().status().as_u16(),
+            message,
+        }
+    }
+}
+
+/// Parse a line into its `custom_id` (default `line-<n>`) and request
+fn parse_line(
+    line: &str,
+    index: usize,
+    model: Option<&str>,
+) -> (String, std::result::Result<BatchRequest, ExsaError>) {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => {
+            return (
+                format!("line-{}", index + 1),
+                Err(ExsaError::InvalidParameters(format!("Invalid JSON: {}", e))),
+            )
+        }
+    };
+
+    let custom_id = value
+        .get("custom_id")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("line-{}", index + 1));
+    let url = value.get("url").and_then(Value::as_str).map(str::to_string);
+    let body = match value.get("body") {
+        Some(body) => body.clone(),
+        None => value,
+    };
+
+    (custom_id, parse_body(body, url.as_deref(), model))
+}
+
+/// Request for `url`, or for the body's shape (`messages` means chat) without one
+fn parse_body(mut body: Value, url: Option<&str>, model: Option<&str>) -> Result<BatchRequest> {
+    let object = body.as_object_mut().ok_or_else(|| {
+        ExsaError::InvalidParameters("Request body must be a JSON object".to_string())
+    })?;
+    let chat = match url {
+        Some(url) if url.ends_with("/chat/completions") => true,
+        Some(url) if url.ends_with("/generate") => false,
+        Some(url) => {
+            return Err(ExsaError::InvalidParameters(format!(
+                "Unsupported batch url: {}",
+                url
+            )))
+        }
+        None => object.contains_key("messages"),
+    };
+
+    // Job model for lines without one; chat requests require the field
+    if !object.contains_key("model") && (chat || model.is_some()) {
+        object.insert("model".to_string(), model.unwrap_or_default().into());
+    }
+
+    let parsed = if chat {
+        serde_json::from_value(body).map(BatchRequest::Chat)
+    } else {
+        serde_json::from_value(body).map(BatchRequest::Generate)
+    };
+    parsed.map_err(|e| ExsaError::InvalidParameters(format!("Invalid request body: {}", e)))
+}
+
+/// What every line of a batch shares
+struct BatchContext {
+    state: AppState,
+    model: Option<String>,
+    user: String,
+    cancellation_token: CancellationToken,
+}
+
+impl BatchContext {
+    /// Run one line; `None` when the batch was cancelled before it finished
+    async fn run_line(&self, index: usize, line: &str) -> Option<BatchResult> {
+        if self.cancellation_token.is_cancelled() {
+            return None;
+        }
+
+        let (custom_id, request) = parse_line(line, index, self.model.as_deref());
+        let mut id = String::new();
+        let result = match request {
+            Ok(request) => self.execute(request, &mut id).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(Some(body)) => Some(BatchResult {
+                id,
+                custom_id,
+                response: Some(BatchResponse {
+                    status_code: 200,
+                    body,
+                }),
+                error: None,
+            }),
+            Ok(None) => None,
+            Err(e) => Some(BatchResult {
+                id,
+                custom_id,
+                response: None,
+                error: Some(BatchError::from_error(e)),
+            }),
+        }
+    }
+
+    /// Submit a request at background priority and collect its output
+    async fn execute(&self, request: BatchRequest, id: &mut String) -> Result<Option<Value>> {
+        let (prepared, chat_model) = match &request {
+            BatchRequest::Chat(r) => (prepare_chat(&self.state, r).await?, Some(r.model.clone())),
+            BatchRequest::Generate(r) => (prepare_generate(&self.state, r).await?, None),
+        };
+        let PreparedRequest {
+            served,
+            prompt,
+            mut sampling_params,
+        } = prepared;
+
+        sampling_params.priority = Priority::Background;
+        sampling_params.user = Some(self.user.clone());
+        let max_tokens = sampling_params.max_tokens;
+        let mut reasoning = ReasoningParser::new(prompt_opens_reasoning(&prompt));
+
+        // Nobody waits on a line: a shed request waits out its Retry-After and one
+        // that expired in the queue (EXSA_MAX_QUEUE_WAIT_SECS) is submitted again,
+        // up to MAX_EXPIRED_RESUBMITS times
+        sampling_params.deadline_ms = None;
+        let mut resubmits = 0;
+        let (text, tokens) = loop {
+            let mut queued = loop {
+                let e = match served
+                    .queue
+                    .submit(prompt.clone(), sampling_params.clone())
+                    .await
+                {
+                    Ok(queued) => break queued,
+                    Err(e) => e,
+                };
+                let Some(secs) = e.retry_after_secs() else {
+                    return Err(e);
+                };
+                tokio::select! {
+                    _ = self.cancellation_token.cancelled() => return Ok(None),
+                    _ = tokio::time::sleep(Duration::from_secs(secs)) => {}
+                }
+            };
+            *id = queued.id.to_string();
+
+            let mut text = String::new();
+            let mut tokens = 0usize;
+            loop {
+                tokio::select! {
+                    _ = self.cancellation_token.cancelled() => {
+                        served.queue.cancel(queued.id);
+                        return Ok(None);
+                    }
+                    token = queued.token_rx.recv() => match token {
+                        Some(token) if !token.done => {
+                            text.push_str(&token.token);
+                            tokens += 1;
+                        }
+                        Some(_) => {}
+                        None => break,
+                    },
+                }
+            }
+
+            // A dropped completion sender means the engine finished without reporting
+            match queued.completion_rx.await {
+                Ok(Err(ExsaError::Timeout)) if resubmits < MAX_EXPIRED_RESUBMITS => {
+                    resubmits += 1;
+                    info!("Batch request {} expired in the queue; resubmitting", id);
+                }
+                Ok(Err(e)) => return Err(e),
+                _ => break (text, tokens),
+            }
+        };
+
+        let finish_reason = if tokens >= max_tokens {
+            "length"
+        } else {
+            "stop"
+        };
+        let body = match chat_model {
+            Some(model) => {
+                let mut content = reasoning.feed(&text).content().unwrap_or_default();
+                content.push_str(&reasoning.finish().content().unwrap_or_default());
+                let message = ChatMessage {
+                    role: "assistant".to_string(),
+                    content,
+                };
+                let response =
+                    ChatCompletionResponse::new(id.clone(), model, message, finish_reason.into());
+                serde_json::to_value(response)
+                    .map_err(|e| ExsaError::InternalError(e.to_string()))?
+            }
+            None => serde_json::json!({
+                "text": text,
+                "tokens": tokens,
+                "finish_reason": finish_reason,
+            }),
+        };
+        Ok(Some(body))
+    }
+}
+
+/// Read the input, run its lines and write results in input order
+async fn run_batch(
+    ctx: &BatchContext,
+    job: &BatchJobHandle,
+    input: PathBuf,
+    output: PathBuf,
+) -> Result<()> {
+    let content = tokio::fs::read_to_string(&input).await?;
+    let lines: Vec<(usize, String)> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index, line.to_string()))
+        .collect();
+    job.start(lines.len());
+
+    let mut out = tokio::io::BufWriter::new(tokio::fs::File::create(&output).await?);
+    let mut results = futures::stream::iter(lines)
+        .map(|(index, line)| async move { ctx.run_line(index, &line).await })
+        .buffered(batch_concurrency());
+
+    while let Some(result) = results.next().await {
+        let Some(result) = result else {
+            continue;
+        };
+        let mut json =
+            serde_json::to_string(&result).map_err(|e| ExsaError::InternalError(e.to_string()))?;
+        json.push('\n');
+        out.write_all(json.as_bytes()).await?;
+        // Flush per line so the output can be followed while the batch runs
+        out.flush().await?;
+        job.record(result.error.is_none());
+    }
+
+    Ok(())
+}
+
+/// Register a batch job and run it in the background
+fn start_batch(
+    state: &AppState,
+    headers: &HeaderMap,
+    batch_dir: &std::path::Path,
+    input_file: String,
+    output_file: Option<String>,
+    model: Option<String>,
+) -> Result<BatchJob> {
+    let input = batch_path(batch_dir, &input_file)?;
+    if !input.is_file() {
+        return Err(ExsaError::InvalidParameters(format!(
+            "Batch input file not found: {}",
+            input_file
+        )));
+    }
+    let output_file = output_file.unwrap_or_else(|| default_output_file(&input_file));
+    let output = batch_path(batch_dir, &output_file)?;
+    if output == input {
+        return Err(ExsaError::InvalidParameters(
+            "Batch output file must differ from the input file".to_string(),
+        ));
+    }
+
+    let job = state
+        .batch_jobs
+        .create(input_file, output_file, model.clone());
+    let ctx = BatchContext {
+        state: state.clone(),
+        model,
+        user: client_id(headers, None).unwrap_or_else(|| BATCH_USER.to_string()),
+        cancellation_token: job.cancellation_token().clone(),
+    };
+
+    let handle = job.clone();
+    tokio::spawn(async move {
+        let id = handle.id();
+        info!("📦 Batch {} started", id);
+        match run_batch(&ctx, &handle, input, output).await {
+            Ok(()) => handle.finish(),
+            Err(e) => {
+                warn!("Batch {} failed: {}", id, e);
+                handle.fail(e.to_string());
+            }
+        }
+        let job = handle.snapshot();
+        info!(
+            "📦 Batch {} {:?}: {}/{} lines ({} failed)",
+            id, job.status, job.completed, job.total, job.failed
+        );
+    });
+
+    Ok(job.snapshot())
+}
+
+/// Start a batch over a JSONL file in the batch directory
+pub async fn create_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateBatchRequest>,
+) -> Result<Response> {
+    let batch_dir = resolve_batch_dir()?;
+    let job = start_batch(
+        &state,
+        &headers,
+        &batch_dir,
+        request.input_file,
+        request.output_file,
+        request.model,
+    )?;
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+/// Upload a JSONL file and start a batch over it.
+///
+/// Multipart fields supported:
+/// - file: the input JSONL (stored as `upload-<id>.jsonl` in the batch directory)
+/// - model: optional model for lines that don't name one
+/// - output_file: optional output path inside the batch directory
+pub async fn upload_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response> {
+    let mut input: Option<Vec<u8>> = None;
+    let mut model: Option<String> = None;
+    let mut output_file: Option<String> = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ExsaError::InvalidParameters(format!("Invalid multipart: {e}")))?
+    {
+        let name = field.name().unwrap_or("").to_string();
+        let read_failed = |e| ExsaError::InvalidParameters(format!("{name} read failed: {e}"));
+
+        match name.as_str() {
+            "file" => input = Some(field.bytes().await.map_err(read_failed)?.to_vec()),
+            "model" => model = Some(field.text().await.map_err(read_failed)?),
+            "output_file" => output_file = Some(field.text().await.map_err(read_failed)?),
+            _ => {}
+        }
+    }
+
+    let input =
+        input.ok_or_else(|| ExsaError::InvalidParameters("No batch file provided".to_string()))?;
+    let batch_dir = resolve_batch_dir()?;
+    let input_file = format!("upload-{}.jsonl", Uuid::new_v4());
+    tokio::fs::write(batch_dir.join(&input_file), input).await?;
+
+    let model = model.filter(|m| !m.trim().is_empty());
+    let job = start_batch(&state, &headers, &batch_dir, input_file, output_file, model)?;
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+fn parse_batch_id(batch_id: &str) -> Result<Uuid> {
+    Uuid::parse_str(batch_id)
+        .map_err(|_| ExsaError::InvalidParameters(format!("Invalid batch id: {}", batch_id)))
+}
+
+fn unknown_batch(batch_id: &str) -> ExsaError {
+    ExsaError::InvalidParameters(format!("Unknown batch: {}", batch_id))
+}
+
+/// Recent batch jobs, oldest first
+pub async fn list_batches(State(state): State<AppState>) -> Json<Vec<BatchJob>> {
+    Json(state.batch_jobs.list())
+}
+
+/// Status and progress of a batch job
+pub async fn get_batch(
+    State(state): State<AppState>,
+    Path(batch_id): Path<String>,
+) -> Result<Json<BatchJob>> {
+    let id = parse_batch_id(&batch_id)?;
+    state
+        .batch_jobs
+        .get(id)
+        .map(Json)
+        .ok_or_else(|| unknown_batch(&batch_id))
+}
+
+/// Cancel a batch job: no further lines are submitted and its running requests stop
+pub async fn cancel_batch(
+    State(state): State<AppState>,
+    Path(batch_id): Path<String>,
+) -> Result<Json<BatchJob>> {
+    let id = parse_batch_id(&batch_id)?;
+    let job = state
+        .batch_jobs
+        .cancel(id)
+        .ok_or_else(|| unknown_batch(&batch_id))?;
+    info!("🛑 Cancel requested for batch {} ({:?})", id, job.status);
+    Ok(Json(job))
+}
+
+/// Results written so far, as JSONL
+pub async fn batch_output(
+    State(state): State<AppState>,
+    Path(batch_id): Path<String>,
+) -> Result<Response> {
+    let id = parse_batch_id(&batch_id)?;
+    let job = state
+        .batch_jobs
+        .get(id)
+        .ok_or_else(|| unknown_batch(&batch_id))?;
+    let output = batch_path(&resolve_batch_dir()?, &job.output_file)?;
+    let content = tokio::fs::read(&output).await.unwrap_or_default();
+    Ok(([(CONTENT_TYPE, "application/x-ndjson")], content).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_lines() {
+        let line = r#"{"custom_id": "q1", "method": "POST", "url": "/v1/chat/completions", "body": {"messages": [{"role": "user", "content": "Hi"}]}}"#;
+        let (custom_id, request) = parse_line(line, 0, Some("qwen"));
+        assert_eq!(custom_id, "q1");
+        match request.unwrap() {
+            BatchRequest::Chat(r) => assert_eq!(r.model, "qwen"),
+            BatchRequest::Generate(_) => panic!("expected a chat request"),
+        }
+
+        // Bare bodies: shape decides the endpoint, line number is the id
+        let (custom_id, request) = parse_line(r#"{"prompt": "Label this"}"#, 4, None);
+        assert_eq!(custom_id, "line-5");
+        assert!(matches!(request, Ok(BatchRequest::Generate(r)) if r.model.is_none()));
+
+        let (_, request) = parse_line(r#"{"url": "/v1/embeddings", "body": {}}"#, 0, None);
+        assert!(request.is_err());
+        let (custom_id, request) = parse_line("{not json", 1, None);
+        assert_eq!(custom_id, "line-2");
+        assert!(request.is_err());
+    }
+
+    #[test]
+    fn test_batch_paths() {
+        let dir = std::env::temp_dir().join(format!("exsa-batch-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("nightly")).unwrap();
+        let dir = std::fs::canonicalize(&dir).unwrap();
+
+        assert_eq!(
+            batch_path(&dir, "nightly/eval.jsonl").unwrap(),
+            dir.join("nightly/eval.jsonl")
+        );
+        assert!(batch_path(&dir, "../secrets.jsonl").is_err());
+        assert!(batch_path(&dir, "/etc/passwd").is_err());
+        assert!(default_output_file("eval.jsonl").starts_with("eval-"));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc", dir.join("escape")).unwrap();
+            assert!(batch_path(&dir, "escape/passwd").is_err());
+            assert!(batch_path(&dir, "escape/new.output.jsonl").is_err());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -28,7 +28,7 @@ pub async fn chat_completions(
 
     // Log the sampling parameters received from client
     info!(
-        "📊 Request params: temperature={}, max_tokens={}, top_p={}, top_k={}, repeat_penalty={}",
+        "📊 Request params: temperature={:?}, max_tokens={:?}, top_p={:?}, top_k={:?}, repeat_penalty={:?}",
         request.temperature,
         request.max_tokens,
         request.top_p,
@@ -137,8 +137,7 @@ pub async fn chat_completions(
     let queued_request = state
         .queue
         .submit(formatted_prompt, sampling_params)
-        .await
-        .map_err(|_| ExsaError::QueueFull)?;
+        .await?;
 
     let request_id = queued_request.id.to_string();
     info!("Request {} queued successfully", request_id);
@@ -1,19 +1,28 @@
 //! HTTP request handlers
 
 use crate::api::openai::{
-    ChatCompletionChunk, ChatCompletionRequest, EmbeddingItem, EmbeddingsRequest,
+    ChatCompletionChunk, ChatCompletionRequest, EmbeddingItem, EmbeddingValue, EmbeddingsRequest,
     EmbeddingsResponse, EmbeddingsUsage,
 };
-use crate::api::schema::{AppState, GenerateRequest, HealthResponse, StatusResponse, TokenEvent};
+use crate::api::priority::{client_id, deadline_ms};
+use crate::api::queue_events::QueueEventMode;
+use crate::api::resume::{last_event_id, sse_response};
+use crate::api::schema::{
+    AppState, GenerateRequest, HealthResponse, ModelInfo, StatusResponse, TokenEvent,
+};
+use crate::inference::reasoning::{prompt_opens_reasoning, strip_reasoning};
+use crate::inference::{EngineState, ReasoningParser, SamplingParams, ServedModel};
 use crate::utils::error::ExsaError;
 use axum::{
     extract::State,
-    response::sse::{Event, KeepAlive, Sse},
+    http::HeaderMap,
+    response::sse::{Event, Sse},
     Json,
 };
 use futures::stream::Stream;
 use std::convert::Infallible;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 use tracing::{error, info, warn};
@@ -29,12 +38,21 @@ pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
         .shutdown_flag
         .load(std::sync::atomic::Ordering::Relaxed);
 
+    let model_state = state.engine.state();
+    let model_loaded = model_state == EngineState::Ready;
+
     let status = if shutting_down {
         "shutting_down"
-    } else if active_requests > 100 {
-        "overloaded"
     } else {
-        "healthy"
+        match model_state {
+            EngineState::Loading => "loading",
+            EngineState::Warming => "warming",
+            EngineState::Unloading => "unloading",
+            EngineState::ModelNotLoaded => "model_not_loaded",
+            EngineState::Idle => "idle",
+            EngineState::Ready if active_requests > 100 => "overloaded",
+            EngineState::Ready => "healthy",
+        }
     };
 
     // Calculate uptime
@@ -43,7 +61,8 @@ pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
     Json(HealthResponse {
         status: status.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        model_loaded: Some(true),
+        model_loaded: Some(model_loaded),
+        model_state: Some(model_state),
         model_path: Some(model_info.model_path),
         context_size: Some(model_info.context_size),
         gpu_layers: Some(model_info.gpu_layers),
@@ -62,31 +81,28 @@ pub async fn status(State(state): State<AppState>) -> Json<StatusResponse> {
         status: "running".to_string(),
         queue_capacity: state.queue.capacity(),
         active_requests: active,
+        users: state.queue.user_stats(),
+        shed: state.router.shed_stats(),
     })
 }
 
 /// Model information handler
-pub async fn model_info(State(state): State<AppState>) -> Json<serde_json::Value> {
-    let info = state.engine.model_info();
-    Json(serde_json::json!({
-        "model_path": info.model_path,
-        "context_size": info.context_size,
-        "gpu_layers": info.gpu_layers
-    }))
+pub async fn model_info(State(state): State<AppState>) -> Json<ModelInfo> {
+    Json(state.engine.model_info())
 }
 
-/// Generate text handler with SSE streaming
-pub async fn generate(
-    State(state): State<AppState>,
-    Json(request): Json<GenerateRequest>,
-) -> std::result::Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>, ExsaError>
-{
-    // Log prompt length instead of full content for security/privacy
-    info!(
-        "Received generation request with prompt length: {} chars",
-        request.prompt.len()
-    );
+/// A request ready for its model's queue
+pub(crate) struct PreparedRequest {
+    pub served: Arc<ServedModel>,
+    pub prompt: String,
+    pub sampling_params: SamplingParams,
+}
 
+/// Validate a `/v1/generate` request, route it and apply the chat template
+pub(crate) async fn prepare_generate(
+    state: &AppState,
+    request: &GenerateRequest,
+) -> std::result::Result<PreparedRequest, ExsaError> {
     // Validate request
     if request.prompt.is_empty() {
         return Err(ExsaError::InvalidParameters(
@@ -94,9 +110,17 @@ pub async fn generate(
         ));
     }
 
+    // Route to the requested resident model (default model when unspecified)
+    let served = state.router.resolve(request.model.as_deref()).await?;
+
+    // Per-model sampling defaults (models manifest) fill the fields the request leaves out
+    let manifest_entry = state.router.manifest_entry(&served);
+    let request_params =
+        request.to_sampling_params_with(manifest_entry.as_ref().map(|entry| &entry.sampling))?;
+
     // Validate prompt length (rough estimate: 4 chars per token)
     let estimated_prompt_tokens = request.prompt.len() / 4;
-    let context_size = state.engine.model_info().context_size;
+    let context_size = served.engine.model_info().context_size;
 
     if estimated_prompt_tokens > context_size {
         return Err(ExsaError::InvalidParameters(format!(
@@ -106,32 +130,34 @@ pub async fn generate(
     }
 
     // Validate max_tokens + prompt doesn't exceed context
-    if estimated_prompt_tokens + request.sampling_params.max_tokens > context_size {
+    if estimated_prompt_tokens + request_params.max_tokens > context_size {
         return Err(ExsaError::InvalidParameters(format!(
             "Prompt ({} tokens) + max_tokens ({}) exceeds context size ({})",
-            estimated_prompt_tokens, request.sampling_params.max_tokens, context_size
+            estimated_prompt_tokens, request_params.max_tokens, context_size
         )));
     }
 
-    if let Err(e) = request.sampling_params.validate() {
+    if let Err(e) = request_params.validate() {
         return Err(ExsaError::InvalidParameters(e.to_string()));
     }
 
+    // Fail fast on unknown LoRA adapters (before queueing)
+    served.engine.resolve_loras(&request_params.lora)?;
+
     // Apply chat template if enabled (fixes 24-token bug)
-    use crate::inference::templates::{apply_chat_template, create_single_message, TemplateType};
+    use crate::inference::templates::create_single_message;
 
-    let (formatted_prompt, sampling_params) = if request.use_chat_template.unwrap_or(true) {
-        // Auto-detect template type from model
-        let model_path = state.engine.model_info().model_path;
-        let template_type = TemplateType::from_model_name(&model_path);
+    let (prompt, sampling_params) = if request.use_chat_template.unwrap_or(true) {
+        // Manifest chat_template, else the GGUF-embedded template, else auto-detected
+        let template = state.router.template_for(&served);
 
         // Convert prompt to chat message and apply template
         let messages = create_single_message("user", &request.prompt);
-        let formatted = apply_chat_template(&messages, template_type);
+        let formatted = template.render(&messages, None, None);
 
         // Add template-specific stop sequences
-        let mut params = request.sampling_params.clone();
-        let template_stops = template_type.stop_sequences();
+        let mut params = request_params.clone();
+        let template_stops = template.stop_sequences();
 
         // Merge with user-provided stop sequences, avoiding duplicates
         for stop in template_stops {
@@ -142,80 +168,133 @@ pub async fn generate(
 
         info!(
             "Applied {:?} template to prompt with stop sequences: {:?}",
-            template_type, params.stop_sequences
+            template, params.stop_sequences
         );
         (formatted, params)
     } else {
-        (request.prompt.clone(), request.sampling_params.clone())
+        (request.prompt.clone(), request_params.clone())
     };
 
+    Ok(PreparedRequest {
+        served,
+        prompt,
+        sampling_params,
+    })
+}
+
+/// Generate text handler with SSE streaming
+pub async fn generate(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<GenerateRequest>,
+) -> std::result::Result<
+    (
+        [(&'static str, String); 1],
+        Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>,
+    ),
+    ExsaError,
+> {
+    // Log prompt length instead of full content for security/privacy
+    info!(
+        "Received generation request with prompt length: {} chars",
+        request.prompt.len()
+    );
+
+    // A reconnecting client resumes its earlier stream instead of submitting again
+    let mode = QueueEventMode::for_request(&headers);
+    if let Some((id, after)) = last_event_id(&headers) {
+        info!("Resuming stream of request {} after chunk {}", id, after);
+        let stream = state.streams.subscribe(id, after, mode)?;
+        return Ok(sse_response(id, stream));
+    }
+
+    let PreparedRequest {
+        served,
+        prompt: formatted_prompt,
+        mut sampling_params,
+    } = prepare_generate(&state, &request).await?;
+
+    // Scheduling class from X-Priority / API key (falls back to sampling_params.priority)
+    if let Some(priority) = state.priorities.resolve(&headers) {
+        sampling_params.priority = priority;
+    }
+    sampling_params.user = client_id(&headers, sampling_params.user.as_deref());
+    sampling_params.deadline_ms = deadline_ms(&headers, sampling_params.deadline_ms);
+
     // Submit request to queue with formatted prompt
-    let queued_request = state
+    let queued_request = served
         .queue
         .submit(formatted_prompt, sampling_params)
-        .await
-        .map_err(|_| ExsaError::QueueFull)?;
+        .await?;
 
     info!("Request {} queued successfully", queued_request.id);
 
-    // Create SSE stream from token receiver
-    let token_stream = ReceiverStream::new(queued_request.token_rx).map(|token_response| {
+    // SSE data from the token receiver, buffered so a dropped client can resume
+    let chunks = ReceiverStream::new(queued_request.token_rx).map(|token_response| {
         let event = TokenEvent {
             token: token_response.token,
             done: token_response.done,
         };
 
-        let json = serde_json::to_string(&event).unwrap_or_else(|e| {
+        serde_json::to_string(&event).unwrap_or_else(|e| {
             error!("Failed to serialize token event: {}", e);
             "{}".to_string()
-        });
-
-        Ok(Event::default().data(json))
+        })
     });
+    state.streams.register(
+        queued_request.id,
+        served.queue.clone(),
+        queued_request.cancellation_token.clone(),
+        chunks,
+        queued_request.completion_rx,
+    );
 
-    Ok(Sse::new(token_stream).keep_alive(KeepAlive::default()))
+    // Optional queue position events until the request starts
+    let stream = state.streams.subscribe(queued_request.id, 0, mode)?;
+    Ok(sse_response(queued_request.id, stream))
 }
 
-/// OpenAI-compatible chat completions endpoint
-pub async fn chat_completions(
-    State(state): State<AppState>,
-    Json(request): Json<ChatCompletionRequest>,
-) -> std::result::Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>, ExsaError>
-{
-    info!(
-        "Received OpenAI chat completion request with {} messages",
-        request.messages.len()
-    );
-
-    // Validate request
-    if request.messages.is_empty() {
-        return Err(ExsaError::InvalidParameters(
-            "Messages cannot be empty".to_string(),
-        ));
+/// System prompt used when a chat request has none: the model's manifest prompt,
+/// else EXSA_DEFAULT_SYSTEM_PROMPT, else a short built-in one
+pub(crate) fn default_system_prompt(model_prompt: Option<&str>) -> String {
+    // Per-model prompt from the models manifest wins over the global one
+    if let Some(s) = model_prompt.map(str::trim).filter(|s| !s.is_empty()) {
+        return s.to_string();
     }
 
-    // Ensure we always have a stable base system prompt.
-    // Many OpenAI-compatible clients omit a system message; without one, small local models
-    // can drift in identity/language and become inconsistent across turns.
-    use crate::inference::templates::{apply_chat_template, TemplateType};
-
-    fn default_system_prompt() -> String {
-        if let Ok(v) = std::env::var("EXSA_DEFAULT_SYSTEM_PROMPT") {
-            let s = v.trim().to_string();
-            if !s.is_empty() {
-                return s;
-            }
+    if let Ok(v) = std::env::var("EXSA_DEFAULT_SYSTEM_PROMPT") {
+        let s = v.trim().to_string();
+        if !s.is_empty() {
+            return s;
         }
+    }
 
-        // Keep this short and directive for small models.
-        "You are EXSA, a helpful AI assistant.\n\
+    // Keep this short and directive for small models.
+    "You are EXSA, a helpful AI assistant.\n\
 Answer clearly and accurately.\n\
 Stay consistent about who you are. Do not invent alternate names.\n\
 Reply in the same language as the user unless asked otherwise.\n\
 If you are unsure or lack information, say so instead of guessing."
-            .to_string()
+        .to_string()
+}
+
+/// Validate a chat request, route it and build its prompt (system prompt,
+/// file and RAG context, trimming, chat template)
+pub(crate) async fn prepare_chat(
+    state: &AppState,
+    request: &ChatCompletionRequest,
+) -> std::result::Result<PreparedRequest, ExsaError> {
+    // Validate request
+    if request.messages.is_empty() {
+        return Err(ExsaError::InvalidParameters(
+            "Messages cannot be empty".to_string(),
+        ));
     }
 
+    // Route to the requested resident model (unknown names fall back to the default model)
+    let served = state.router.resolve(Some(&request.model)).await?;
+    let manifest_entry = state.router.manifest_entry(&served);
+
     fn estimate_tokens(text: &str) -> usize {
         (text.len() / 4).max(1)
     }
@@ -324,12 +403,24 @@ If the file does not contain the answer, say so instead of guessing.\n\n",
 
     let mut messages = request.messages.clone();
 
+    // Reasoning from earlier assistant turns is not sent back to the model
+    for m in messages.iter_mut().filter(|m| m.role == "assistant") {
+        m.content = strip_reasoning(&m.content);
+    }
+
+    // Ensure we always have a stable base system prompt.
+    // Many OpenAI-compatible clients omit a system message; without one, small local models
+    // can drift in identity/language and become inconsistent across turns.
     if !messages.iter().any(|m| m.role == "system") {
         messages.insert(
             0,
             crate::inference::templates::ChatMessage {
                 role: "system".to_string(),
-                content: default_system_prompt(),
+                content: default_system_prompt(
+                    manifest_entry
+                        .as_ref()
+                        .and_then(|e| e.system_prompt.as_deref()),
+                ),
             },
         );
     }
@@ -485,7 +576,7 @@ Do NOT guess its contents. Ask the user to paste the relevant section or fix ser
     // Server-side conversation trimming (approximate) to avoid huge prompts and reduce
     // identity drift when the engine activates its sliding window.
     // Keep all system messages, plus the most recent non-system messages.
-    let context_limit = state.engine.model_info().context_size;
+    let context_limit = served.engine.model_info().context_size;
     let emergency_threshold = (context_limit as f32 * 0.95) as usize;
 
     let system_msgs: Vec<_> = messages
@@ -543,13 +634,19 @@ Do NOT guess its contents. Ask the user to paste the relevant section or fix ser
     // Add a small buffer for template tokens.
     n_keep_estimate = n_keep_estimate.saturating_add(32);
 
-    let model_path = state.engine.model_info().model_path;
-    let template_type = TemplateType::from_model_name(&model_path);
-    let formatted_prompt = apply_chat_template(&trimmed_messages, template_type);
+    let template = state.router.template_for(&served);
+    let formatted_prompt = template.render(
+        &trimmed_messages,
+        request.tools.as_ref(),
+        request.enable_thinking,
+    );
 
-    // Convert to sampling parameters and add template stop sequences
-    let mut sampling_params = request.to_sampling_params();
-    let template_stops = template_type.stop_sequences();
+    // Convert to sampling parameters (request, then manifest defaults) and add template stop sequences
+    let mut sampling_params = match &manifest_entry {
+        Some(entry) => request.to_sampling_params_with(&entry.sampling),
+        None => request.to_sampling_params(),
+    };
+    let template_stops = template.stop_sequences();
 
     // Merge with user-provided stop sequences, avoiding duplicates
     for stop in template_stops {
@@ -562,7 +659,7 @@ Do NOT guess its contents. Ask the user to paste the relevant section or fix ser
 
     info!(
         "Applied {:?} template to {} messages with stop sequences: {:?}",
-        template_type,
+        template,
         trimmed_messages.len(),
         sampling_params.stop_sequences
     );
@@ -571,40 +668,97 @@ Do NOT guess its contents. Ask the user to paste the relevant section or fix ser
     sampling_params
         .validate()
         .map_err(|e| ExsaError::InvalidParameters(e.to_string()))?;
+    served.engine.resolve_loras(&sampling_params.lora)?;
+
+    Ok(PreparedRequest {
+        served,
+        prompt: formatted_prompt,
+        sampling_params,
+    })
+}
+
+/// OpenAI-compatible chat completions endpoint
+pub async fn chat_completions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<ChatCompletionRequest>,
+) -> std::result::Result<
+    (
+        [(&'static str, String); 1],
+        Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>,
+    ),
+    ExsaError,
+> {
+    info!(
+        "Received OpenAI chat completion request with {} messages",
+        request.messages.len()
+    );
+
+    // A reconnecting client resumes its earlier stream instead of submitting again
+    let mode = QueueEventMode::for_request(&headers);
+    if let Some((id, after)) = last_event_id(&headers) {
+        info!("Resuming stream of request {} after chunk {}", id, after);
+        let stream = state.streams.subscribe(id, after, mode)?;
+        return Ok(sse_response(id, stream));
+    }
+
+    let PreparedRequest {
+        served,
+        prompt: formatted_prompt,
+        mut sampling_params,
+    } = prepare_chat(&state, &request).await?;
+
+    // Split `<think>` output into reasoning_content (the prompt may already open the block)
+    let mut reasoning = ReasoningParser::new(prompt_opens_reasoning(&formatted_prompt));
+
+    // Scheduling class from X-Priority / API key; fair-share identity from API key or `user`
+    if let Some(priority) = state.priorities.resolve(&headers) {
+        sampling_params.priority = priority;
+    }
+    sampling_params.user = client_id(&headers, request.user.as_deref());
+    sampling_params.deadline_ms = deadline_ms(&headers, sampling_params.deadline_ms);
 
     // Submit request to queue
-    let queued_request = state
+    let queued_request = served
         .queue
         .submit(formatted_prompt, sampling_params)
-        .await
-        .map_err(|_| ExsaError::QueueFull)?;
+        .await?;
 
     let request_id = queued_request.id.to_string();
     info!("OpenAI request {} queued successfully", request_id);
 
-    // Create SSE stream for OpenAI-compatible responses
+    // SSE data for OpenAI-compatible chunks, buffered so a dropped client can resume
     let model_name = request.model.clone();
     let mut is_first = true;
 
-    let token_stream = ReceiverStream::new(queued_request.token_rx).map(move |token_response| {
+    let chunks = ReceiverStream::new(queued_request.token_rx).filter_map(move |token_response| {
         let chunk = if token_response.done {
-            // Final chunk with finish reason
+            // Final chunk with finish reason (and any held-back partial tag text)
+            let rest = reasoning.finish();
             ChatCompletionChunk::new(
                 request_id.clone(),
                 model_name.clone(),
-                None,
+                rest.content(),
                 Some("stop".to_string()),
                 false,
             )
+            .with_reasoning(rest.reasoning())
         } else {
-            // Regular content chunk
+            let delta = reasoning.feed(&token_response.token);
+            if delta.is_empty() {
+                // Only tag text, or a possible partial tag: nothing to send yet
+                return None;
+            }
+
+            // Regular content / reasoning chunk
             let chunk = ChatCompletionChunk::new(
                 request_id.clone(),
                 model_name.clone(),
-                Some(token_response.token.clone()),
+                delta.content(),
                 None,
                 is_first,
-            );
+            )
+            .with_reasoning(delta.reasoning());
             is_first = false;
             chunk
         };
@@ -614,10 +768,19 @@ Do NOT guess its contents. Ask the user to paste the relevant section or fix ser
             "{}".to_string()
         });
 
-        Ok(Event::default().data(json))
+        Some(json)
     });
+    state.streams.register(
+        queued_request.id,
+        served.queue.clone(),
+        queued_request.cancellation_token.clone(),
+        chunks,
+        queued_request.completion_rx,
+    );
 
-    Ok(Sse::new(token_stream).keep_alive(KeepAlive::default()))
+    // Optional queue position events until the request starts
+    let stream = state.streams.subscribe(queued_request.id, 0, mode)?;
+    Ok(sse_response(queued_request.id, stream))
 }
 
 /// OpenAI-compatible embeddings endpoint.
@@ -628,14 +791,7 @@ pub async fn embeddings(
     State(state): State<AppState>,
     Json(req): Json<EmbeddingsRequest>,
 ) -> std::result::Result<Json<EmbeddingsResponse>, ExsaError> {
-    use llama_cpp_2::model::AddBos;
-    use std::num::NonZero;
-    use std::path::Path;
-    use std::sync::OnceLock;
-
-    // llama.cpp backends (especially GPU/Metal) can be sensitive to concurrent context usage.
-    // Serialize embeddings to avoid hard crashes under load.
-    let _guard = state.embeddings_lock.lock().await;
+    use crate::inference::embeddings::{encode_base64, truncate_dimensions, PoolingMode};
 
     let inputs: Vec<String> = if let Some(s) = req.input.as_str() {
         vec![s.to_string()]
@@ -655,163 +811,73 @@ pub async fn embeddings(
         ));
     }
 
-    let engine_cfg = state.engine.current_model_config();
-    let backend = state.engine.llama_backend();
-
-    // IMPORTANT: llama.cpp embeddings on Metal has proven crash-prone on some setups.
-    // To make RAG reliable, run embeddings using a CPU-only model instance.
-    type CpuEmbedModelCache =
-        tokio::sync::Mutex<Option<(String, std::sync::Arc<llama_cpp_2::model::LlamaModel>)>>;
-
-    static CPU_EMBED_MODEL: OnceLock<CpuEmbedModelCache> = OnceLock::new();
-
-    let cpu_model_cache = CPU_EMBED_MODEL.get_or_init(|| tokio::sync::Mutex::new(None));
-
-    let mut need_reload = false;
-    {
-        let guard = cpu_model_cache.lock().await;
-        if let Some((path, _)) = guard.as_ref() {
-            if path != &engine_cfg.model_path {
-                need_reload = true;
-            }
-        } else {
-            need_reload = true;
+    let base64_output = match req.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => {
+            return Err(ExsaError::InvalidParameters(format!(
+                "Unsupported encoding_format '{}' (expected 'float' or 'base64')",
+                other
+            )))
         }
-    }
+    };
 
-    if need_reload {
-        let model_path = engine_cfg.model_path.clone();
-        let backend_clone = backend.clone();
-        let mut cpu_cfg = engine_cfg.clone();
-        cpu_cfg.n_gpu_layers = 0;
-        // embeddings can use a smaller batch; we'll size context separately per request
-        cpu_cfg.n_batch = cpu_cfg.n_batch.clamp(64, 512);
-
-        // Move only the load config into the blocking task; keep `cpu_cfg` for context params.
-        let cpu_cfg_for_load = cpu_cfg.clone();
-
-        let loaded = tokio::task::spawn_blocking(move || {
-            llama_cpp_2::model::LlamaModel::load_from_file(
-                &backend_clone,
-                Path::new(&model_path),
-                &cpu_cfg_for_load.into_params(),
-            )
-            .map(std::sync::Arc::new)
-        })
-        .await
-        .map_err(|e| ExsaError::InternalError(format!("Embeddings model load join failed: {e}")))?
-        .map_err(|e| ExsaError::InternalError(format!("Embeddings model load failed: {e}")))?;
+    let pooling = match req.pooling.as_deref() {
+        None => None,
+        Some(p) => Some(PoolingMode::parse(p).ok_or_else(|| {
+            ExsaError::InvalidParameters(format!(
+                "Unsupported pooling '{}' (expected model, mean, cls or last)",
+                p
+            ))
+        })?),
+    };
 
-        let mut guard = cpu_model_cache.lock().await;
-        *guard = Some((engine_cfg.model_path.clone(), loaded));
+    if req.dimensions == Some(0) {
+        return Err(ExsaError::InvalidParameters(
+            "dimensions must be greater than 0".to_string(),
+        ));
     }
 
-    let model = {
-        let guard = cpu_model_cache.lock().await;
-        guard
-            .as_ref()
-            .map(|(_, m)| m.clone())
-            .ok_or_else(|| ExsaError::InternalError("Embeddings model cache empty".to_string()))?
-    };
+    let normalize = req.normalize.unwrap_or(false);
 
-    // Tokenize first so we can size the embeddings context appropriately.
-    // This avoids allocating a huge KV cache (e.g. 8192 ctx) just to embed short strings.
-    let mut tokenized: Vec<Vec<llama_cpp_2::token::LlamaToken>> = Vec::with_capacity(inputs.len());
-    let mut max_tokens = 0usize;
-    for input in &inputs {
-        let tokens = model
-            .str_to_token(input, AddBos::Never)
-            .map_err(|e| ExsaError::InvalidParameters(format!("Tokenization failed: {e}")))?;
-        max_tokens = max_tokens.max(tokens.len());
-        tokenized.push(tokens);
-    }
+    // llama.cpp backends (especially GPU/Metal) can be sensitive to concurrent context usage.
+    // Serialize embeddings to avoid hard crashes under load.
+    let _guard = state.embeddings_lock.lock().await;
 
-    // IMPORTANT: build embeddings context params from a CPU-only config.
-    // Using the engine's GPU config here can cause Metal crashes/segfaults.
-    let mut cpu_cfg = engine_cfg.clone();
-    cpu_cfg.n_gpu_layers = 0;
-    cpu_cfg.n_batch = cpu_cfg.n_batch.clamp(64, 512);
-
-    let desired_ctx = (max_tokens + 8).clamp(64, cpu_cfg.n_ctx as usize);
-    let desired_batch = (max_tokens).clamp(1, cpu_cfg.n_batch as usize).min(512);
-
-    // Create an embeddings-enabled context sized to this request and reuse it for all inputs.
-    let threads = (cpu_cfg.n_threads as i32).max(1);
-    let ctx_params = cpu_cfg
-        .into_context_params()
-        .with_n_ctx(NonZero::new(desired_ctx as u32))
-        .with_n_batch(desired_batch as u32)
-        .with_embeddings(true)
-        .with_n_threads(threads)
-        .with_n_threads_batch(threads);
-
-    let mut ctx = model.new_context(&backend, ctx_params).map_err(|e| {
-        ExsaError::InternalError(format!("Failed to create embeddings context: {e}"))
-    })?;
-
-    let mut out = Vec::with_capacity(inputs.len());
-    let mut total_tokens = 0usize;
-
-    for (index, tokens) in tokenized.iter().enumerate() {
-        ctx.clear_kv_cache();
-
-        if tokens.is_empty() {
-            out.push(EmbeddingItem {
+    let embedder = state
+        .embeddings
+        .resolve(&state.engine, req.model.as_deref())
+        .await?;
+
+    let worker = embedder.clone();
+    let (vectors, total_tokens) =
+        tokio::task::spawn_blocking(move || worker.embed(&inputs, pooling, normalize))
+            .await
+            .map_err(|e| ExsaError::InternalError(format!("Embeddings task failed: {e}")))??;
+
+    let data = vectors
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut vector)| {
+            if let Some(dimensions) = req.dimensions {
+                truncate_dimensions(&mut vector, dimensions, normalize);
+            }
+            EmbeddingItem {
                 object: "embedding".to_string(),
                 index,
-                embedding: vec![],
-            });
-            continue;
-        }
-
-        total_tokens += tokens.len();
-
-        let mut batch = llama_cpp_2::llama_batch::LlamaBatch::new(tokens.len(), 1);
-        batch
-            .add_sequence(tokens, 0, true)
-            .map_err(|e| ExsaError::InternalError(format!("Batch build failed: {e}")))?;
-
-        ctx.encode(&mut batch)
-            .map_err(|e| ExsaError::InternalError(format!("Embeddings encode failed: {e}")))?;
-
-        // Pool token embeddings by averaging across all tokens.
-        let n_embd = model.n_embd() as usize;
-        let mut pooled = vec![0.0f32; n_embd];
-
-        for i in 0..tokens.len() {
-            let emb = ctx
-                .embeddings_ith(i as i32)
-                .map_err(|e| ExsaError::InternalError(format!("Embeddings read failed: {e}")))?;
-            for (dst, src) in pooled.iter_mut().zip(emb.iter()) {
-                *dst += *src;
+                embedding: if base64_output {
+                    EmbeddingValue::Base64(encode_base64(&vector))
+                } else {
+                    EmbeddingValue::Float(vector)
+                },
             }
-        }
-
-        let denom = tokens.len() as f32;
-        for v in &mut pooled {
-            *v /= denom;
-        }
-
-        out.push(EmbeddingItem {
-            object: "embedding".to_string(),
-            index,
-            embedding: pooled,
-        });
-    }
-
-    let model_name = state
-        .engine
-        .model_info()
-        .model_path
-        .rsplit('/')
-        .next()
-        .unwrap_or("exsa-model")
-        .to_string();
+        })
+        .collect();
 
     Ok(Json(EmbeddingsResponse {
         object: "list".to_string(),
-        model: model_name,
-        data: out,
+        model: embedder.name().to_string(),
+        data,
         usage: Some(EmbeddingsUsage {
             prompt_tokens: total_tokens,
             total_tokens,
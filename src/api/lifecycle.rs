@@ -1,16 +1,26 @@
 //! Model lifecycle management API
 
 use crate::api::schema::{AppState, ModelInfo};
+use crate::inference::{LoadJob, LoadJobHandle, LoadJobStatus};
+use crate::model::planner::{plan_model, MemoryPlan, PlanOptions};
+use crate::model::{KvCacheQuantization, ModelConfig, ModelEntry, RopeScalingType};
 use crate::utils::error::{ExsaError, Result};
 use axum::{
-    extract::{Json, State},
+    extract::{Json, Path, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
 };
+use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
 use std::path::PathBuf;
+use tracing::{info, warn};
 
-fn resolve_models_dir() -> Result<PathBuf> {
+/// Resolve the models directory (MODELS_DIR, ./models or ../models)
+pub fn resolve_models_dir() -> Result<PathBuf> {
     // Prefer explicit configuration
     if let Ok(dir) = std::env::var("MODELS_DIR") {
         let p = PathBuf::from(dir);
@@ -55,10 +65,37 @@ fn resolve_model_path(models_dir: &PathBuf, raw: &str) -> Result<PathBuf> {
     Ok(canon)
 }
 
+/// Resolve a load target to a model file and its manifest entry.
+///
+/// Manifest names/aliases resolve to their declared file; otherwise only GGUF models
+/// from the local ./models directory are allowed (matches /v1/models/list and prevents
+/// arbitrary path access).
+fn resolve_load_target(state: &AppState, raw: &str) -> Result<(PathBuf, Option<ModelEntry>)> {
+    let manifest = state.router.manifest();
+
+    if let Some(entry) = manifest.as_ref().and_then(|m| m.find(raw)) {
+        return Ok((entry.path.clone(), Some(entry.clone())));
+    }
+
+    if !raw.to_lowercase().ends_with(".gguf") {
+        return Err(ExsaError::InvalidParameters(
+            "Only .gguf models are supported".to_string(),
+        ));
+    }
+
+    let models_dir = resolve_models_dir()?;
+    let target_path = resolve_model_path(&models_dir, raw)?;
+    let entry = manifest
+        .as_ref()
+        .and_then(|m| m.find_by_path(&target_path.to_string_lossy()))
+        .cloned();
+    Ok((target_path, entry))
+}
+
 /// Load model request
 #[derive(Debug, Deserialize)]
 pub struct LoadModelRequest {
-    /// Path to GGUF model file
+    /// Path to GGUF model file, or a model name/alias from the models manifest
     pub model_path: String,
 
     /// Number of GPU layers (optional)
@@ -66,6 +103,43 @@ pub struct LoadModelRequest {
 
     /// Context size (optional)
     pub context_size: Option<usize>,
+
+    /// Memory-map the model file (optional)
+    #[serde(default)]
+    pub use_mmap: Option<bool>,
+
+    /// Lock model memory in RAM (optional)
+    #[serde(default)]
+    pub use_mlock: Option<bool>,
+
+    /// RoPE scaling type: none, linear, yarn (optional)
+    #[serde(default)]
+    pub rope_scaling_type: Option<RopeScalingType>,
+
+    /// RoPE scale factor, e.g. 4.0 for 4x the trained context (optional)
+    #[serde(default)]
+    pub rope_scale_factor: Option<f32>,
+
+    /// RoPE frequency base override (optional, 0 = model default)
+    #[serde(default)]
+    pub rope_freq_base: Option<f32>,
+
+    /// Load alongside the current models instead of switching the default model
+    #[serde(default)]
+    pub resident: bool,
+
+    /// Name requests use to select a resident model (default: file stem)
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// Additional names routed to a resident model
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// Return a job id right away and load while the current model keeps serving
+    /// (progress at `/v1/models/load/:job_id`)
+    #[serde(default)]
+    pub background: bool,
 }
 
 /// Load model response
@@ -80,27 +154,169 @@ pub struct LoadModelResponse {
 #[derive(Debug, Serialize)]
 pub struct ListModelsResponse {
     pub models: Vec<String>,
+
+    /// Per-file GGUF header metadata, same order as `models`
+    pub details: Vec<ModelFileDetails>,
+}
+
+/// Catalog entry for a model file
+#[derive(Debug, Serialize)]
+pub struct ModelFileDetails {
+    pub path: String,
+    pub size_bytes: u64,
+
+    #[serde(flatten)]
+    pub gguf: Option<crate::model::GgufInfo>,
+
+    /// Why the header could not be read (corrupt or truncated file)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Memory-fit plan request (dry run, nothing is loaded)
+#[derive(Debug, Deserialize)]
+pub struct PlanModelRequest {
+    /// Path to GGUF model file, or a model name/alias from the models manifest
+    pub model_path: String,
+
+    /// Largest context to consider (default: the manifest's, else the trained context)
+    pub context_size: Option<u32>,
+
+    /// Use this KV cache type instead of choosing one
+    pub kv_cache_type: Option<KvCacheQuantization>,
+
+    /// Batch size (default: 2048, capped at the planned context)
+    pub batch_size: Option<u32>,
+
+    /// Plan against this much memory instead of what is available now
+    pub memory_mb: Option<u64>,
+
+    /// Plan GPU offload against this much VRAM
+    pub vram_mb: Option<u64>,
+}
+
+/// Plan context size and KV cache type for a model without loading it
+pub async fn plan_model_load(
+    State(state): State<AppState>,
+    Json(request): Json<PlanModelRequest>,
+) -> Result<Json<MemoryPlan>> {
+    let (target_path, entry) = resolve_load_target(&state, &request.model_path)?;
+
+    let defaults = PlanOptions::from_env();
+    let options = PlanOptions {
+        max_context: request
+            .context_size
+            .or_else(|| entry.as_ref().and_then(|e| e.context_size)),
+        kv_cache_type: request
+            .kv_cache_type
+            .or_else(|| entry.as_ref().and_then(|e| e.kv_cache_type)),
+        n_batch: request.batch_size.unwrap_or(defaults.n_batch),
+        memory_budget_bytes: request
+            .memory_mb
+            .map(|mb| mb.saturating_mul(1024 * 1024))
+            .or(defaults.memory_budget_bytes),
+        vram_budget_bytes: request
+            .vram_mb
+            .map(|mb| mb.saturating_mul(1024 * 1024))
+            .or(defaults.vram_budget_bytes),
+        ..defaults
+    };
+
+    let plan = tokio::task::spawn_blocking(move || plan_model(&target_path, &options))
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("Plan task failed: {}", e)))??;
+
+    Ok(Json(plan))
+}
+
+/// Build the config for switching to `target_path`: the active runtime settings,
+/// then the manifest entry, then the request's explicit overrides
+fn switch_config(
+    state: &AppState,
+    target_path: &std::path::Path,
+    entry: Option<&ModelEntry>,
+    request: &LoadModelRequest,
+) -> ModelConfig {
+    let mut cfg = state.engine.current_model_config();
+    // Use canonical absolute path for engine stability
+    cfg.model_path = target_path.to_string_lossy().to_string();
+    if let Some(entry) = entry {
+        entry.apply_to_config(&mut cfg);
+    }
+    if let Some(gl) = request.gpu_layers.filter(|gl| *gl >= 0) {
+        cfg = cfg.with_gpu_layers(gl as u32);
+    }
+    if let Some(cs) = request.context_size {
+        cfg = cfg.with_context_size(cs as u32);
+    }
+    if let Some(mmap) = request.use_mmap {
+        cfg = cfg.with_mmap(mmap);
+    }
+    if let Some(mlock) = request.use_mlock {
+        cfg = cfg.with_mlock(mlock);
+    }
+    if let Some(scaling) = request.rope_scaling_type {
+        cfg.rope_scaling_type = scaling;
+    }
+    if let Some(factor) = request.rope_scale_factor {
+        cfg.rope_scale_factor = factor;
+    }
+    if let Some(base) = request.rope_freq_base {
+        cfg = cfg.with_rope_freq_base(base);
+    }
+    cfg
 }
 
 /// Load a model from disk
 pub async fn load_model(
     State(state): State<AppState>,
     Json(request): Json<LoadModelRequest>,
-) -> Result<impl IntoResponse> {
+) -> Result<Response> {
+    let (target_path, entry) = resolve_load_target(&state, &request.model_path)?;
+
+    if request.background {
+        if request.resident {
+            return Err(ExsaError::InvalidParameters(
+                "background loading applies to model switches, not resident loads".to_string(),
+            ));
+        }
+        let cfg = switch_config(&state, &target_path, entry.as_ref(), &request);
+        let job = start_load_job(&state, cfg);
+        return Ok((StatusCode::ACCEPTED, Json(job)).into_response());
+    }
+
     // Serialize model switching across requests
     let _guard = state.model_switch_lock.lock().await;
 
-    // Only allow loading GGUF models from the local ./models directory
-    // (matches /v1/models/list and prevents arbitrary path access).
-    if !request.model_path.to_lowercase().ends_with(".gguf") {
-        return Err(ExsaError::InvalidParameters(
-            "Only .gguf models are supported".to_string(),
-        ));
+    if request.resident {
+        let name = request
+            .name
+            .clone()
+            .or_else(|| entry.as_ref().map(|e| e.name.clone()))
+            .unwrap_or_else(|| {
+                target_path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("model")
+                    .to_string()
+            });
+        let served = state
+            .router
+            .load(
+                name.clone(),
+                target_path.to_string_lossy().to_string(),
+                request.aliases.clone(),
+            )
+            .await?;
+
+        let response = LoadModelResponse {
+            success: true,
+            message: format!("Model resident as '{}'", name),
+            model_info: Some(served.engine.model_info()),
+        };
+        return Ok((StatusCode::OK, Json(response)).into_response());
     }
 
-    let models_dir = resolve_models_dir()?;
-    let target_path = resolve_model_path(&models_dir, &request.model_path)?;
-
     // Refuse switching while there are queued requests (avoid user-perceived "random" latency)
     if state.queue.pending_count() > 0 {
         return Err(ExsaError::InvalidParameters(
@@ -108,17 +324,14 @@ pub async fn load_model(
         ));
     }
 
-    let engine = state.engine.clone();
-    // Use canonical absolute path for engine stability
-    let model_path = target_path.to_string_lossy().to_string();
-    let gpu_layers = request.gpu_layers;
-    let context_size = request.context_size;
+    let cfg = switch_config(&state, &target_path, entry.as_ref(), &request);
+    let warmup = WarmupSettings::from_env();
 
-    let info = tokio::task::spawn_blocking(move || {
-        engine.load_and_switch_model(model_path, gpu_layers, context_size)
-    })
-    .await
-    .map_err(|e| ExsaError::InternalError(format!("Model switch task failed: {}", e)))??;
+    let switch_state = state.clone();
+    let info =
+        tokio::task::spawn_blocking(move || load_warm_and_switch(&switch_state, cfg, warmup, None))
+            .await
+            .map_err(|e| ExsaError::InternalError(format!("Model switch task failed: {}", e)))??;
 
     let response = LoadModelResponse {
         success: true,
@@ -126,17 +339,265 @@ pub async fn load_model(
         model_info: Some(info),
     };
 
-    Ok((StatusCode::OK, Json(response)))
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+/// Text decoded by the warm-up when no system prompt is pre-filled
+const WARMUP_PROMPT: &str = "Hello";
+
+/// Optional warm-up after a model is loaded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WarmupSettings {
+    /// Run a one-token decode before the model takes traffic (EXSA_WARMUP)
+    pub enabled: bool,
+
+    /// Also pre-fill the default system prompt into the KV cache (EXSA_WARMUP_SYSTEM_PROMPT)
+    pub prefill_system_prompt: bool,
+}
+
+impl WarmupSettings {
+    pub fn from_env() -> Self {
+        let flag = |name: &str| {
+            std::env::var(name)
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false)
+        };
+        let prefill_system_prompt = flag("EXSA_WARMUP_SYSTEM_PROMPT");
+        Self {
+            enabled: flag("EXSA_WARMUP") || prefill_system_prompt,
+            prefill_system_prompt,
+        }
+    }
+}
+
+/// Prompt for warming up the default model: its rendered default system prompt
+/// when pre-filling, so chat requests reuse it from the KV cache
+pub fn warmup_prompt(state: &AppState, warmup: WarmupSettings) -> String {
+    if !warmup.prefill_system_prompt {
+        return WARMUP_PROMPT.to_string();
+    }
+
+    let served = state.router.default_model();
+    let system_prompt = crate::api::handlers::default_system_prompt(
+        state
+            .router
+            .manifest_entry(&served)
+            .as_ref()
+            .and_then(|e| e.system_prompt.as_deref()),
+    );
+    let message = crate::inference::templates::ChatMessage {
+        role: "system".to_string(),
+        content: system_prompt,
+    };
+    state
+        .router
+        .template_for(&served)
+        .render(&[message], None, None)
+}
+
+/// Load `cfg` while the current model keeps serving, then switch to it and warm
+/// it up before it serves any request.
+///
+/// Blocking. A failed warm-up is logged and the switch goes ahead.
+pub fn load_warm_and_switch(
+    state: &AppState,
+    cfg: ModelConfig,
+    warmup: WarmupSettings,
+    job: Option<LoadJobHandle>,
+) -> Result<ModelInfo> {
+    let progress = job.clone();
+    let name = state.engine.preload(&cfg, move |p| {
+        if let Some(job) = &progress {
+            job.set_progress(p);
+        }
+    })?;
+
+    if !warmup.enabled {
+        return state.engine.activate(&name, cfg);
+    }
+    if let Some(job) = &job {
+        job.set_status(LoadJobStatus::Warming);
+    }
+    // The system prompt is rendered with the new model's template, so after the switch
+    state
+        .engine
+        .activate_and_warm_up(&name, cfg, || warmup_prompt(state, warmup))
+}
+
+/// Register a load job and run it in the background.
+///
+/// The job waits for earlier loads/switches, then loads the model while the current
+/// one keeps serving, and switches to it (warming it up first, with EXSA_WARMUP).
+fn start_load_job(state: &AppState, cfg: ModelConfig) -> LoadJob {
+    let job = state.load_jobs.create(cfg.model_path.clone());
+    let snapshot = job.snapshot();
+
+    let state = state.clone();
+    let warmup = WarmupSettings::from_env();
+    tokio::spawn(async move {
+        let lock = state.model_switch_lock.clone();
+        let _guard = lock.lock().await;
+        job.set_status(LoadJobStatus::Loading);
+        info!(
+            "📦 Background load {} started: {}",
+            job.id(),
+            cfg.model_path
+        );
+
+        let switch_state = state.clone();
+        let handle = job.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            load_warm_and_switch(&switch_state, cfg, warmup, Some(handle))
+        })
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("Model switch task failed: {}", e)))
+        .and_then(|r| r);
+
+        match result {
+            Ok(info) => {
+                info!(
+                    "✅ Background load {} switched to {}",
+                    job.id(),
+                    info.model_path
+                );
+                job.complete(info);
+            }
+            Err(e) => {
+                warn!("Background load {} failed: {}", job.id(), e);
+                job.fail(e.to_string());
+            }
+        }
+    });
+
+    snapshot
+}
+
+fn parse_job_id(job_id: &str) -> Result<uuid::Uuid> {
+    uuid::Uuid::parse_str(job_id)
+        .map_err(|_| ExsaError::InvalidParameters(format!("Invalid load job id: {}", job_id)))
+}
+
+/// Recent background load jobs, oldest first
+pub async fn list_load_jobs(State(state): State<AppState>) -> Json<Vec<LoadJob>> {
+    Json(state.load_jobs.list())
+}
+
+/// Status of a background load job
+pub async fn get_load_job(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<LoadJob>> {
+    let id = parse_job_id(&job_id)?;
+    state
+        .load_jobs
+        .get(id)
+        .map(Json)
+        .ok_or_else(|| ExsaError::InvalidParameters(format!("Unknown load job: {}", job_id)))
+}
+
+/// Stream a background load job's progress as SSE `progress` events until it finishes
+pub async fn load_job_events(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let id = parse_job_id(&job_id)?;
+    let mut rx = state
+        .load_jobs
+        .subscribe(id)
+        .ok_or_else(|| ExsaError::InvalidParameters(format!("Unknown load job: {}", job_id)))?;
+
+    let stream = async_stream::stream! {
+        loop {
+            let job = rx.borrow_and_update().clone();
+            let finished = job.status.is_finished();
+            let event = Event::default()
+                .event("progress")
+                .json_data(&job)
+                .unwrap_or_else(|_| Event::default().event("progress"));
+            yield Ok(event);
+
+            if finished || rx.changed().await.is_err() {
+                break;
+            }
+        }
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
 }
 
-/// Unload the currently active model
-pub async fn unload_model(State(_state): State<AppState>) -> Result<impl IntoResponse> {
+/// Unload the currently active model and free its memory.
+///
+/// Requests already dispatched to the inference thread finish first; queued and
+/// new requests get 503 until a model is loaded again via `/v1/models/load`.
+pub async fn unload_model(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    let _guard = state.model_switch_lock.lock().await;
+
+    let engine = state.engine.clone();
+    let unloaded = tokio::task::spawn_blocking(move || engine.unload())
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("Model unload task failed: {}", e)))??;
+
     let response = LoadModelResponse {
-        success: false,
-        message: "Unload is not supported via API at the moment".to_string(),
+        success: true,
+        message: match unloaded {
+            Some(name) => format!("Model unloaded: {}", name),
+            None => "No model was loaded".to_string(),
+        },
         model_info: None,
     };
-    Ok((StatusCode::BAD_REQUEST, Json(response)))
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Idle time after which the default model is unloaded (EXSA_IDLE_UNLOAD_SECS, unset or 0 = never)
+pub fn idle_unload_ttl() -> Option<std::time::Duration> {
+    std::env::var("EXSA_IDLE_UNLOAD_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(std::time::Duration::from_secs)
+}
+
+/// Unload the default model once it has had no requests for `ttl`.
+///
+/// The next request reloads it transparently (`/v1/health` reports `idle`, then
+/// `loading`). Skipped while requests are queued or a load/switch is running.
+pub fn spawn_idle_unloader(
+    state: AppState,
+    ttl: std::time::Duration,
+) -> tokio::task::JoinHandle<()> {
+    let period = (ttl / 4).clamp(
+        std::time::Duration::from_secs(1),
+        std::time::Duration::from_secs(30),
+    );
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if state
+                .shutdown_flag
+                .load(std::sync::atomic::Ordering::Relaxed)
+            {
+                break;
+            }
+            if state.queue.pending_count() > 0 {
+                continue;
+            }
+            let Ok(_guard) = state.model_switch_lock.try_lock() else {
+                continue;
+            };
+
+            match state.engine.unload_if_idle(ttl).await {
+                Ok(Some(name)) => info!(
+                    "💤 Unloaded idle model {} after {}s without requests",
+                    name,
+                    ttl.as_secs()
+                ),
+                Ok(None) => {}
+                Err(e) => warn!("Idle unload failed: {}", e),
+            }
+        }
+    })
 }
 
 /// Reload the currently active model
@@ -183,7 +644,13 @@ pub async fn list_models() -> Result<impl IntoResponse> {
     let mut entries = match tokio::fs::read_dir(&models_dir).await {
         Ok(entries) => entries,
         Err(_) => {
-            return Ok((StatusCode::OK, Json(ListModelsResponse { models })));
+            return Ok((
+                StatusCode::OK,
+                Json(ListModelsResponse {
+                    models,
+                    details: vec![],
+                }),
+            ));
         }
     };
 
@@ -207,5 +674,168 @@ pub async fn list_models() -> Result<impl IntoResponse> {
 
     models.sort();
 
-    Ok((StatusCode::OK, Json(ListModelsResponse { models })))
+    // Header parsing reads (and seeks through) each file's metadata: keep it off the runtime
+    let paths = models.clone();
+    let details = tokio::task::spawn_blocking(move || {
+        paths
+            .into_iter()
+            .map(|path| {
+                let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                let (gguf, error) = match crate::model::read_gguf_info(std::path::Path::new(&path))
+                {
+                    Ok(info) => (Some(info), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                ModelFileDetails {
+                    path,
+                    size_bytes,
+                    gguf,
+                    error,
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| ExsaError::InternalError(format!("Model scan task failed: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(ListModelsResponse { models, details })))
+}
+
+/// OpenAI-style model object for a resident model
+#[derive(Debug, Serialize)]
+pub struct ModelObject {
+    pub id: String,
+    pub object: &'static str,
+    pub owned_by: &'static str,
+    #[serde(flatten)]
+    pub info: crate::inference::ServedModelInfo,
+}
+
+/// OpenAI-style model list
+#[derive(Debug, Serialize)]
+pub struct ModelListResponse {
+    pub object: &'static str,
+    pub data: Vec<ModelObject>,
+}
+
+/// List resident models (OpenAI-compatible `/v1/models`)
+pub async fn list_resident_models(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    let data = state
+        .router
+        .list()
+        .into_iter()
+        .map(|info| ModelObject {
+            id: info.name.clone(),
+            object: "model",
+            owned_by: "exsa",
+            info,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(ModelListResponse {
+            object: "list",
+            data,
+        }),
+    ))
+}
+
+/// Remove a resident model (by name or alias) and free its worker
+pub async fn unload_resident_model(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse> {
+    state.router.unload(&name)?;
+
+    let response = LoadModelResponse {
+        success: true,
+        message: format!("Model '{}' unloaded", name),
+        model_info: None,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// List LoRA adapters response
+#[derive(Debug, Serialize)]
+pub struct ListLorasResponse {
+    pub dir: String,
+    pub adapters: Vec<crate::model::LoraAdapterInfo>,
+}
+
+/// List registered LoRA adapters
+pub async fn list_loras(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    let Some(registry) = state.engine.lora_registry() else {
+        return Err(ExsaError::ServiceUnavailable(
+            "LoRA adapters are not enabled".to_string(),
+        ));
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(ListLorasResponse {
+            dir: registry.dir().display().to_string(),
+            adapters: registry.list(),
+        }),
+    ))
+}
+
+/// Rescan LoRA adapters response
+#[derive(Debug, Serialize)]
+pub struct RescanLorasResponse {
+    pub dir: String,
+    pub adapters: Vec<crate::model::LoraAdapterInfo>,
+
+    /// Models reloaded from disk to initialize the changed adapters on them
+    pub reloaded_models: Vec<String>,
+    pub message: String,
+}
+
+/// Rescan the LoRA adapter directory.
+///
+/// Adapters can only be initialized on a model as it loads, so every served model
+/// whose adapter set changed is reloaded from disk. This is the only path that
+/// reloads a model for LoRA; requests just select adapters already loaded.
+pub async fn rescan_loras(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    let Some(registry) = state.engine.lora_registry() else {
+        return Err(ExsaError::ServiceUnavailable(
+            "LoRA adapters are not enabled".to_string(),
+        ));
+    };
+
+    let scan_registry = registry.clone();
+    tokio::task::spawn_blocking(move || scan_registry.rescan())
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("LoRA rescan task failed: {e}")))??;
+
+    let mut reloaded_models = Vec::new();
+    for served in state.router.served_models() {
+        if served.engine.reload_loras().await?.is_some() {
+            reloaded_models.push(served.name.clone());
+        }
+    }
+
+    let adapters = registry.list();
+    let message = if reloaded_models.is_empty() {
+        format!(
+            "{} LoRA adapter(s) registered; no model reload needed",
+            adapters.len()
+        )
+    } else {
+        format!(
+            "{} LoRA adapter(s) registered; reloaded {} from disk to load them",
+            adapters.len(),
+            reloaded_models.join(", ")
+        )
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(RescanLorasResponse {
+            dir: registry.dir().display().to_string(),
+            adapters,
+            reloaded_models,
+            message,
+        }),
+    ))
 }
@@ -1,8 +1,14 @@
+pub mod batches;
 pub mod chat;
 pub mod handlers;
 pub mod lifecycle;
 pub mod openai;
+pub mod priority;
+pub mod queue_events;
 pub mod rag;
+pub mod requests;
+pub mod rerank;
+pub mod resume;
 pub mod routes;
 pub mod schema;
 
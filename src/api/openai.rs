@@ -17,24 +17,24 @@ pub struct ChatCompletionRequest {
     pub messages: Vec<ChatMessage>,
 
     /// Sampling temperature (0.0-2.0)
-    #[serde(default = "default_temperature")]
-    pub temperature: f32,
+    #[serde(default)]
+    pub temperature: Option<f32>,
 
     /// Maximum tokens to generate
-    #[serde(default = "default_max_tokens")]
-    pub max_tokens: usize,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
 
     /// Top-p sampling
-    #[serde(default = "default_top_p")]
-    pub top_p: f32,
+    #[serde(default)]
+    pub top_p: Option<f32>,
 
     /// Top-k sampling
-    #[serde(default = "default_top_k")]
-    pub top_k: i32,
+    #[serde(default)]
+    pub top_k: Option<i32>,
 
     /// Repeat penalty
-    #[serde(default = "default_repeat_penalty")]
-    pub repeat_penalty: f32,
+    #[serde(default)]
+    pub repeat_penalty: Option<f32>,
 
     /// Number of completions to generate
     #[serde(default = "default_n")]
@@ -56,12 +56,33 @@ pub struct ChatCompletionRequest {
     #[serde(default)]
     pub frequency_penalty: f32,
 
+    /// Tool definitions, passed to the model's chat template as `tools`
+    #[serde(default)]
+    pub tools: Option<serde_json::Value>,
+
     /// User identifier (optional)
     pub user: Option<String>,
 
     /// Optional EXSA extension: Retrieval-Augmented Generation controls.
     #[serde(default)]
     pub rag: Option<RagChatOptions>,
+
+    /// Optional EXSA extension: LoRA adapters to apply (`[{"name": "...", "scale": 1.0}]`).
+    #[serde(default)]
+    pub lora: Vec<crate::model::lora::LoraRequest>,
+
+    /// Optional EXSA extension: let reasoning models think before answering
+    /// (`false` closes the `<think>` block in the prompt; default: the template's default).
+    #[serde(default)]
+    pub enable_thinking: Option<bool>,
+
+    /// Optional EXSA extension: maximum reasoning tokens before `</think>` is forced.
+    #[serde(default)]
+    pub reasoning_budget: Option<usize>,
+    /// Optional EXSA extension: milliseconds the request may wait in the queue
+    /// before it fails with a timeout (`X-Deadline-Ms` takes precedence).
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
 }
 
 /// OpenAI-compatible embeddings request.
@@ -69,13 +90,31 @@ pub struct ChatCompletionRequest {
 /// This is used by EXSA RAG to compute embeddings locally via llama.cpp.
 #[derive(Debug, Clone, Deserialize)]
 pub struct EmbeddingsRequest {
-    /// Model identifier (accepted for compatibility; EXSA uses the active model)
+    /// Embedding model name (see EXSA_EMBEDDINGS_MODELS).
+    ///
+    /// Ignored when no dedicated embedding model is configured (the active chat model is used).
     pub model: Option<String>,
 
     /// The input text(s) to embed.
     ///
     /// OpenAI accepts a string or an array of strings.
     pub input: serde_json::Value,
+
+    /// Truncate embeddings to this many dimensions (Matryoshka-style)
+    #[serde(default)]
+    pub dimensions: Option<usize>,
+
+    /// "float" (default) or "base64" (little-endian f32 bytes)
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+
+    /// Optional EXSA extension: pooling mode ("model", "mean", "cls", "last")
+    #[serde(default)]
+    pub pooling: Option<String>,
+
+    /// Optional EXSA extension: L2-normalize embeddings (default: false)
+    #[serde(default)]
+    pub normalize: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -90,7 +129,15 @@ pub struct EmbeddingsResponse {
 pub struct EmbeddingItem {
     pub object: String,
     pub index: usize,
-    pub embedding: Vec<f32>,
+    pub embedding: EmbeddingValue,
+}
+
+/// Embedding payload: a float array, or a base64 string when `encoding_format` is "base64"
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum EmbeddingValue {
+    Float(Vec<f32>),
+    Base64(String),
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -176,6 +223,10 @@ pub struct ChatMessageDelta {
     /// Content delta
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+
+    /// Reasoning delta (`<think>` block of reasoning models), kept out of `content`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
 }
 
 /// Token usage statistics
@@ -216,15 +267,38 @@ fn default_n() -> usize {
 impl ChatCompletionRequest {
     /// Convert to internal sampling parameters
     pub fn to_sampling_params(&self) -> crate::inference::SamplingParams {
+        self.to_sampling_params_with(&crate::model::SamplingDefaults::default())
+    }
+
+    /// Convert to internal sampling parameters, filling unset fields from
+    /// per-model defaults (models manifest) before the global defaults
+    pub fn to_sampling_params_with(
+        &self,
+        defaults: &crate::model::SamplingDefaults,
+    ) -> crate::inference::SamplingParams {
+        let stop = match &self.stop {
+            Some(stop) => stop.clone(),
+            None => defaults.stop.clone(),
+        };
+
         crate::inference::SamplingParams {
-            temperature: self.temperature,
-            max_tokens: self.max_tokens,
-            top_k: self.top_k,
-            top_p: self.top_p,
-            repeat_penalty: self.repeat_penalty,
+            temperature: self
+                .temperature
+                .or(defaults.temperature)
+                .unwrap_or_else(default_temperature),
+            max_tokens: self
+                .max_tokens
+                .or(defaults.max_tokens)
+                .unwrap_or_else(default_max_tokens),
+            top_k: self.top_k.or(defaults.top_k).unwrap_or_else(default_top_k),
+            top_p: self.top_p.or(defaults.top_p).unwrap_or_else(default_top_p),
+            repeat_penalty: self
+                .repeat_penalty
+                .or(defaults.repeat_penalty)
+                .unwrap_or_else(default_repeat_penalty),
             presence_penalty: self.presence_penalty,
             frequency_penalty: self.frequency_penalty,
-            stop_sequences: self.stop.clone().unwrap_or_default(),
+            stop_sequences: stop,
             seed: None,
             min_p: 0.0, // DISABLED - causes llama.cpp crashes (as noted in params.rs)
             mirostat: 0,
@@ -236,6 +310,11 @@ impl ChatCompletionRequest {
             // Context management fields
             n_keep: None,     // Use default (no preserved tokens)
             session_id: None, // No session by default
+            lora: self.lora.clone(),
+            reasoning_budget: self.reasoning_budget,
+            priority: Default::default(),
+            user: self.user.clone(),
+            deadline_ms: self.deadline_ms,
         }
     }
 }
@@ -291,9 +370,18 @@ impl ChatCompletionChunk {
                         None
                     },
                     content,
+                    reasoning_content: None,
                 },
                 finish_reason,
             }],
         }
     }
+
+    /// Attach a reasoning delta
+    pub fn with_reasoning(mut self, reasoning: Option<String>) -> Self {
+        if let Some(choice) = self.choices.first_mut() {
+            choice.delta.reasoning_content = reasoning;
+        }
+        self
+    }
 }
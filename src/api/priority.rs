@@ -0,0 +1,186 @@
+//! Request priority and fair-share identity from headers and API keys
+//!
+//! Clients pick a scheduling class with `X-Priority: interactive|batch|background`.
+//! Operators can pin API keys to a class (EXSA_API_KEY_PRIORITIES); the header
+//! may then lower a key's class but never raise it.
+//!
+//! For fair sharing, requests with an API key are grouped by key, so a client
+//! can't escape its share by varying `user`; other requests by their `user`.
+//!
+//! Keys are not authenticated here: any client can send any key, so classes and
+//! fair share are advisory unless a proxy in front validates the keys.
+//!
+//! `X-Deadline-Ms` bounds how long a request may wait in the queue.
+
+use crate::inference::Priority;
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use std::collections::HashMap;
+
+/// Header carrying the requested priority class
+pub const PRIORITY_HEADER: &str = "x-priority";
+
+/// Header carrying the longest queue wait the client accepts, in milliseconds
+pub const DEADLINE_HEADER: &str = "x-deadline-ms";
+
+/// API key from `Authorization: Bearer <key>` or `X-API-Key`
+pub fn api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+}
+
+/// Stable, non-reversible label for an API key (`key-1a2b3c4d`), safe to show in status
+pub fn api_key_label(key: &str) -> String {
+    // FNV-1a: same label across restarts, so it can be used in EXSA_USER_WEIGHTS
+    let hash = key.bytes().fold(0x811c_9dc5u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x0100_0193)
+    });
+    format!("key-{:08x}", hash)
+}
+
+/// Fair-share identity of a request: its API key label, else the given `user`.
+///
+/// Both come from the client unverified, so this only separates cooperating clients.
+pub fn client_id(headers: &HeaderMap, user: Option<&str>) -> Option<String> {
+    api_key(headers).map(api_key_label).or_else(|| {
+        user.map(str::trim)
+            .filter(|u| !u.is_empty())
+            .map(str::to_string)
+    })
+}
+
+/// Queue deadline of a request: `X-Deadline-Ms`, else the body's `deadline_ms`
+pub fn deadline_ms(headers: &HeaderMap, body: Option<u64>) -> Option<u64> {
+    headers
+        .get(DEADLINE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .or(body)
+}
+
+/// Maps requests to a priority class
+#[derive(Debug, Clone, Default)]
+pub struct PriorityPolicy {
+    /// Class per API key
+    keys: HashMap<String, Priority>,
+}
+
+impl PriorityPolicy {
+    /// Load from EXSA_API_KEY_PRIORITIES=key=class,... (e.g. `sk-etl=batch,sk-eval=background`)
+    pub fn from_env() -> Self {
+        std::env::var("EXSA_API_KEY_PRIORITIES")
+            .map(|spec| Self::parse(&spec))
+            .unwrap_or_default()
+    }
+
+    /// Parse `key=class` pairs, skipping malformed entries
+    pub fn parse(spec: &str) -> Self {
+        let keys = spec
+            .split(',')
+            .filter_map(|entry| {
+                let (key, class) = entry.split_once('=')?;
+                let key = key.trim();
+                let priority = Priority::parse(class);
+                if key.is_empty() || priority.is_none() {
+                    tracing::warn!("Ignoring invalid EXSA_API_KEY_PRIORITIES entry: {}", entry);
+                    return None;
+                }
+                Some((key.to_string(), priority?))
+            })
+            .collect();
+
+        Self { keys }
+    }
+
+    /// Priority for a request, or `None` to keep the one from the request body.
+    ///
+    /// Key classes are ceilings for whoever presents the key; the key itself is
+    /// not validated.
+    pub fn resolve(&self, headers: &HeaderMap) -> Option<Priority> {
+        let requested = headers
+            .get(PRIORITY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Priority::parse);
+        let key_class = api_key(headers).and_then(|key| self.keys.get(key).copied());
+
+        match (requested, key_class) {
+            (Some(requested), Some(ceiling)) => Some(requested.lower(ceiling)),
+            (requested, ceiling) => requested.or(ceiling),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_client_id() {
+        let label = api_key_label("sk-etl");
+        assert!(label.starts_with("key-") && label.len() == 12);
+        assert_eq!(label, api_key_label("sk-etl"));
+
+        assert_eq!(
+            client_id(&headers(&[("x-api-key", "sk-etl")]), Some("alice")),
+            Some(label)
+        );
+        assert_eq!(
+            client_id(&headers(&[]), Some("alice")),
+            Some("alice".to_string())
+        );
+        assert_eq!(client_id(&headers(&[]), Some(" ")), None);
+    }
+
+    #[test]
+    fn test_deadline_ms() {
+        assert_eq!(
+            deadline_ms(&headers(&[(DEADLINE_HEADER, "1500")]), Some(10)),
+            Some(1500)
+        );
+        assert_eq!(
+            deadline_ms(&headers(&[(DEADLINE_HEADER, "soon")]), Some(10)),
+            Some(10)
+        );
+        assert_eq!(deadline_ms(&headers(&[]), None), None);
+    }
+
+    #[test]
+    fn test_resolve_priority() {
+        let policy = PriorityPolicy::parse("sk-etl=batch, sk-eval=background, broken");
+
+        assert_eq!(policy.resolve(&headers(&[])), None);
+        assert_eq!(
+            policy.resolve(&headers(&[("x-priority", "background")])),
+            Some(Priority::Background)
+        );
+        assert_eq!(
+            policy.resolve(&headers(&[("authorization", "Bearer sk-etl")])),
+            Some(Priority::Batch)
+        );
+
+        // A key's class caps what the header can ask for
+        assert_eq!(
+            policy.resolve(&headers(&[
+                ("x-api-key", "sk-etl"),
+                ("x-priority", "interactive")
+            ])),
+            Some(Priority::Batch)
+        );
+        assert_eq!(
+            policy.resolve(&headers(&[("x-api-key", "sk-etl"), ("x-priority", "low")])),
+            Some(Priority::Background)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Queue position and prompt progress events on SSE streams
+//!
+//! While a streaming request waits for the engine, the client would otherwise
+//! see nothing until its first token. With queue events enabled the stream
+//! carries the request's queue position and estimated start time every second
+//! until it starts, then how far prompt processing has got until the prompt is
+//! done, either as SSE comments (ignored by every client) or as named `queue`
+//! and `prompt` events for UIs that want to show them.
+
+use crate::inference::{PromptProgress, QueueHandle, QueuePosition};
+use axum::http::HeaderMap;
+use axum::response::sse::Event;
+use futures::stream::{Stream, StreamExt};
+use std::convert::Infallible;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Header to choose the mode per request
+pub const QUEUE_EVENTS_HEADER: &str = "x-queue-events";
+
+/// How often a waiting request's position or prompt progress is sent
+const QUEUE_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How queue positions appear on the stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueueEventMode {
+    #[default]
+    Off,
+
+    /// `: queue position=3 ahead=2 eta=12s` and `: prompt processed=2048/8192 cached=512`
+    /// comment lines
+    Comment,
+
+    /// `event: queue` with a JSON `QueuePosition`, `event: prompt` with a JSON `PromptProgress`
+    Event,
+}
+
+impl QueueEventMode {
+    /// Parse from string, returning `None` on unknown input
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "none" | "false" | "0" => Some(Self::Off),
+            "comment" | "comments" | "true" | "1" => Some(Self::Comment),
+            "event" | "events" => Some(Self::Event),
+            _ => None,
+        }
+    }
+
+    /// Mode for a request: `X-Queue-Events` header, else EXSA_QUEUE_EVENTS (default: off)
+    pub fn for_request(headers: &HeaderMap) -> Self {
+        headers
+            .get(QUEUE_EVENTS_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::parse)
+            .or_else(|| {
+                std::env::var("EXSA_QUEUE_EVENTS")
+                    .ok()
+                    .and_then(|v| Self::parse(&v))
+            })
+            .unwrap_or_default()
+    }
+
+    fn event(self, position: &QueuePosition) -> Event {
+        match self {
+            Self::Event => Event::default()
+                .event("queue")
+                .json_data(position)
+                .unwrap_or_else(|_| Event::default().event("queue")),
+            _ => {
+                let eta = position
+                    .eta_seconds
+                    .map(|eta| format!(" eta={:.0}s", eta.ceil()))
+                    .unwrap_or_default();
+                Event::default().comment(format!(
+                    "queue position={} ahead={}{}",
+                    position.position, position.ahead, eta
+                ))
+            }
+        }
+    }
+
+    fn prompt_event(self, progress: &PromptProgress) -> Event {
+        match self {
+            Self::Event => Event::default()
+                .event("prompt")
+                .json_data(progress)
+                .unwrap_or_else(|_| Event::default().event("prompt")),
+            _ => Event::default().comment(format!(
+                "prompt processed={}/{} cached={}",
+                progress.processed, progress.total, progress.cached
+            )),
+        }
+    }
+}
+
+/// Interleave queue events for request `id` with its `tokens`; ends with `tokens`
+pub fn with_queue_events<S>(
+    tokens: S,
+    queue: QueueHandle,
+    id: Uuid,
+    mode: QueueEventMode,
+) -> impl Stream<Item = Result<Event, Infallible>>
+where
+    S: Stream<Item = Result<Event, Infallible>>,
+{
+    // `None` marks the end of the tokens, so a pending tick can't hold the response open
+    let events = queue_events(queue, id, mode).map(Some);
+    let tokens = tokens
+        .map(Some)
+        .chain(futures::stream::once(async { None }));
+
+    futures::stream::select(events, tokens)
+        .take_while(|item| std::future::ready(item.is_some()))
+        .filter_map(std::future::ready)
+}
+
+/// Position updates for request `id` until it leaves the queue, then prompt
+/// progress until its prompt is processed (empty when `Off`)
+fn queue_events(
+    queue: QueueHandle,
+    id: Uuid,
+    mode: QueueEventMode,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let interval =
+        (mode != QueueEventMode::Off).then(|| tokio::time::interval(QUEUE_EVENT_INTERVAL));
+
+    futures::stream::unfold(interval, move |interval| {
+        let queue = queue.clone();
+        async move {
+            let mut interval = interval?;
+            loop {
+                interval.tick().await;
+                if let Some(position) = queue.position(id) {
+                    return Some((Ok(mode.event(&position)), Some(interval)));
+                }
+                match queue.prompt_progress(id) {
+                    Some(progress) if progress.is_done() => return None,
+                    Some(progress) => {
+                        return Some((Ok(mode.prompt_event(&progress)), Some(interval)))
+                    }
+                    // Dequeued, prefill not started yet
+                    None if queue
+                        .request(id)
+                        .is_some_and(|r| r.status.is_in_flight() && r.generated_tokens == 0) => {}
+                    None => return None,
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::PromptTracker;
+
+    #[test]
+    fn test_mode_from_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(QUEUE_EVENTS_HEADER, "Event".parse().unwrap());
+        assert_eq!(QueueEventMode::for_request(&headers), QueueEventMode::Event);
+        assert_eq!(
+            QueueEventMode::parse("comments"),
+            Some(QueueEventMode::Comment)
+        );
+        assert_eq!(QueueEventMode::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_prompt_progress() {
+        let tracker = PromptTracker::default();
+        assert_eq!(tracker.get(), None);
+
+        tracker.start(8192, 512);
+        tracker.advance(2048);
+        let progress = tracker.get().unwrap();
+        assert!(!progress.is_done());
+        assert_eq!(
+            serde_json::to_value(progress).unwrap(),
+            serde_json::json!({"processed": 2048, "total": 8192, "cached": 512})
+        );
+
+        tracker.advance(8192);
+        assert!(tracker.get().unwrap().is_done());
+    }
+}
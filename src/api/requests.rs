@@ -0,0 +1,131 @@
+//! Request status and cancellation API
+//!
+//! Every request submitted to a model's queue can be looked up by its id (the
+//! `id` of OpenAI chunks, also sent as `X-Request-Id` on streaming responses)
+//! while queued or running and for a while after it finished, and cancelled
+//! through its `CancellationToken`.
+
+use crate::api::queue_events::QueueEventMode;
+use crate::api::resume::{last_event_id, sse_response};
+use crate::api::schema::AppState;
+use crate::inference::{RequestRecord, ServedModel};
+use crate::utils::error::{ExsaError, Result};
+use axum::extract::{Json, Path, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, Sse};
+use futures::stream::Stream;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Response header carrying the request id of a streaming response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// A request and the resident model it was routed to
+#[derive(Debug, Serialize)]
+pub struct RequestEntry {
+    pub model: String,
+    #[serde(flatten)]
+    pub request: RequestRecord,
+}
+
+fn parse_request_id(request_id: &str) -> Result<Uuid> {
+    Uuid::parse_str(request_id)
+        .map_err(|_| ExsaError::InvalidParameters(format!("Invalid request id: {}", request_id)))
+}
+
+fn unknown_request(request_id: &str) -> ExsaError {
+    ExsaError::InvalidParameters(format!("Unknown request: {}", request_id))
+}
+
+/// Routing name of a served model (the default model follows switches)
+fn model_name(state: &AppState, served: &Arc<ServedModel>) -> String {
+    if Arc::ptr_eq(served, &state.router.default_model()) {
+        state.router.default_name()
+    } else {
+        served.name.clone()
+    }
+}
+
+/// Find a request in any resident model's queue
+fn find(
+    state: &AppState,
+    lookup: impl Fn(&ServedModel) -> Option<RequestRecord>,
+) -> Option<RequestEntry> {
+    state.router.served_models().iter().find_map(|served| {
+        lookup(served).map(|request| RequestEntry {
+            model: model_name(state, served),
+            request,
+        })
+    })
+}
+
+/// Queued and running requests across all resident models, oldest first
+pub async fn list_requests(State(state): State<AppState>) -> Json<Vec<RequestEntry>> {
+    let mut entries: Vec<RequestEntry> = state
+        .router
+        .served_models()
+        .iter()
+        .flat_map(|served| {
+            let model = model_name(&state, served);
+            served
+                .queue
+                .in_flight()
+                .into_iter()
+                .map(move |request| RequestEntry {
+                    model: model.clone(),
+                    request,
+                })
+        })
+        .collect();
+    entries.sort_by_key(|e| e.request.created_at);
+    Json(entries)
+}
+
+/// Status and timing of a request (queued, running, done, failed or cancelled)
+pub async fn get_request(
+    State(state): State<AppState>,
+    Path(request_id): Path<String>,
+) -> Result<Json<RequestEntry>> {
+    let id = parse_request_id(&request_id)?;
+    find(&state, |served| served.queue.request(id))
+        .map(Json)
+        .ok_or_else(|| unknown_request(&request_id))
+}
+
+/// Cancel a request: dropped from the queue if waiting, stopped at the next token if running
+pub async fn cancel_request(
+    State(state): State<AppState>,
+    Path(request_id): Path<String>,
+) -> Result<Json<RequestEntry>> {
+    let id = parse_request_id(&request_id)?;
+    let entry = find(&state, |served| served.queue.cancel(id))
+        .ok_or_else(|| unknown_request(&request_id))?;
+    tracing::info!(
+        "🛑 Cancel requested for {} (status: {:?})",
+        id,
+        entry.request.status
+    );
+    Ok(Json(entry))
+}
+
+/// Stream a request's output as SSE, from the start or after `Last-Event-ID`
+pub async fn stream_request(
+    State(state): State<AppState>,
+    Path(request_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<(
+    [(&'static str, String); 1],
+    Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>,
+)> {
+    let id = parse_request_id(&request_id)?;
+    let after = last_event_id(&headers)
+        .filter(|(last, _)| *last == id)
+        .map(|(_, n)| n)
+        .unwrap_or(0);
+    let stream = state
+        .streams
+        .subscribe(id, after, QueueEventMode::for_request(&headers))?;
+    Ok(sse_response(id, stream))
+}
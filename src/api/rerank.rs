@@ -0,0 +1,122 @@
+//! Reranking endpoint (Jina/Cohere-compatible request shape)
+
+use crate::api::schema::AppState;
+use crate::utils::error::{ExsaError, Result};
+use axum::{extract::State, Json};
+use serde::{Deserialize, Serialize};
+
+/// Upper bound on documents per rerank request
+const MAX_RERANK_DOCUMENTS: usize = 1000;
+
+/// Rerank request
+#[derive(Debug, Clone, Deserialize)]
+pub struct RerankRequest {
+    /// Model identifier (accepted for compatibility; EXSA uses the configured reranker)
+    pub model: Option<String>,
+
+    /// Search query
+    pub query: String,
+
+    /// Documents to score. Accepts strings or `{ "text": "..." }` objects.
+    pub documents: Vec<serde_json::Value>,
+
+    /// Return only the top N results (default: all)
+    pub top_n: Option<usize>,
+
+    /// Include the document text in each result (default: true)
+    pub return_documents: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RerankResponse {
+    pub model: String,
+    pub results: Vec<RerankResult>,
+    pub usage: RerankUsage,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RerankResult {
+    pub index: usize,
+    pub relevance_score: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<RerankDocument>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RerankDocument {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RerankUsage {
+    pub total_tokens: usize,
+}
+
+/// Score documents against a query with the local reranker model
+pub async fn rerank(
+    State(state): State<AppState>,
+    Json(req): Json<RerankRequest>,
+) -> Result<Json<RerankResponse>> {
+    let Some(reranker) = state.reranker.clone() else {
+        return Err(ExsaError::ServiceUnavailable(
+            "Reranking is not enabled (set EXSA_RERANK_MODEL_PATH)".to_string(),
+        ));
+    };
+
+    if req.query.trim().is_empty() {
+        return Err(ExsaError::InvalidParameters(
+            "Rerank query is empty".to_string(),
+        ));
+    }
+
+    if req.documents.len() > MAX_RERANK_DOCUMENTS {
+        return Err(ExsaError::InvalidParameters(format!(
+            "Too many documents ({}), maximum is {}",
+            req.documents.len(),
+            MAX_RERANK_DOCUMENTS
+        )));
+    }
+
+    let documents = req
+        .documents
+        .iter()
+        .map(|d| {
+            d.as_str()
+                .or_else(|| d.get("text").and_then(|t| t.as_str()))
+                .map(|s| s.to_string())
+                .ok_or_else(|| {
+                    ExsaError::InvalidParameters(
+                        "Rerank documents must be strings or objects with a 'text' field"
+                            .to_string(),
+                    )
+                })
+        })
+        .collect::<Result<Vec<String>>>()?;
+
+    let query = req.query.clone();
+    let docs = documents.clone();
+    let top_n = req.top_n;
+    let worker = reranker.clone();
+    let (ranked, total_tokens) =
+        tokio::task::spawn_blocking(move || worker.rerank(&query, &docs, top_n))
+            .await
+            .map_err(|e| ExsaError::InternalError(format!("Rerank task failed: {e}")))??;
+
+    let return_documents = req.return_documents.unwrap_or(true);
+    let results = ranked
+        .into_iter()
+        .map(|r| RerankResult {
+            index: r.index,
+            relevance_score: r.score,
+            document: return_documents.then(|| RerankDocument {
+                text: documents[r.index].clone(),
+            }),
+        })
+        .collect();
+
+    Ok(Json(RerankResponse {
+        model: reranker.name().to_string(),
+        results,
+        usage: RerankUsage { total_tokens },
+    }))
+}
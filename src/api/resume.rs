@@ -0,0 +1,301 @@
+//! Resumable SSE streams
+//!
+//! Generation output is drained into a per-request buffer instead of being sent
+//! straight to the HTTP response, and every chunk gets an SSE id
+//! (`<request id>:<n>`). A client whose connection dropped reconnects with
+//! `Last-Event-ID` (on the original endpoint or `GET /v1/requests/:id/stream`)
+//! and receives the chunks after that id, while the request keeps generating.
+//!
+//! A request nobody is listening to is cancelled once the retention window
+//! passes without a reconnect; finished streams stay for the same window.
+//!
+//! A request that fails (queue deadline, engine error) ends its stream with an
+//! `error` event carrying `{"error": "<message>"}`.
+
+use crate::api::queue_events::{with_queue_events, QueueEventMode};
+use crate::api::requests::REQUEST_ID_HEADER;
+use crate::inference::QueueHandle;
+use crate::utils::error::{ExsaError, Result};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Notify};
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use uuid::Uuid;
+
+/// Header a reconnecting SSE client sends with the last id it received
+pub const LAST_EVENT_ID_HEADER: &str = "last-event-id";
+
+/// SSE id of the `n`-th chunk (1-based) of a request
+fn event_id(id: Uuid, n: usize) -> String {
+    format!("{}:{}", id, n)
+}
+
+/// Request id and chunk count from a `Last-Event-ID` value
+pub fn parse_event_id(value: &str) -> Option<(Uuid, usize)> {
+    let (id, n) = value.trim().rsplit_once(':')?;
+    Some((Uuid::parse_str(id).ok()?, n.parse().ok()?))
+}
+
+/// Resume point sent by a reconnecting client, if any
+pub fn last_event_id(headers: &HeaderMap) -> Option<(Uuid, usize)> {
+    headers
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_event_id)
+}
+
+/// SSE response for request `id`, with its id in `X-Request-Id`
+pub fn sse_response<S>(id: Uuid, stream: S) -> ([(&'static str, String); 1], Sse<S>)
+where
+    S: Stream<Item = std::result::Result<Event, Infallible>> + Send + 'static,
+{
+    (
+        [(REQUEST_ID_HEADER, id.to_string())],
+        Sse::new(stream).keep_alive(KeepAlive::default()),
+    )
+}
+
+/// Final event of a failed request, shaped like the JSON error responses
+fn error_event(message: &str) -> Event {
+    Event::default()
+        .event("error")
+        .json_data(serde_json::json!({ "error": message }))
+        .unwrap_or_else(|_| Event::default().event("error"))
+}
+
+#[derive(Default)]
+struct BufferState {
+    /// `data` of every chunk so far
+    chunks: Vec<String>,
+    done: bool,
+    /// Why the request failed, sent as a final `error` event
+    error: Option<String>,
+    finished_at: Option<Instant>,
+    subscribers: usize,
+}
+
+struct StreamBuffer {
+    state: Mutex<BufferState>,
+    changed: Notify,
+    queue: QueueHandle,
+    cancellation_token: CancellationToken,
+}
+
+impl StreamBuffer {
+    fn push(&self, data: String) {
+        if let Ok(mut state) = self.state.lock() {
+            state.chunks.push(data);
+        }
+        self.changed.notify_waiters();
+    }
+
+    fn finish(&self, error: Option<String>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.done = true;
+            state.error = error;
+            state.finished_at = Some(Instant::now());
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// Chunks from index `from`, whether the stream has ended, and its error
+    fn read(&self, from: usize) -> (Vec<String>, bool, Option<String>) {
+        match self.state.lock() {
+            Ok(state) => (
+                state.chunks.get(from..).unwrap_or_default().to_vec(),
+                state.done,
+                state.error.clone(),
+            ),
+            Err(_) => (Vec::new(), true, None),
+        }
+    }
+
+    fn expired(&self, retention: Duration) -> bool {
+        self.state
+            .lock()
+            .map(|s| s.finished_at.is_some_and(|t| t.elapsed() >= retention))
+            .unwrap_or(true)
+    }
+}
+
+/// Counts a listener; the last one to leave starts the reconnect grace period
+struct Subscriber {
+    id: Uuid,
+    buffer: Arc<StreamBuffer>,
+    retention: Duration,
+}
+
+impl Subscriber {
+    fn new(id: Uuid, buffer: Arc<StreamBuffer>, retention: Duration) -> Self {
+        if let Ok(mut state) = buffer.state.lock() {
+            state.subscribers += 1;
+        }
+        Self {
+            id,
+            buffer,
+            retention,
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let abandoned = match self.buffer.state.lock() {
+            Ok(mut state) => {
+                state.subscribers = state.subscribers.saturating_sub(1);
+                state.subscribers == 0 && !state.done
+            }
+            Err(_) => false,
+        };
+        if !abandoned {
+            return;
+        }
+
+        let (id, buffer, retention) = (self.id, self.buffer.clone(), self.retention);
+        tokio::spawn(async move {
+            tokio::time::sleep(retention).await;
+            let still_abandoned = buffer
+                .state
+                .lock()
+                .map(|s| s.subscribers == 0 && !s.done)
+                .unwrap_or(false);
+            if still_abandoned {
+                info!("🛑 Cancelling request {}: client did not reconnect", id);
+                buffer.cancellation_token.cancel();
+            }
+        });
+    }
+}
+
+/// Buffered output of streaming requests, by request id
+pub struct ResumableStreams {
+    streams: Mutex<HashMap<Uuid, Arc<StreamBuffer>>>,
+    retention: Duration,
+}
+
+impl ResumableStreams {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            streams: Mutex::new(HashMap::new()),
+            retention,
+        }
+    }
+
+    /// Load from EXSA_STREAM_RETENTION_SECS (default: 30; 0 cancels on disconnect)
+    pub fn from_env() -> Self {
+        let secs = std::env::var("EXSA_STREAM_RETENTION_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(30);
+        Self::new(Duration::from_secs(secs))
+    }
+
+    /// Buffer the `data` chunks of request `id` in the background until they
+    /// end, then record the request's outcome from `completion`
+    pub fn register<S>(
+        &self,
+        id: Uuid,
+        queue: QueueHandle,
+        cancellation_token: CancellationToken,
+        chunks: S,
+        completion: oneshot::Receiver<std::result::Result<(), ExsaError>>,
+    ) where
+        S: Stream<Item = String> + Send + 'static,
+    {
+        let buffer = Arc::new(StreamBuffer {
+            state: Mutex::new(BufferState::default()),
+            changed: Notify::new(),
+            queue,
+            cancellation_token,
+        });
+
+        if let Ok(mut streams) = self.streams.lock() {
+            streams.retain(|_, b| !b.expired(self.retention));
+            streams.insert(id, buffer.clone());
+        }
+
+        tokio::spawn(async move {
+            let mut chunks = std::pin::pin!(chunks);
+            while let Some(data) = chunks.next().await {
+                buffer.push(data);
+            }
+            // A dropped sender means the queue went away without an outcome
+            let error = match completion.await {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some("Request was dropped before completing".to_string()),
+            };
+            buffer.finish(error);
+        });
+    }
+
+    /// SSE events of request `id` after its first `after` chunks, with queue
+    /// events while it waits
+    pub fn subscribe(
+        &self,
+        id: Uuid,
+        after: usize,
+        mode: QueueEventMode,
+    ) -> Result<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+        let buffer = self
+            .streams
+            .lock()
+            .ok()
+            .and_then(|streams| streams.get(&id).cloned())
+            .filter(|buffer| !buffer.expired(self.retention))
+            .ok_or_else(|| {
+                ExsaError::InvalidParameters(format!(
+                    "Stream of request {} is unknown or expired",
+                    id
+                ))
+            })?;
+
+        let queue = buffer.queue.clone();
+        let subscriber = Subscriber::new(id, buffer, self.retention);
+        let chunks = async_stream::stream! {
+            let subscriber = subscriber;
+            let mut next = after;
+            loop {
+                // Registered before reading, so a push in between still wakes us
+                let changed = subscriber.buffer.changed.notified();
+                let (chunks, done, error) = subscriber.buffer.read(next);
+                for data in chunks {
+                    next += 1;
+                    yield Ok(Event::default().id(event_id(id, next)).data(data));
+                }
+                if done {
+                    if let Some(message) = error {
+                        yield Ok(error_event(&message));
+                    }
+                    break;
+                }
+                changed.await;
+            }
+        };
+
+        Ok(with_queue_events(chunks, queue, id, mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event_id() {
+        let id = Uuid::new_v4();
+        assert_eq!(parse_event_id(&event_id(id, 12)), Some((id, 12)));
+        assert_eq!(parse_event_id("12"), None);
+        assert_eq!(parse_event_id("not-a-uuid:3"), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(LAST_EVENT_ID_HEADER, event_id(id, 4).parse().unwrap());
+        assert_eq!(last_event_id(&headers), Some((id, 4)));
+    }
+}
@@ -1,12 +1,22 @@
 //! API route configuration
 
+use super::batches::{
+    batch_output, cancel_batch, create_batch, get_batch, list_batches, upload_batch, upload_limit,
+};
 use super::handlers::{chat_completions, embeddings, generate, health, status};
-use super::lifecycle::{get_active_model, list_models, load_model, reload_model, unload_model};
+use super::lifecycle::{
+    get_active_model, get_load_job, list_load_jobs, list_loras, list_models, list_resident_models,
+    load_job_events, load_model, plan_model_load, reload_model, rescan_loras, unload_model,
+    unload_resident_model,
+};
 use super::rag::{
     delete_document, ingest_document_multipart, list_documents, rag_search, rag_status,
 };
+use super::requests::{cancel_request, get_request, list_requests, stream_request};
+use super::rerank::rerank;
 use super::schema::AppState;
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router,
 };
@@ -20,15 +30,40 @@ pub fn build_router(state: AppState) -> Router {
         // OpenAI-compatible endpoint
         .route("/v1/chat/completions", post(chat_completions))
         .route("/v1/embeddings", post(embeddings))
+        .route("/v1/rerank", post(rerank))
         // Status endpoints (using AppState)
         .route("/v1/health", get(health))
         .route("/v1/status", get(status))
         .route("/v1/model/info", get(super::handlers::model_info))
-        .route("/v1/models/load", post(load_model))
+        .route("/v1/models", get(list_resident_models))
+        .route(
+            "/v1/models/resident/:name",
+            axum::routing::delete(unload_resident_model),
+        )
+        .route("/v1/models/load", post(load_model).get(list_load_jobs))
+        .route("/v1/models/load/:job_id", get(get_load_job))
+        .route("/v1/models/load/:job_id/events", get(load_job_events))
+        .route("/v1/models/plan", post(plan_model_load))
         .route("/v1/models/unload", post(unload_model))
         .route("/v1/models/reload", post(reload_model))
         .route("/v1/models/list", get(list_models))
         .route("/v1/models/active", get(get_active_model))
+        .route("/v1/requests", get(list_requests))
+        .route(
+            "/v1/requests/:request_id",
+            get(get_request).delete(cancel_request),
+        )
+        .route("/v1/requests/:request_id/stream", get(stream_request))
+        .route("/v1/batches", post(create_batch).get(list_batches))
+        .route(
+            "/v1/batches/upload",
+            post(upload_batch).layer(DefaultBodyLimit::max(upload_limit())),
+        )
+        .route("/v1/batches/:batch_id", get(get_batch))
+        .route("/v1/batches/:batch_id/cancel", post(cancel_batch))
+        .route("/v1/batches/:batch_id/output", get(batch_output))
+        .route("/v1/loras", get(list_loras))
+        .route("/v1/loras/rescan", post(rescan_loras))
         // RAG endpoints
         .route("/v1/rag/status", get(rag_status))
         .route(
@@ -1,22 +1,51 @@
 //! API request/response schemas
 
-use crate::inference::{InferenceEngine, QueueHandle, SamplingParams};
+use crate::api::priority::PriorityPolicy;
+use crate::api::resume::ResumableStreams;
+use crate::inference::{
+    BatchJobs, EmbeddingsRegistry, EngineState, InferenceEngine, LoadJobs, ModelRouter,
+    QueueHandle, Reranker, SamplingParams, ShedStats, UserQueueStats,
+};
+use crate::model::{KvCacheQuantization, ModelConfig, RopeScalingType, SamplingDefaults};
 use crate::rag::RagService;
+use crate::utils::error::{ExsaError, Result};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
+    /// Queue and engine of the default model
     pub queue: QueueHandle,
     pub engine: Arc<InferenceEngine>,
 
+    /// Routes requests to resident models by `model` name or alias
+    pub router: Arc<ModelRouter>,
+
     /// Optional Retrieval-Augmented Generation service.
     pub rag: Option<Arc<RagService>>,
 
+    /// Optional local cross-encoder reranker (EXSA_RERANK_MODEL_PATH).
+    pub reranker: Option<Arc<Reranker>>,
+
+    /// Embedding models for /v1/embeddings (falls back to the chat model on CPU).
+    pub embeddings: Arc<EmbeddingsRegistry>,
+
     /// Serialize model switching/loading operations
     pub model_switch_lock: Arc<tokio::sync::Mutex<()>>,
 
+    /// Background model loads (`/v1/models/load` with `background: true`)
+    pub load_jobs: Arc<LoadJobs>,
+
+    /// Batch jobs over JSONL files (`/v1/batches`)
+    pub batch_jobs: Arc<BatchJobs>,
+
+    /// Buffered SSE output of streaming requests, for `Last-Event-ID` resume
+    pub streams: Arc<ResumableStreams>,
+
+    /// Scheduling class per request (X-Priority header, EXSA_API_KEY_PRIORITIES)
+    pub priorities: Arc<PriorityPolicy>,
+
     /// Serialize embeddings requests (llama.cpp backends can be sensitive to concurrent contexts).
     pub embeddings_lock: Arc<tokio::sync::Mutex<()>>,
 
@@ -33,15 +62,45 @@ pub struct GenerateRequest {
     /// The input prompt
     pub prompt: String,
 
-    /// Sampling parameters (optional, uses defaults if not provided)
+    /// Model name or alias (default model when omitted)
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Sampling parameters (optional; fields left out fall back to the model's
+    /// manifest defaults, then the global defaults)
     #[serde(default)]
-    pub sampling_params: SamplingParams,
+    pub sampling_params: Option<serde_json::Map<String, serde_json::Value>>,
 
     /// Whether to apply chat template formatting (default: true)
     #[serde(default)]
     pub use_chat_template: Option<bool>,
 }
 
+impl GenerateRequest {
+    /// Sampling parameters with the fields set in the request overriding the
+    /// per-model defaults (models manifest), which override the global defaults
+    pub fn to_sampling_params_with(
+        &self,
+        defaults: Option<&SamplingDefaults>,
+    ) -> Result<SamplingParams> {
+        let mut params = SamplingParams::default();
+        if let Some(defaults) = defaults {
+            defaults.apply_to(&mut params);
+        }
+        let Some(fields) = &self.sampling_params else {
+            return Ok(params);
+        };
+
+        let mut merged = serde_json::to_value(&params)
+            .map_err(|e| ExsaError::InternalError(format!("Sampling defaults: {}", e)))?;
+        if let serde_json::Value::Object(map) = &mut merged {
+            map.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        serde_json::from_value(merged)
+            .map_err(|e| ExsaError::InvalidParameters(format!("Invalid sampling_params: {}", e)))
+    }
+}
+
 /// Server-sent event for token streaming
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenEvent {
@@ -62,6 +121,9 @@ pub struct HealthResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model_loaded: Option<bool>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_state: Option<EngineState>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model_path: Option<String>,
 
@@ -90,6 +152,13 @@ pub struct StatusResponse {
     pub status: String,
     pub queue_capacity: usize,
     pub active_requests: usize,
+
+    /// Queue depth per user (request `user` or API key label), busiest first
+    pub users: Vec<UserQueueStats>,
+
+    /// Requests rejected at admission (429/503 with Retry-After) or expired in the
+    /// queue, summed over the queues of all resident models
+    pub shed: ShedStats,
 }
 
 /// Model information response
@@ -106,6 +175,31 @@ pub struct ModelInfo {
     pub model_path: String,
     pub context_size: usize,
     pub gpu_layers: i32,
+    pub batch_size: u32,
+    pub kv_cache_type: KvCacheQuantization,
+    pub use_mmap: bool,
+    pub use_mlock: bool,
+    pub rope_scaling_type: RopeScalingType,
+    pub rope_scale_factor: f32,
+    /// 0 when the model's own frequency base is used
+    pub rope_freq_base: f32,
+}
+
+impl From<&ModelConfig> for ModelInfo {
+    fn from(cfg: &ModelConfig) -> Self {
+        Self {
+            model_path: cfg.model_path.clone(),
+            context_size: cfg.n_ctx as usize,
+            gpu_layers: cfg.n_gpu_layers as i32,
+            batch_size: cfg.n_batch,
+            kv_cache_type: cfg.kv_cache_type_k,
+            use_mmap: cfg.use_mmap,
+            use_mlock: cfg.use_mlock,
+            rope_scaling_type: cfg.rope_scaling_type,
+            rope_scale_factor: cfg.rope_scale_factor,
+            rope_freq_base: cfg.rope_freq_base,
+        }
+    }
 }
 
 impl From<ModelInfo> for ModelInfoResponse {
@@ -0,0 +1,260 @@
+//! Batch jobs over JSONL files
+//!
+//! `POST /v1/batches` runs every request line of an input JSONL file through the
+//! model queue at background priority and writes one result line per request to
+//! an output JSONL file. Each batch is tracked as a job with progress counters
+//! and can be cancelled while it runs.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Finished jobs kept for status queries
+const MAX_FINISHED_JOBS: usize = 32;
+
+/// Stage of a batch job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchJobStatus {
+    /// Created, input not read yet
+    Queued,
+
+    /// Submitting lines and writing results
+    Running,
+
+    /// Every line has a result (some may be errors)
+    Completed,
+
+    /// Input or output file error; results so far are in the output file
+    Failed,
+
+    /// Stopped on request; results so far are in the output file
+    Cancelled,
+}
+
+impl BatchJobStatus {
+    /// Whether the job will not change any more
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Snapshot of a batch job
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchJob {
+    pub id: Uuid,
+    pub input_file: String,
+    pub output_file: String,
+
+    /// Model for lines that don't name one (default model when unset)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    pub status: BatchJobStatus,
+
+    /// Request lines in the input, and how many have a result so far
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+
+    /// Unix timestamps (seconds)
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or(std::time::Duration::from_secs(0))
+        .as_secs()
+}
+
+/// Writer side of a job, moved into the task that runs the batch
+#[derive(Clone)]
+pub struct BatchJobHandle {
+    tx: Arc<watch::Sender<BatchJob>>,
+    cancellation_token: CancellationToken,
+}
+
+impl BatchJobHandle {
+    pub fn id(&self) -> Uuid {
+        self.tx.borrow().id
+    }
+
+    /// Current state of the job
+    pub fn snapshot(&self) -> BatchJob {
+        self.tx.borrow().clone()
+    }
+
+    /// Cancelled when the job is cancelled
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// Input read; `total` request lines to process
+    pub fn start(&self, total: usize) {
+        self.tx.send_modify(|job| {
+            job.status = BatchJobStatus::Running;
+            job.total = total;
+            job.started_at = Some(unix_now());
+        });
+    }
+
+    /// Count a finished line
+    pub fn record(&self, ok: bool) {
+        self.tx.send_modify(|job| {
+            job.completed += 1;
+            if !ok {
+                job.failed += 1;
+            }
+        });
+    }
+
+    /// All lines done (or stopped, if the job was cancelled)
+    pub fn finish(&self) {
+        let status = if self.cancellation_token.is_cancelled() {
+            BatchJobStatus::Cancelled
+        } else {
+            BatchJobStatus::Completed
+        };
+        self.tx.send_modify(|job| {
+            job.status = status;
+            job.finished_at = Some(unix_now());
+        });
+    }
+
+    pub fn fail(&self, error: impl Into<String>) {
+        self.tx.send_modify(|job| {
+            job.status = BatchJobStatus::Failed;
+            job.finished_at = Some(unix_now());
+            job.error = Some(error.into());
+        });
+    }
+}
+
+/// Registry of recent batch jobs
+#[derive(Default)]
+pub struct BatchJobs {
+    jobs: RwLock<VecDeque<BatchJobHandle>>,
+}
+
+impl BatchJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a queued job reading `input_file` and writing `output_file`
+    pub fn create(
+        &self,
+        input_file: impl Into<String>,
+        output_file: impl Into<String>,
+        model: Option<String>,
+    ) -> BatchJobHandle {
+        let (tx, _) = watch::channel(BatchJob {
+            id: Uuid::new_v4(),
+            input_file: input_file.into(),
+            output_file: output_file.into(),
+            model,
+            status: BatchJobStatus::Queued,
+            total: 0,
+            completed: 0,
+            failed: 0,
+            created_at: unix_now(),
+            started_at: None,
+            finished_at: None,
+            error: None,
+        });
+        let handle = BatchJobHandle {
+            tx: Arc::new(tx),
+            cancellation_token: CancellationToken::new(),
+        };
+
+        if let Ok(mut jobs) = self.jobs.write() {
+            jobs.push_back(handle.clone());
+
+            // Drop the oldest finished jobs beyond the retention limit
+            let mut finished = jobs
+                .iter()
+                .filter(|j| j.snapshot().status.is_finished())
+                .count();
+            jobs.retain(|j| {
+                if finished > MAX_FINISHED_JOBS && j.snapshot().status.is_finished() {
+                    finished -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        handle
+    }
+
+    fn find(&self, id: Uuid) -> Option<BatchJobHandle> {
+        let jobs = self.jobs.read().ok()?;
+        jobs.iter().find(|j| j.id() == id).cloned()
+    }
+
+    /// Current snapshot of a job
+    pub fn get(&self, id: Uuid) -> Option<BatchJob> {
+        self.find(id).map(|j| j.snapshot())
+    }
+
+    /// Stop a job: no new lines are submitted and its in-flight requests are cancelled
+    pub fn cancel(&self, id: Uuid) -> Option<BatchJob> {
+        let handle = self.find(id)?;
+        if !handle.snapshot().status.is_finished() {
+            handle.cancellation_token.cancel();
+        }
+        Some(handle.snapshot())
+    }
+
+    /// All retained jobs, oldest first
+    pub fn list(&self) -> Vec<BatchJob> {
+        self.jobs
+            .read()
+            .map(|jobs| jobs.iter().map(|j| j.snapshot()).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_job_lifecycle() {
+        let jobs = BatchJobs::new();
+        let handle = jobs.create("nightly.jsonl", "nightly.output.jsonl", None);
+        assert_eq!(
+            jobs.get(handle.id()).unwrap().status,
+            BatchJobStatus::Queued
+        );
+
+        handle.start(3);
+        handle.record(true);
+        handle.record(false);
+        let job = jobs.get(handle.id()).unwrap();
+        assert_eq!(job.status, BatchJobStatus::Running);
+        assert_eq!((job.total, job.completed, job.failed), (3, 2, 1));
+
+        let cancelled = jobs.cancel(handle.id()).unwrap();
+        assert!(handle.cancellation_token().is_cancelled());
+        assert_eq!(cancelled.status, BatchJobStatus::Running);
+
+        handle.finish();
+        let job = jobs.get(handle.id()).unwrap();
+        assert_eq!(job.status, BatchJobStatus::Cancelled);
+        assert!(job.finished_at.is_some());
+        assert!(jobs.cancel(Uuid::new_v4()).is_none());
+    }
+}
@@ -56,7 +56,7 @@ pub enum SchedulingStrategy {
     /// Shortest requests first (minimize latency)
     ShortestFirst,
 
-    /// Priority-based (future enhancement)
+    /// Priority class first (interactive, batch, background), FIFO within a class
     Priority,
 
     /// Dynamic adaptive (future enhancement)
@@ -307,6 +307,17 @@ impl BatchManager {
                 self.pending.extend(requests);
             }
 
+            SchedulingStrategy::Priority => {
+                // Stable sort keeps arrival order within a class
+                let mut requests: Vec<_> = self.pending.drain(..).collect();
+                requests.sort_by_key(|r| r.params.priority.rank());
+
+                batch.extend(requests.drain(..batch_size.min(requests.len())));
+
+                // Put remaining back
+                self.pending.extend(requests);
+            }
+
             _ => {
                 // Default to FIFO for now
                 for _ in 0..batch_size {
@@ -0,0 +1,273 @@
+//! Jinja chat templates
+//!
+//! Renders the model's own `tokenizer.chat_template` (GGUF metadata) with a
+//! Jinja-compatible engine, the way Hugging Face tokenizers do. The hardcoded
+//! `TemplateType` formats remain as fallbacks for models without an embedded
+//! template, or when the template fails to render.
+
+use crate::inference::reasoning::disable_thinking;
+use crate::inference::templates::{apply_chat_template, ChatMessage, TemplateType};
+use crate::utils::error::{ExsaError, Result};
+use llama_cpp_2::model::{LlamaModel, Special};
+use minijinja::{context, Environment, Error, ErrorKind};
+use std::sync::Arc;
+use tracing::warn;
+
+/// End-of-turn markers used as stop sequences when a template contains them
+const TURN_END_MARKERS: &[&str] = &[
+    "<|im_end|>",
+    "<|eot_id|>",
+    "<end_of_turn>",
+    "<|end|>",
+    "<|endoftext|>",
+];
+
+/// A compiled-on-demand Jinja chat template with the model's special tokens
+pub struct JinjaChatTemplate {
+    source: String,
+    bos_token: String,
+    eos_token: String,
+    env: Environment<'static>,
+}
+
+impl std::fmt::Debug for JinjaChatTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JinjaChatTemplate")
+            .field("source_len", &self.source.len())
+            .field("bos_token", &self.bos_token)
+            .field("eos_token", &self.eos_token)
+            .finish()
+    }
+}
+
+impl JinjaChatTemplate {
+    /// Create a template, checking that it compiles
+    pub fn new(
+        source: impl Into<String>,
+        bos_token: impl Into<String>,
+        eos_token: impl Into<String>,
+    ) -> Result<Self> {
+        let source = source.into();
+        let env = environment();
+        env.template_from_str(&source)
+            .map_err(|e| ExsaError::InvalidParameters(format!("Invalid chat template: {}", e)))?;
+
+        Ok(Self {
+            source,
+            bos_token: bos_token.into(),
+            eos_token: eos_token.into(),
+            env,
+        })
+    }
+
+    /// Read `tokenizer.chat_template` and the BOS/EOS token text from a loaded model
+    pub fn from_model(model: &LlamaModel) -> Option<Self> {
+        let source = model
+            .meta_val_str("tokenizer.chat_template")
+            .ok()
+            .filter(|s| !s.trim().is_empty())?;
+
+        let token_text = |token| {
+            model
+                .token_to_str(token, Special::Tokenize)
+                .unwrap_or_default()
+        };
+        let bos_token = token_text(model.token_bos());
+        let eos_token = token_text(model.token_eos());
+
+        match Self::new(source, bos_token, eos_token) {
+            Ok(template) => Some(template),
+            Err(e) => {
+                warn!("Ignoring embedded chat template: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Render messages to a prompt.
+    ///
+    /// `enable_thinking` is passed through for templates that support it (Qwen3).
+    /// A leading BOS token is stripped because the engine adds it while tokenizing.
+    pub fn render(
+        &self,
+        messages: &[ChatMessage],
+        tools: Option<&serde_json::Value>,
+        add_generation_prompt: bool,
+        enable_thinking: Option<bool>,
+    ) -> Result<String> {
+        let rendered = self
+            .env
+            .render_str(
+                &self.source,
+                context! {
+                    messages => messages,
+                    tools => tools,
+                    bos_token => &self.bos_token,
+                    eos_token => &self.eos_token,
+                    add_generation_prompt => add_generation_prompt,
+                    enable_thinking => enable_thinking,
+                },
+            )
+            .map_err(|e| {
+                ExsaError::InvalidParameters(format!("Chat template render failed: {}", e))
+            })?;
+
+        Ok(match rendered.strip_prefix(self.bos_token.as_str()) {
+            Some(rest) if !self.bos_token.is_empty() => rest.to_string(),
+            _ => rendered,
+        })
+    }
+
+    /// EOS token text plus any known end-of-turn markers the template uses
+    pub fn stop_sequences(&self) -> Vec<String> {
+        let mut stops = Vec::new();
+        if !self.eos_token.is_empty() {
+            stops.push(self.eos_token.clone());
+        }
+        for marker in TURN_END_MARKERS {
+            if self.source.contains(marker) && !stops.iter().any(|s| s == marker) {
+                stops.push(marker.to_string());
+            }
+        }
+        stops
+    }
+}
+
+/// Jinja environment matching Hugging Face's chat template sandbox
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    // Python string/dict methods (.strip(), .startswith(), .items(), ...)
+    env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+    env.add_function(
+        "raise_exception",
+        |msg: String| -> std::result::Result<String, Error> {
+            Err(Error::new(ErrorKind::InvalidOperation, msg))
+        },
+    );
+    env.add_function("strftime_now", |format: String| {
+        use std::fmt::Write;
+        let mut out = String::new();
+        // Invalid format strings render as empty instead of panicking
+        let _ = write!(out, "{}", chrono::Local::now().format(&format));
+        out
+    });
+    env
+}
+
+/// How a model's chat prompt is built
+#[derive(Debug, Clone)]
+pub enum PromptTemplate {
+    /// The model's embedded Jinja template, with a hardcoded fallback
+    Jinja(Arc<JinjaChatTemplate>, TemplateType),
+
+    /// A hardcoded template format
+    Builtin(TemplateType),
+}
+
+impl PromptTemplate {
+    /// Render messages with the generation prompt appended.
+    ///
+    /// `enable_thinking: Some(false)` leaves an empty `<think>` block so reasoning
+    /// models answer directly.
+    pub fn render(
+        &self,
+        messages: &[ChatMessage],
+        tools: Option<&serde_json::Value>,
+        enable_thinking: Option<bool>,
+    ) -> String {
+        let prompt = match self {
+            Self::Jinja(template, fallback) => {
+                match template.render(messages, tools, true, enable_thinking) {
+                    Ok(prompt) => prompt,
+                    Err(e) => {
+                        warn!("{}; falling back to {:?} template", e, fallback);
+                        apply_chat_template(messages, *fallback)
+                    }
+                }
+            }
+            Self::Builtin(template_type) => apply_chat_template(messages, *template_type),
+        };
+
+        if enable_thinking == Some(false) {
+            disable_thinking(prompt)
+        } else {
+            prompt
+        }
+    }
+
+    /// Template-specific stop sequences
+    pub fn stop_sequences(&self) -> Vec<String> {
+        match self {
+            Self::Jinja(template, _) => template.stop_sequences(),
+            Self::Builtin(template_type) => template_type.stop_sequences(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LLAMA3_STYLE: &str = "{{ bos_token }}{% for message in messages %}\
+<|start_header_id|>{{ message['role'] }}<|end_header_id|>\n\n{{ message['content'] | trim }}<|eot_id|>\
+{% endfor %}{% if add_generation_prompt %}<|start_header_id|>assistant<|end_header_id|>\n\n{% endif %}";
+
+    fn msg(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_strips_bos_and_adds_generation_prompt() {
+        let template =
+            JinjaChatTemplate::new(LLAMA3_STYLE, "<|begin_of_text|>", "<|eot_id|>").unwrap();
+        let prompt = template
+            .render(&[msg("user", " Hi ")], None, true, None)
+            .unwrap();
+
+        assert_eq!(
+            prompt,
+            "<|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+<|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(template.stop_sequences(), vec!["<|eot_id|>".to_string()]);
+    }
+
+    #[test]
+    fn test_raise_exception_and_pycompat_methods() {
+        let source = "{% if messages[0].role != 'user' %}{{ raise_exception('first must be user') }}{% endif %}\
+{% for m in messages %}{{ m.content.strip().upper() }}{% endfor %}\
+{% if tools %} tools={{ tools | length }}{% endif %}";
+        let template = JinjaChatTemplate::new(source, "", "</s>").unwrap();
+
+        let tools = serde_json::json!([{"type": "function"}]);
+        let prompt = template
+            .render(&[msg("user", " hey ")], Some(&tools), true, None)
+            .unwrap();
+        assert_eq!(prompt, "HEY tools=1");
+
+        assert!(template
+            .render(&[msg("system", "x")], None, true, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_jinja_falls_back_on_render_error() {
+        let template = JinjaChatTemplate::new("{{ raise_exception('nope') }}", "", "").unwrap();
+        let prompt = PromptTemplate::Jinja(Arc::new(template), TemplateType::ChatML).render(
+            &[msg("user", "Hello")],
+            None,
+            None,
+        );
+        assert!(prompt.starts_with("<|im_start|>user\nHello<|im_end|>"));
+    }
+
+    #[test]
+    fn test_invalid_template_rejected() {
+        assert!(JinjaChatTemplate::new("{% for %}", "", "").is_err());
+    }
+}
@@ -0,0 +1,580 @@
+//! Text embeddings with llama.cpp
+//!
+//! Embedders wrap a GGUF model (a dedicated embedding model such as nomic/bge,
+//! or the chat model as a fallback) and compute pooled, optionally normalized
+//! vectors for a batch of inputs in a single multi-sequence decode.
+
+use crate::inference::InferenceEngine;
+use crate::model::ModelConfig;
+use crate::utils::error::{ExsaError, Result};
+use llama_cpp_2::context::params::LlamaPoolingType;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::{AddBos, LlamaModel};
+use llama_cpp_2::token::LlamaToken;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+/// Maximum number of inputs packed into a single decode
+const MAX_SEQS_PER_BATCH: usize = 32;
+
+/// How token embeddings are pooled into a single vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolingMode {
+    /// Use the pooling declared by the model (falls back to mean if it has none)
+    #[default]
+    Model,
+    /// Average of all token embeddings
+    Mean,
+    /// Embedding of the first (CLS) token
+    Cls,
+    /// Embedding of the last token (decoder-style embedding models)
+    Last,
+}
+
+impl PoolingMode {
+    /// Parse from string, defaulting to `Model` on unknown input
+    pub fn from_str_lossy(s: &str) -> Self {
+        match s.trim().to_lowercase().as_str() {
+            "mean" | "avg" | "average" => PoolingMode::Mean,
+            "cls" | "first" => PoolingMode::Cls,
+            "last" | "eos" => PoolingMode::Last,
+            _ => PoolingMode::Model,
+        }
+    }
+
+    /// Parse from string, returning `None` on unknown input
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "model" | "native" | "model-native" | "default" => Some(PoolingMode::Model),
+            "mean" | "avg" | "average" => Some(PoolingMode::Mean),
+            "cls" | "first" => Some(PoolingMode::Cls),
+            "last" | "eos" => Some(PoolingMode::Last),
+            _ => None,
+        }
+    }
+
+    fn to_llama_type(self) -> LlamaPoolingType {
+        match self {
+            PoolingMode::Model => LlamaPoolingType::Unspecified,
+            PoolingMode::Mean => LlamaPoolingType::Mean,
+            PoolingMode::Cls => LlamaPoolingType::Cls,
+            PoolingMode::Last => LlamaPoolingType::Last,
+        }
+    }
+}
+
+/// A configured embedding model
+#[derive(Debug, Clone)]
+pub struct EmbeddingModelSpec {
+    /// Name clients use in the request's `model` field
+    pub name: String,
+
+    /// Path to the embedding GGUF
+    pub model_path: String,
+}
+
+/// Embeddings configuration
+#[derive(Debug, Clone)]
+pub struct EmbeddingsConfig {
+    /// Dedicated embedding models (empty = use the chat model on CPU)
+    pub models: Vec<EmbeddingModelSpec>,
+
+    /// Default pooling mode
+    pub pooling: PoolingMode,
+
+    /// Context size for embedding contexts
+    pub n_ctx: u32,
+
+    /// Number of GPU layers to offload for dedicated embedding models
+    pub n_gpu_layers: u32,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            models: Vec::new(),
+            pooling: PoolingMode::Model,
+            n_ctx: 2048,
+            n_gpu_layers: 0,
+        }
+    }
+}
+
+impl EmbeddingsConfig {
+    /// Load embeddings configuration from environment variables.
+    ///
+    /// - EXSA_EMBEDDINGS_MODELS=name=/path/a.gguf,name2=/path/b.gguf
+    /// - EXSA_EMBEDDINGS_MODEL_PATH=... (single model, named after the file stem)
+    /// - EXSA_EMBEDDINGS_POOLING=model|mean|cls|last (default: model)
+    /// - EXSA_EMBEDDINGS_CONTEXT_SIZE=... (default: 2048)
+    /// - EXSA_EMBEDDINGS_GPU_LAYERS=... (default: 0)
+    pub fn from_env() -> Self {
+        let defaults = EmbeddingsConfig::default();
+
+        let mut models = std::env::var("EXSA_EMBEDDINGS_MODELS")
+            .map(|v| parse_model_list(&v))
+            .unwrap_or_default();
+
+        if let Ok(path) = std::env::var("EXSA_EMBEDDINGS_MODEL_PATH") {
+            if !path.trim().is_empty() {
+                models.push(EmbeddingModelSpec {
+                    name: model_name_from_path(&path),
+                    model_path: path,
+                });
+            }
+        }
+
+        let pooling = match std::env::var("EXSA_EMBEDDINGS_POOLING") {
+            Ok(v) => PoolingMode::parse(&v).unwrap_or_else(|| {
+                warn!(
+                    "Unknown EXSA_EMBEDDINGS_POOLING '{}' (expected model, mean, cls or last), using {:?}",
+                    v, defaults.pooling
+                );
+                defaults.pooling
+            }),
+            Err(_) => defaults.pooling,
+        };
+
+        let n_ctx = std::env::var("EXSA_EMBEDDINGS_CONTEXT_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.n_ctx);
+
+        let n_gpu_layers = std::env::var("EXSA_EMBEDDINGS_GPU_LAYERS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.n_gpu_layers);
+
+        Self {
+            models,
+            pooling,
+            n_ctx,
+            n_gpu_layers,
+        }
+    }
+}
+
+/// Parse `name=path` pairs separated by commas. Bare paths are named after their file stem.
+pub fn parse_model_list(s: &str) -> Vec<EmbeddingModelSpec> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((name, path)) => EmbeddingModelSpec {
+                name: name.trim().to_string(),
+                model_path: path.trim().to_string(),
+            },
+            None => EmbeddingModelSpec {
+                name: model_name_from_path(entry),
+                model_path: entry.to_string(),
+            },
+        })
+        .collect()
+}
+
+fn model_name_from_path(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("embeddings")
+        .to_string()
+}
+
+/// Embedding computation for a single model
+pub struct Embedder {
+    name: String,
+    model: Arc<LlamaModel>,
+    backend: Arc<LlamaBackend>,
+    config: ModelConfig,
+    pooling: PoolingMode,
+}
+
+impl Embedder {
+    /// Wrap an already-loaded model
+    pub fn new(
+        name: String,
+        model: Arc<LlamaModel>,
+        backend: Arc<LlamaBackend>,
+        config: ModelConfig,
+        pooling: PoolingMode,
+    ) -> Self {
+        Self {
+            name,
+            model,
+            backend,
+            config,
+            pooling,
+        }
+    }
+
+    /// Load a dedicated embedding GGUF with `InferenceEngine::load_auxiliary_model`
+    /// (outside the `ModelManager` and its memory budget).
+    ///
+    /// This is CPU/IO heavy and should be called from a blocking context.
+    pub fn load(
+        engine: &InferenceEngine,
+        spec: &EmbeddingModelSpec,
+        cfg: &EmbeddingsConfig,
+    ) -> Result<Self> {
+        let config = ModelConfig::new(spec.model_path.clone())
+            .with_gpu_layers(cfg.n_gpu_layers)
+            .with_context_size(cfg.n_ctx)
+            .with_batch_size(cfg.n_ctx);
+
+        let model = engine.load_auxiliary_model(spec.name.clone(), config.clone())?;
+        info!("✅ Embedding model loaded: {}", spec.name);
+
+        Ok(Self::new(
+            spec.name.clone(),
+            model,
+            engine.llama_backend(),
+            config,
+            cfg.pooling,
+        ))
+    }
+
+    /// Embedder name (used as the response `model`)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Path of the underlying GGUF
+    pub fn model_path(&self) -> &str {
+        &self.config.model_path
+    }
+
+    /// Embed all inputs.
+    ///
+    /// Returns one vector per input (empty inputs yield empty vectors) and the
+    /// number of tokens processed. Blocking: call from `spawn_blocking`.
+    pub fn embed(
+        &self,
+        inputs: &[String],
+        pooling: Option<PoolingMode>,
+        normalize: bool,
+    ) -> Result<(Vec<Vec<f32>>, usize)> {
+        let pooling = pooling.unwrap_or(self.pooling);
+        let n_ctx = self.config.n_ctx as usize;
+
+        let mut tokenized: Vec<Vec<LlamaToken>> = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let mut tokens = self
+                .model
+                .str_to_token(input, AddBos::Always)
+                .map_err(|e| ExsaError::InvalidParameters(format!("Tokenization failed: {e}")))?;
+            if input.is_empty() {
+                tokens.clear();
+            }
+            if tokens.len() > n_ctx {
+                warn!(
+                    "Embeddings input truncated from {} to {} tokens",
+                    tokens.len(),
+                    n_ctx
+                );
+                tokens.truncate(n_ctx);
+            }
+            tokenized.push(tokens);
+        }
+        let total_tokens = tokenized.iter().map(|t| t.len()).sum();
+
+        // Size the context to this request so short inputs don't allocate a huge KV cache.
+        // Every sequence gets room for the longest input, within the configured n_ctx.
+        let longest = tokenized.iter().map(|t| t.len()).max().unwrap_or(0).max(1);
+        let n_seq = inputs
+            .len()
+            .clamp(1, MAX_SEQS_PER_BATCH)
+            .min((n_ctx / longest).max(1));
+        let batch_budget = longest * n_seq;
+
+        // Non-causal embedding models need each sequence within a single ubatch.
+        let threads = (self.config.n_threads as i32).max(1);
+        let ctx_params = self
+            .config
+            .into_context_params()
+            .with_n_ctx(std::num::NonZero::new(batch_budget as u32))
+            .with_n_batch(batch_budget as u32)
+            .with_n_ubatch(batch_budget as u32)
+            .with_n_seq_max(n_seq as u32)
+            .with_embeddings(true)
+            .with_pooling_type(pooling.to_llama_type())
+            .with_n_threads(threads)
+            .with_n_threads_batch(threads);
+
+        let mut ctx = self
+            .model
+            .new_context(&self.backend, ctx_params)
+            .map_err(|e| {
+                ExsaError::InternalError(format!("Failed to create embeddings context: {e}"))
+            })?;
+
+        let mut out: Vec<Vec<f32>> = vec![Vec::new(); inputs.len()];
+        let pending: Vec<usize> = (0..tokenized.len())
+            .filter(|&i| !tokenized[i].is_empty())
+            .collect();
+        let mut start = 0usize;
+
+        while start < pending.len() {
+            // Greedily pack inputs until the token or sequence budget is reached.
+            let mut end = start;
+            let mut batch_tokens = 0usize;
+            while end < pending.len()
+                && end - start < n_seq
+                && (end == start || batch_tokens + tokenized[pending[end]].len() <= batch_budget)
+            {
+                batch_tokens += tokenized[pending[end]].len();
+                end += 1;
+            }
+
+            ctx.clear_kv_cache();
+            let mut batch = LlamaBatch::new(batch_tokens, (end - start) as i32);
+            for (seq, &idx) in pending[start..end].iter().enumerate() {
+                batch
+                    .add_sequence(&tokenized[idx], seq as i32, true)
+                    .map_err(|e| ExsaError::InternalError(format!("Batch build failed: {e}")))?;
+            }
+
+            ctx.encode(&mut batch)
+                .map_err(|e| ExsaError::InternalError(format!("Embeddings encode failed: {e}")))?;
+
+            let mut offset = 0usize;
+            for (seq, &idx) in pending[start..end].iter().enumerate() {
+                let n_tokens = tokenized[idx].len();
+                let mut vector = match ctx.embeddings_seq_ith(seq as i32) {
+                    Ok(pooled) => pooled.to_vec(),
+                    // Model has no pooling of its own: average the token embeddings.
+                    Err(_) => {
+                        let mut sum = Vec::new();
+                        for i in offset..offset + n_tokens {
+                            let emb = ctx.embeddings_ith(i as i32).map_err(|e| {
+                                ExsaError::InternalError(format!("Embeddings read failed: {e}"))
+                            })?;
+                            if sum.is_empty() {
+                                sum = vec![0.0f32; emb.len()];
+                            }
+                            for (dst, src) in sum.iter_mut().zip(emb.iter()) {
+                                *dst += *src;
+                            }
+                        }
+                        let denom = n_tokens as f32;
+                        sum.iter_mut().for_each(|v| *v /= denom);
+                        sum
+                    }
+                };
+
+                if normalize {
+                    l2_normalize(&mut vector);
+                }
+                out[idx] = vector;
+                offset += n_tokens;
+            }
+
+            start = end;
+        }
+
+        Ok((out, total_tokens))
+    }
+}
+
+/// Registry of embedders, keyed by model name
+pub struct EmbeddingsRegistry {
+    config: EmbeddingsConfig,
+    embedders: HashMap<String, Arc<Embedder>>,
+
+    /// Fallback embedder built from the chat model on CPU (keyed by chat model path).
+    chat_fallback: tokio::sync::Mutex<Option<Arc<Embedder>>>,
+}
+
+impl EmbeddingsRegistry {
+    /// Load all configured embedding models. Failed loads are logged and skipped.
+    ///
+    /// This is CPU/IO heavy and should be called from a blocking context.
+    pub fn load(engine: &InferenceEngine, config: EmbeddingsConfig) -> Self {
+        let mut embedders = HashMap::new();
+        for spec in &config.models {
+            match Embedder::load(engine, spec, &config) {
+                Ok(e) => {
+                    embedders.insert(spec.name.clone(), Arc::new(e));
+                }
+                Err(e) => warn!("❌ Embedding model '{}' failed to load: {}", spec.name, e),
+            }
+        }
+
+        Self {
+            config,
+            embedders,
+            chat_fallback: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Names of the dedicated embedding models
+    pub fn model_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.embedders.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Resolve the embedder for a request's `model` field.
+    ///
+    /// With dedicated models configured, an unknown name is an error and no name
+    /// selects the first configured model. Without dedicated models, the chat
+    /// model is used on CPU.
+    pub async fn resolve(
+        &self,
+        engine: &InferenceEngine,
+        model: Option<&str>,
+    ) -> Result<Arc<Embedder>> {
+        if !self.embedders.is_empty() {
+            if let Some(name) = model.filter(|m| !m.trim().is_empty()) {
+                return self.embedders.get(name).cloned().ok_or_else(|| {
+                    ExsaError::InvalidParameters(format!(
+                        "Unknown embeddings model '{}'. Available: {}",
+                        name,
+                        self.model_names().join(", ")
+                    ))
+                });
+            }
+
+            let first = self
+                .config
+                .models
+                .iter()
+                .find_map(|spec| self.embedders.get(&spec.name));
+            if let Some(e) = first {
+                return Ok(e.clone());
+            }
+        }
+
+        self.chat_model_embedder(engine).await
+    }
+
+    /// Embedder backed by the current chat model, reloaded on CPU when the chat model changes.
+    ///
+    /// IMPORTANT: llama.cpp embeddings on Metal has proven crash-prone on some setups.
+    /// To make RAG reliable, run embeddings using a CPU-only model instance.
+    async fn chat_model_embedder(&self, engine: &InferenceEngine) -> Result<Arc<Embedder>> {
+        let engine_cfg = engine.current_model_config();
+        let mut guard = self.chat_fallback.lock().await;
+
+        if let Some(e) = guard.as_ref() {
+            if e.model_path() == engine_cfg.model_path {
+                return Ok(e.clone());
+            }
+        }
+
+        let mut cpu_cfg = engine_cfg.clone();
+        cpu_cfg.n_gpu_layers = 0;
+        cpu_cfg.n_ctx = cpu_cfg.n_ctx.min(self.config.n_ctx.max(64));
+        cpu_cfg.n_batch = cpu_cfg.n_ctx;
+
+        let backend = engine.llama_backend();
+        let load_cfg = cpu_cfg.clone();
+        let load_backend = backend.clone();
+        let model = tokio::task::spawn_blocking(move || {
+            LlamaModel::load_from_file(
+                &load_backend,
+                std::path::Path::new(&load_cfg.model_path),
+                &load_cfg.into_params(),
+            )
+            .map(Arc::new)
+        })
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("Embeddings model load join failed: {e}")))?
+        .map_err(|e| ExsaError::InternalError(format!("Embeddings model load failed: {e}")))?;
+
+        // Chat models have no pooling head: default to mean pooling.
+        let pooling = match self.config.pooling {
+            PoolingMode::Model => PoolingMode::Mean,
+            p => p,
+        };
+
+        let embedder = Arc::new(Embedder::new(
+            model_name_from_path(&cpu_cfg.model_path),
+            model,
+            backend,
+            cpu_cfg,
+            pooling,
+        ));
+        *guard = Some(embedder.clone());
+        Ok(embedder)
+    }
+}
+
+/// Scale a vector to unit L2 norm (zero vectors are left unchanged)
+pub fn l2_normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Truncate to `dimensions` (Matryoshka-style), re-normalizing when requested
+pub fn truncate_dimensions(v: &mut Vec<f32>, dimensions: usize, normalize: bool) {
+    if dimensions < v.len() {
+        v.truncate(dimensions);
+        if normalize {
+            l2_normalize(v);
+        }
+    }
+}
+
+/// Encode a vector as base64 of little-endian f32 bytes (OpenAI `encoding_format: base64`)
+pub fn encode_base64(v: &[f32]) -> String {
+    use base64::Engine as _;
+
+    let bytes: Vec<u8> = v.iter().flat_map(|x| x.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_l2_normalize() {
+        let mut v = vec![3.0, 4.0];
+        l2_normalize(&mut v);
+        assert!((v[0] - 0.6).abs() < 1e-6);
+        assert!((v[1] - 0.8).abs() < 1e-6);
+
+        let mut zero = vec![0.0, 0.0];
+        l2_normalize(&mut zero);
+        assert_eq!(zero, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_truncate_dimensions_renormalizes() {
+        let mut v = vec![3.0, 4.0, 12.0];
+        truncate_dimensions(&mut v, 2, true);
+        assert_eq!(v.len(), 2);
+        assert!((v[0] - 0.6).abs() < 1e-6);
+
+        let mut short = vec![1.0];
+        truncate_dimensions(&mut short, 8, true);
+        assert_eq!(short, vec![1.0]);
+    }
+
+    #[test]
+    fn test_encode_base64_little_endian() {
+        // 1.0f32 = 0x3f800000 -> bytes [00, 00, 80, 3f]
+        assert_eq!(encode_base64(&[1.0]), "AACAPw==");
+    }
+
+    #[test]
+    fn test_parse_model_list() {
+        let specs = parse_model_list("nomic=/models/nomic.gguf, /models/bge-small.gguf,");
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].name, "nomic");
+        assert_eq!(specs[0].model_path, "/models/nomic.gguf");
+        assert_eq!(specs[1].name, "bge-small");
+    }
+
+    #[test]
+    fn test_pooling_parse() {
+        assert_eq!(PoolingMode::parse("CLS"), Some(PoolingMode::Cls));
+        assert_eq!(PoolingMode::parse("model-native"), Some(PoolingMode::Model));
+        assert_eq!(PoolingMode::parse("max"), None);
+        assert_eq!(PoolingMode::from_str_lossy("max"), PoolingMode::Model);
+    }
+}
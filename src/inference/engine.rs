@@ -1,11 +1,15 @@
 //! Inference engine with GPU-accelerated llama.cpp integration
 
 use crate::api::schema::ModelInfo;
-use crate::inference::queue::{InferenceRequest, TokenResponse};
-use crate::model::ModelConfig;
+use crate::inference::chat_template::JinjaChatTemplate;
+use crate::inference::queue::{InferenceRequest, PromptTracker, TokenResponse};
+use crate::inference::reasoning::{FORCED_THINK_END, THINK_END, THINK_START};
+use crate::model::{LoraRegistry, LoraRequest, LoraSet, ModelConfig};
 use crate::utils::error::{ExsaError, Result};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 use tracing::{info, warn};
 
 // llama-cpp-2 imports
@@ -16,9 +20,92 @@ use llama_cpp_2::model::{AddBos, LlamaModel, Special};
 use llama_cpp_2::sampling::LlamaSampler;
 use llama_cpp_2::token::LlamaToken;
 
+use serde::Serialize;
 use std::sync::mpsc::{channel, Sender};
 use std::thread;
 
+/// Lifecycle state of the engine's model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineState {
+    /// Model loaded, accepting requests
+    Ready,
+
+    /// Unload in progress: already dispatched work drains, new requests are rejected
+    Unloading,
+
+    /// Loading a model with none in memory; requests are rejected until it is ready
+    Loading,
+
+    /// Model loaded, running the warm-up decode; requests are rejected until it is ready
+    Warming,
+
+    /// No model in memory; a load brings the engine back
+    ModelNotLoaded,
+
+    /// Unloaded after sitting idle (EXSA_IDLE_UNLOAD_SECS); the next request reloads it
+    Idle,
+}
+
+/// Message to the background inference thread
+enum WorkerMessage {
+    Infer(Box<InferenceCommand>),
+
+    /// Drop the cached context, LoRA adapters and model, then acknowledge
+    Unload(Sender<()>),
+}
+
+/// Embedded chat template cached per model path
+type CachedChatTemplate = Option<(String, Option<Arc<JinjaChatTemplate>>)>;
+
+/// Requests in flight (from `process_request` until the inference thread is done
+/// with them) and when the last one finished, for idle unloading
+struct ActivityTracker {
+    started: std::time::Instant,
+    in_flight: AtomicUsize,
+    last_active_ms: AtomicU64,
+}
+
+impl ActivityTracker {
+    fn new() -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            in_flight: AtomicUsize::new(0),
+            last_active_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn begin(self: &Arc<Self>) -> ActivityGuard {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        ActivityGuard(self.clone())
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Nothing in flight and nothing finished for at least `ttl`
+    fn idle_for(&self, ttl: std::time::Duration) -> bool {
+        self.in_flight.load(Ordering::SeqCst) == 0
+            && self
+                .now_ms()
+                .saturating_sub(self.last_active_ms.load(Ordering::SeqCst))
+                >= ttl.as_millis() as u64
+    }
+}
+
+/// Marks a request in flight until dropped
+struct ActivityGuard(Arc<ActivityTracker>);
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        self.0
+            .last_active_ms
+            .store(self.0.now_ms(), Ordering::SeqCst);
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// Command sent to the background inference thread
 struct InferenceCommand {
     model: Arc<LlamaModel>,
@@ -26,9 +113,19 @@ struct InferenceCommand {
     config: ModelConfig,
     prompt: String,
     params: crate::inference::SamplingParams,
+    loras: Vec<(PathBuf, f32)>,
+
+    /// Adapters for `loras`, initialized on `model` (None when `loras` is empty)
+    lora_set: Option<Arc<LoraSet>>,
     token_tx: tokio::sync::mpsc::Sender<TokenResponse>,
     completion_tx: tokio::sync::oneshot::Sender<std::result::Result<(), String>>,
     request_id: uuid::Uuid,
+
+    /// Prefill progress for the API (None for internal requests)
+    prompt_progress: Option<Arc<PromptTracker>>,
+
+    /// Held until the inference thread is done with the command
+    activity: Option<ActivityGuard>,
 }
 
 /// Core inference engine with GPU acceleration via Metal
@@ -49,31 +146,121 @@ pub struct InferenceEngine {
     speculative_engine: Option<Arc<crate::inference::SpeculativeEngine>>,
 
     /// Channel to background inference thread
-    command_tx: Sender<InferenceCommand>,
+    command_tx: Sender<WorkerMessage>,
+
+    /// Model lifecycle state (write-locked while unloading so no request slips past it)
+    state: Arc<RwLock<EngineState>>,
+
+    /// LoRA adapters available to requests (None = LoRA disabled)
+    lora_registry: Option<Arc<LoraRegistry>>,
+
+    /// Active model's `tokenizer.chat_template`, read once per model path
+    chat_template: Arc<RwLock<CachedChatTemplate>>,
+
+    /// Request activity, for idle unloading
+    activity: Arc<ActivityTracker>,
+
+    /// The model was unloaded for being idle and comes back on the next request
+    idle_unloaded: AtomicBool,
+
+    /// Serializes idle unloads with the reloads that undo them
+    reload_lock: tokio::sync::Mutex<()>,
+}
+
+/// Model cache name for a GGUF path (its file stem)
+fn model_cache_name(path: &std::path::Path) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("model")
+        .to_string()
+}
+
+impl EngineState {
+    /// Error for a request arriving while the engine isn't `Ready`
+    pub fn not_ready_error(self) -> ExsaError {
+        match self {
+            Self::Loading => ExsaError::ServiceUnavailable("Model is loading".to_string()),
+            Self::Warming => ExsaError::ServiceUnavailable("Model is warming up".to_string()),
+            _ => ExsaError::ModelNotLoaded,
+        }
+    }
 }
 
 impl InferenceEngine {
     /// Create a new inference engine with dynamic model management
     pub fn new(model_name: String, model_path: String, config: ModelConfig) -> Result<Self> {
-        info!("Initializing InferenceEngine with ModelManager");
+        // Initialize backend (llama.cpp allows this only once per process)
+        let backend =
+            Arc::new(LlamaBackend::init().map_err(|e| {
+                ExsaError::ModelError(format!("Failed to initialize backend: {}", e))
+            })?);
+
+        Self::with_backend(backend, model_name, model_path, config, None)
+    }
 
-        // Initialize backend
+    /// Create an engine with no model loaded yet; it reports `ModelNotLoaded`
+    /// until `config` is loaded with `preload` and `activate`.
+    ///
+    /// Lets the server bind and report `loading` while the startup model loads.
+    pub fn new_unloaded(config: ModelConfig) -> Result<Self> {
+        // Initialize backend (llama.cpp allows this only once per process)
         let backend =
             Arc::new(LlamaBackend::init().map_err(|e| {
                 ExsaError::ModelError(format!("Failed to initialize backend: {}", e))
             })?);
 
-        // Create model manager with initial model
-        let manager = Arc::new(crate::model::ModelManager::new(
-            model_name,
-            std::path::PathBuf::from(&model_path),
+        // Max 3 models in cache, as with an initial model
+        let manager = Arc::new(crate::model::ModelManager::empty(backend.clone(), 3));
+        Ok(Self::from_manager(
+            manager,
+            backend,
+            config,
+            EngineState::ModelNotLoaded,
+        ))
+    }
+
+    /// Create an engine (own model manager + inference thread) on an existing backend.
+    ///
+    /// Used to serve several models side by side. The adapters in `lora_registry` are
+    /// initialized on the model as it loads. This is CPU/IO heavy and should be
+    /// called from a blocking context.
+    pub fn with_backend(
+        backend: Arc<LlamaBackend>,
+        model_name: String,
+        model_path: String,
+        config: ModelConfig,
+        lora_registry: Option<Arc<LoraRegistry>>,
+    ) -> Result<Self> {
+        info!("Initializing InferenceEngine with ModelManager");
+
+        // Create model manager with initial model (max 3 models in cache)
+        let manager = Arc::new(crate::model::ModelManager::empty(backend.clone(), 3));
+        if let Some(registry) = &lora_registry {
+            manager.set_lora_registry(registry.clone());
+        }
+        manager.load_model(
+            model_name.clone(),
+            PathBuf::from(&model_path),
             config.clone(),
-            backend.clone(),
-            3, // Max 3 models in cache
-        )?);
+        )?;
+        manager.switch_model(&model_name)?;
 
         info!("✅ Model loaded successfully with dynamic loading capability");
 
+        let engine = Self::from_manager(manager, backend, config, EngineState::Ready);
+        Ok(match lora_registry {
+            Some(registry) => engine.with_lora_registry(registry),
+            None => engine,
+        })
+    }
+
+    /// Engine around `manager`, with its inference thread started
+    fn from_manager(
+        manager: Arc<crate::model::ModelManager>,
+        backend: Arc<LlamaBackend>,
+        config: ModelConfig,
+        state: EngineState,
+    ) -> Self {
         // Speculative decoding disabled for now (API mismatch)
         let speculative_engine = None;
 
@@ -84,14 +271,48 @@ impl InferenceEngine {
             Self::background_loop(command_rx);
         });
 
-        Ok(Self {
+        Self {
             manager,
             backend,
             config: Arc::new(std::sync::RwLock::new(config)),
             active_requests: Arc::new(AtomicUsize::new(0)),
             speculative_engine,
             command_tx,
-        })
+            state: Arc::new(RwLock::new(state)),
+            lora_registry: None,
+            chat_template: Arc::new(RwLock::new(None)),
+            activity: Arc::new(ActivityTracker::new()),
+            idle_unloaded: AtomicBool::new(false),
+            reload_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Enable per-request LoRA adapters from the given registry.
+    ///
+    /// Adapters are initialized on models as they load, so call this before loading.
+    pub fn with_lora_registry(mut self, registry: Arc<LoraRegistry>) -> Self {
+        self.manager.set_lora_registry(registry.clone());
+        self.lora_registry = Some(registry);
+        self
+    }
+
+    /// LoRA adapter registry, if enabled
+    pub fn lora_registry(&self) -> Option<Arc<LoraRegistry>> {
+        self.lora_registry.clone()
+    }
+
+    /// Resolve requested LoRA adapters to file paths and scales
+    pub fn resolve_loras(&self, requests: &[LoraRequest]) -> Result<Vec<(PathBuf, f32)>> {
+        if requests.is_empty() {
+            return Ok(vec![]);
+        }
+
+        match &self.lora_registry {
+            Some(registry) => registry.resolve(requests),
+            None => Err(ExsaError::InvalidParameters(
+                "LoRA adapters are not enabled on this server".to_string(),
+            )),
+        }
     }
 
     /// Get model information
@@ -102,11 +323,7 @@ impl InferenceEngine {
             .map(|c| c.clone())
             .unwrap_or_else(|_| ModelConfig::new("unknown"));
 
-        ModelInfo {
-            model_path: cfg.model_path.clone(),
-            context_size: cfg.n_ctx as usize,
-            gpu_layers: cfg.n_gpu_layers as i32,
-        }
+        ModelInfo::from(&cfg)
     }
 
     /// Get the currently active llama.cpp model.
@@ -114,6 +331,209 @@ impl InferenceEngine {
         self.manager.get_active_model()
     }
 
+    /// Model cache name of the active model
+    pub fn active_model_name(&self) -> Result<String> {
+        self.manager.get_active_model_name()
+    }
+
+    /// The active model's embedded Jinja chat template, if it has a usable one
+    pub fn embedded_chat_template(&self) -> Option<Arc<JinjaChatTemplate>> {
+        let model_path = self.current_model_config().model_path;
+        if let Ok(cache) = self.chat_template.read() {
+            if let Some((path, template)) = cache.as_ref() {
+                if *path == model_path {
+                    return template.clone();
+                }
+            }
+        }
+
+        // Not cached while unloaded, so a reload reads the template again
+        let model = self.active_llama_model().ok()?;
+        let template = JinjaChatTemplate::from_model(&model).map(Arc::new);
+        if let Ok(mut cache) = self.chat_template.write() {
+            *cache = Some((model_path, template.clone()));
+        }
+        template
+    }
+
+    /// Load a secondary model (reranker, embedder, ...) owned by the caller.
+    ///
+    /// It stays out of the model cache, so it never takes an LRU slot from (or
+    /// evicts) a chat model. This is CPU/IO heavy and should be called from a
+    /// blocking context.
+    pub fn load_auxiliary_model(
+        &self,
+        name: String,
+        config: ModelConfig,
+    ) -> Result<Arc<LlamaModel>> {
+        crate::model::ModelLoader::new(config.clone()).validate()?;
+
+        info!(
+            "Loading auxiliary model: {} from {}",
+            name, config.model_path
+        );
+        let model =
+            LlamaModel::load_from_file(&self.backend, &config.model_path, &config.into_params())
+                .map_err(|e| {
+                    ExsaError::ModelLoadError(format!("Failed to load model {}: {}", name, e))
+                })?;
+        Ok(Arc::new(model))
+    }
+
+    /// Current model lifecycle state
+    pub fn state(&self) -> EngineState {
+        self.state
+            .read()
+            .map(|s| *s)
+            .unwrap_or(EngineState::ModelNotLoaded)
+    }
+
+    /// Unload the active model and free its memory.
+    ///
+    /// New requests are rejected with `ModelNotLoaded` from the moment this is
+    /// called; requests already handed to the inference thread finish first.
+    /// Blocks until the inference thread has dropped its context and model, so
+    /// call it from a blocking context.
+    pub fn unload(&self) -> Result<Option<String>> {
+        // An idle-unloaded model is already out of memory: just stop the reload
+        if self.transition(EngineState::Idle, EngineState::ModelNotLoaded) {
+            self.idle_unloaded.store(false, Ordering::SeqCst);
+            return Ok(None);
+        }
+        self.unload_to(EngineState::ModelNotLoaded, None)
+    }
+
+    /// Unload the model if no request has been in flight for `ttl`.
+    ///
+    /// The engine reports `Idle` afterwards and the next request reloads the model
+    /// transparently (it waits meanwhile). Returns the unloaded model's name, or
+    /// None if the engine wasn't idle.
+    pub async fn unload_if_idle(
+        self: &Arc<Self>,
+        ttl: std::time::Duration,
+    ) -> Result<Option<String>> {
+        let _guard = self.reload_lock.lock().await;
+        if self.state() != EngineState::Ready || !self.activity.idle_for(ttl) {
+            return Ok(None);
+        }
+
+        // Set first: requests arriving from now on wait for the reload instead of failing
+        self.idle_unloaded.store(true, Ordering::SeqCst);
+        let engine = self.clone();
+        let result =
+            tokio::task::spawn_blocking(move || engine.unload_to(EngineState::Idle, Some(ttl)))
+                .await
+                .map_err(|e| ExsaError::InternalError(format!("Idle unload task failed: {}", e)))
+                .and_then(|r| r);
+
+        if !matches!(result, Ok(Some(_))) {
+            self.idle_unloaded.store(false, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Reload a model unloaded by `unload_if_idle`, waiting for an idle unload in
+    /// progress first (no-op if the model is loaded or was unloaded explicitly)
+    pub async fn reload_if_idle(&self) -> Result<()> {
+        if !self.idle_unloaded.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let _guard = self.reload_lock.lock().await;
+        if !self.idle_unloaded.load(Ordering::SeqCst)
+            || !self.transition(EngineState::Idle, EngineState::Loading)
+        {
+            return Ok(());
+        }
+
+        let cfg = self.current_model_config();
+        info!("📥 Reloading idle-unloaded model: {}", cfg.model_path);
+        let manager = self.manager.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let path = PathBuf::from(&cfg.model_path);
+            let name = model_cache_name(&path);
+            manager.load_model(name.clone(), path, cfg)?;
+            manager.switch_model(&name)
+        })
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("Model reload task failed: {}", e)))
+        .and_then(|r| r);
+
+        match result {
+            Ok(()) => {
+                self.idle_unloaded.store(false, Ordering::SeqCst);
+                self.set_state(EngineState::Ready);
+                Ok(())
+            }
+            Err(e) => {
+                self.set_state(EngineState::Idle);
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether requests are accepted now (an idle-unloaded model reloads on demand)
+    pub fn accepts_requests(&self) -> bool {
+        self.state() == EngineState::Ready || self.idle_unloaded.load(Ordering::SeqCst)
+    }
+
+    /// Drain dispatched work, unload, and end in `final_state`.
+    ///
+    /// With `idle_ttl`, bail out (Ok(None)) unless the engine is still idle once
+    /// new requests are blocked.
+    fn unload_to(
+        &self,
+        final_state: EngineState,
+        idle_ttl: Option<std::time::Duration>,
+    ) -> Result<Option<String>> {
+        let (ack_tx, ack_rx) = channel();
+        {
+            let mut state = self
+                .state
+                .write()
+                .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+            if *state != EngineState::Ready {
+                return Err(ExsaError::ModelNotLoaded);
+            }
+            if idle_ttl.is_some_and(|ttl| !self.activity.idle_for(ttl)) {
+                return Ok(None);
+            }
+            *state = EngineState::Unloading;
+
+            // Queued behind any dispatched requests, so they drain first
+            if let Err(e) = self.command_tx.send(WorkerMessage::Unload(ack_tx)) {
+                *state = EngineState::Ready;
+                return Err(ExsaError::InferenceError(format!(
+                    "Failed to send unload to background thread: {}",
+                    e
+                )));
+            }
+        }
+
+        info!("📤 Unloading model: waiting for the inference thread to drain");
+        let _ = ack_rx.recv();
+
+        let result = self.manager.unload_active();
+        self.set_state(final_state);
+        result
+    }
+
+    fn set_state(&self, new_state: EngineState) {
+        if let Ok(mut state) = self.state.write() {
+            *state = new_state;
+        }
+    }
+
+    /// Move from `from` to `to`; false (and no change) if the engine is in another state
+    fn transition(&self, from: EngineState, to: EngineState) -> bool {
+        match self.state.write() {
+            Ok(mut state) if *state == from => {
+                *state = to;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Get the llama.cpp backend handle.
     pub fn llama_backend(&self) -> Arc<LlamaBackend> {
         self.backend.clone()
@@ -136,21 +556,6 @@ impl InferenceEngine {
         gpu_layers: Option<i32>,
         context_size: Option<usize>,
     ) -> Result<ModelInfo> {
-        // Validate path exists
-        let path = std::path::PathBuf::from(&model_path);
-        if !path.exists() {
-            return Err(ExsaError::InvalidParameters(format!(
-                "Model file not found: {}",
-                model_path
-            )));
-        }
-
-        let name = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("model")
-            .to_string();
-
         // Build config for this model.
         // IMPORTANT: preserve performance-critical settings (GPU layers, context, batch, threads,
         // KV cache quantization, etc.) from the currently active config, unless explicitly overridden.
@@ -169,25 +574,195 @@ impl InferenceEngine {
             cfg = cfg.with_context_size(cs as u32);
         }
 
+        self.load_and_switch_config(cfg)
+    }
+
+    /// Load a model with a fully built configuration and switch it to active.
+    ///
+    /// This is CPU/IO heavy and should be called from a blocking context.
+    pub fn load_and_switch_config(&self, cfg: ModelConfig) -> Result<ModelInfo> {
+        self.load_and_switch_config_with_progress(cfg, |_| {})
+    }
+
+    /// Like `load_and_switch_config`, reporting load progress (0.0..=1.0).
+    ///
+    /// The current model keeps serving until the new one is loaded; the switch
+    /// itself is a pointer swap, and requests already running finish on the old model.
+    pub fn load_and_switch_config_with_progress<F: FnMut(f32) + 'static>(
+        &self,
+        cfg: ModelConfig,
+        on_progress: F,
+    ) -> Result<ModelInfo> {
+        let name = self.preload(&cfg, on_progress)?;
+        self.activate(&name, cfg)
+    }
+
+    /// Load a model into the cache without switching to it; returns its cache name.
+    ///
+    /// With no model in memory the engine reports `Loading` meanwhile. This is
+    /// CPU/IO heavy and should be called from a blocking context.
+    pub fn preload<F: FnMut(f32) + 'static>(
+        &self,
+        cfg: &ModelConfig,
+        on_progress: F,
+    ) -> Result<String> {
+        // Validate path exists
+        let path = std::path::PathBuf::from(&cfg.model_path);
+        if !path.exists() {
+            return Err(ExsaError::InvalidParameters(format!(
+                "Model file not found: {}",
+                cfg.model_path
+            )));
+        }
+
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("model")
+            .to_string();
+
         // Validate before loading (fast fail)
         let loader = crate::model::ModelLoader::new(cfg.clone());
         loader.validate()?;
 
-        // Load into cache (no-op if already present), then switch active
-        self.manager
-            .load_model(name.clone(), path.clone(), cfg.clone())?;
-        self.manager.switch_model(&name)?;
+        let was_unloaded = self.transition(EngineState::ModelNotLoaded, EngineState::Loading);
+
+        // Load into cache (no-op if already present)
+        let result =
+            self.manager
+                .load_model_with_progress(name.clone(), path, cfg.clone(), on_progress);
+        if result.is_err() && was_unloaded {
+            self.set_state(EngineState::ModelNotLoaded);
+        }
+        result.map(|_| name)
+    }
+
+    /// Switch to a model loaded with `preload`
+    pub fn activate(&self, name: &str, cfg: ModelConfig) -> Result<ModelInfo> {
+        let mut state = self
+            .state
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+        self.switch_active(&mut state, name, &cfg)?;
+        *state = EngineState::Ready;
+
+        Ok(ModelInfo::from(&cfg))
+    }
+
+    /// Switch to a model loaded with `preload` and warm it up before any request
+    /// runs on it.
+    ///
+    /// The warm-up is queued on the inference thread together with the switch
+    /// (requests dispatch under the state lock), so it runs after the requests
+    /// already sent to the old model and before any sent to the new one. A switch
+    /// keeps accepting requests, which wait behind the warm-up; a load with
+    /// nothing in memory reports `Warming` until it is done. `prompt` is built
+    /// after the switch, under the state lock, so it must not touch the engine
+    /// state. Blocks; a failed warm-up is logged and the switch stands.
+    pub fn activate_and_warm_up(
+        &self,
+        name: &str,
+        cfg: ModelConfig,
+        prompt: impl FnOnce() -> String,
+    ) -> Result<ModelInfo> {
+        let start = std::time::Instant::now();
+        let sent = {
+            let mut state = self
+                .state
+                .write()
+                .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+            self.switch_active(&mut state, name, &cfg)?;
+            if *state != EngineState::Ready {
+                *state = EngineState::Warming;
+            }
+            self.manager
+                .get_model(name)
+                .and_then(|model| self.send_warm_up(model, &cfg, prompt()))
+        };
+
+        if let Err(e) =
+            sent.and_then(|completion_rx| Self::wait_warm_up(name, completion_rx, start))
+        {
+            warn!("{}", e);
+        }
+        self.transition(EngineState::Warming, EngineState::Ready);
+
+        Ok(ModelInfo::from(&cfg))
+    }
+
+    /// Make `name` the active model. If that fails, a load that started with
+    /// nothing in memory still has nothing to serve.
+    fn switch_active(&self, state: &mut EngineState, name: &str, cfg: &ModelConfig) -> Result<()> {
+        self.idle_unloaded.store(false, Ordering::SeqCst);
+        if let Err(e) = self.manager.switch_model(name) {
+            if matches!(*state, EngineState::Loading | EngineState::Warming) {
+                *state = EngineState::ModelNotLoaded;
+            }
+            return Err(e);
+        }
 
         // Update active config snapshot used by handlers + metrics
         if let Ok(mut w) = self.config.write() {
             *w = cfg.clone();
         }
+        Ok(())
+    }
 
-        Ok(ModelInfo {
-            model_path: cfg.model_path.clone(),
-            context_size: cfg.n_ctx as usize,
-            gpu_layers: cfg.n_gpu_layers as i32,
-        })
+    /// Queue a one-token decode of `prompt` on the inference thread
+    fn send_warm_up(
+        &self,
+        model: Arc<LlamaModel>,
+        cfg: &ModelConfig,
+        prompt: String,
+    ) -> Result<tokio::sync::oneshot::Receiver<std::result::Result<(), String>>> {
+        let (token_tx, _token_rx) = tokio::sync::mpsc::channel(cfg.token_channel_size.max(8));
+        let (completion_tx, completion_rx) = tokio::sync::oneshot::channel();
+
+        let command = InferenceCommand {
+            model,
+            backend: self.backend.clone(),
+            config: cfg.clone(),
+            prompt,
+            params: crate::inference::SamplingParams {
+                max_tokens: 1,
+                ..Default::default()
+            },
+            loras: vec![],
+            lora_set: None,
+            token_tx,
+            completion_tx,
+            request_id: uuid::Uuid::new_v4(),
+            prompt_progress: None,
+            activity: None,
+        };
+
+        self.command_tx
+            .send(WorkerMessage::Infer(Box::new(command)))
+            .map_err(|e| {
+                ExsaError::InferenceError(format!(
+                    "Failed to send warm-up to background thread: {}",
+                    e
+                ))
+            })?;
+        Ok(completion_rx)
+    }
+
+    /// Wait for a warm-up queued with `send_warm_up`
+    fn wait_warm_up(
+        name: &str,
+        completion_rx: tokio::sync::oneshot::Receiver<std::result::Result<(), String>>,
+        start: std::time::Instant,
+    ) -> Result<()> {
+        match completion_rx.blocking_recv() {
+            Ok(Ok(())) => {
+                info!("🔥 Warmed up {} in {}ms", name, start.elapsed().as_millis());
+                Ok(())
+            }
+            Ok(Err(e)) => Err(ExsaError::InferenceError(format!("Warm-up failed: {}", e))),
+            Err(_) => Err(ExsaError::InferenceError(
+                "Warm-up was dropped by the inference thread".to_string(),
+            )),
+        }
     }
 
     /// Get number of active requests
@@ -197,6 +772,12 @@ impl InferenceEngine {
 
     /// Process an inference request with GPU acceleration
     pub async fn process_request(&self, request: InferenceRequest) -> Result<()> {
+        // In flight from here on, so an idle unload can't start underneath this request
+        let activity = self.activity.begin();
+
+        // Bring back a model that was unloaded for being idle
+        self.reload_if_idle().await?;
+
         // Increment active request counter
         self.active_requests.fetch_add(1, Ordering::SeqCst);
 
@@ -208,6 +789,27 @@ impl InferenceEngine {
             return Err(e);
         }
 
+        let loras = match self.resolve_loras(&request.params.lora) {
+            Ok(l) => l,
+            Err(e) => {
+                self.active_requests.fetch_sub(1, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+
+        // Adapters are initialized when the model loads; requests only select them
+        let lora_set = if loras.is_empty() {
+            None
+        } else {
+            match self.lora_set(&loras) {
+                Ok(set) => Some(set),
+                Err(e) => {
+                    self.active_requests.fetch_sub(1, Ordering::SeqCst);
+                    return Err(e);
+                }
+            }
+        };
+
         // Clone active_requests counter for the background task to decrement on completion
         let active_requests = self.active_requests.clone();
 
@@ -226,15 +828,32 @@ impl InferenceEngine {
             active_requests.fetch_sub(1, Ordering::SeqCst);
             spec_result
         } else {
-            // Standard processing with ModelManager
-            // Get active model from manager
-            let model = match self.manager.get_active_model() {
-                Ok(m) => m,
+            // Hold the state read lock until the command is queued, so an unload
+            // can't slip in between fetching the model and dispatching to the worker.
+            let state = match self.state.read() {
+                Ok(state) => state,
                 Err(e) => {
                     active_requests.fetch_sub(1, Ordering::SeqCst);
-                    return Err(e);
+                    return Err(ExsaError::InternalError(format!("Lock error: {}", e)));
                 }
             };
+            if *state != EngineState::Ready {
+                active_requests.fetch_sub(1, Ordering::SeqCst);
+                return Err(state.not_ready_error());
+            }
+
+            // Standard processing with ModelManager
+            // Get active model from manager (the instance holding the adapters, if any)
+            let model = match &lora_set {
+                Some(set) => set.model.clone(),
+                None => match self.manager.get_active_model() {
+                    Ok(m) => m,
+                    Err(e) => {
+                        active_requests.fetch_sub(1, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                },
+            };
             let backend = self.backend.clone();
             let config = self
                 .config
@@ -249,14 +868,22 @@ impl InferenceEngine {
                 config,
                 prompt: request.prompt,
                 params: request.params,
+                loras,
+                lora_set,
                 token_tx: request.token_tx,
                 completion_tx: request.completion_tx,
                 request_id: request.id,
+                prompt_progress: Some(request.prompt_progress),
+                activity: Some(activity),
             };
 
             // Send to background thread
             // Note: active_requests will be decremented by background thread on completion
-            if let Err(e) = self.command_tx.send(command) {
+            let sent = self
+                .command_tx
+                .send(WorkerMessage::Infer(Box::new(command)));
+            drop(state);
+            if let Err(e) = sent {
                 active_requests.fetch_sub(1, Ordering::SeqCst);
                 return Err(ExsaError::InferenceError(format!(
                     "Failed to send command to background thread: {}",
@@ -428,8 +1055,74 @@ impl InferenceEngine {
         Ok(())
     }
 
+    /// The adapters initialized on the active model, if they include all of `loras`
+    fn lora_set(&self, loras: &[(PathBuf, f32)]) -> Result<Arc<LoraSet>> {
+        let name = self.manager.get_active_model_name()?;
+        let paths: Vec<PathBuf> = loras.iter().map(|(path, _)| path.clone()).collect();
+        match self.manager.lora_set(&name)? {
+            Some(set) if set.contains_all(&paths) => Ok(set),
+            _ => Err(ExsaError::InvalidParameters(format!(
+                "LoRA adapter(s) {:?} are not loaded on model '{}' \
+                 (POST /v1/loras/rescan reloads it with newly added adapters)",
+                paths, name
+            ))),
+        }
+    }
+
+    /// Reload the active model from disk if the registered LoRA adapters changed
+    /// since it was loaded. Returns the model's name if it was reloaded.
+    pub async fn reload_loras(&self) -> Result<Option<String>> {
+        if self.lora_registry.is_none() {
+            return Ok(None);
+        }
+        let Ok(name) = self.manager.get_active_model_name() else {
+            return Ok(None);
+        };
+        let manager = self.manager.clone();
+        let reload_name = name.clone();
+        let reloaded = tokio::task::spawn_blocking(move || manager.reload_for_loras(&reload_name))
+            .await
+            .map_err(|e| ExsaError::InternalError(format!("Task join error: {}", e)))??;
+        Ok(reloaded.then_some(name))
+    }
+
+    /// Swap the context's active LoRA adapters from `current` (in `applied`) to
+    /// `desired` (in `set`).
+    fn apply_lora_adapters(
+        ctx: &mut LlamaContext,
+        applied: Option<&LoraSet>,
+        current: &[(PathBuf, f32)],
+        set: Option<&LoraSet>,
+        desired: &[(PathBuf, f32)],
+    ) -> std::result::Result<(), String> {
+        if let Some(applied) = applied {
+            let mut adapters = applied.lock();
+            for (path, _) in current {
+                if let Some(adapter) = adapters.get_mut(path) {
+                    ctx.lora_adapter_remove(adapter)
+                        .map_err(|e| format!("Failed to remove LoRA adapter {:?}: {}", path, e))?;
+                }
+            }
+        }
+
+        if desired.is_empty() {
+            return Ok(());
+        }
+        let set = set.ok_or_else(|| "LoRA adapters not initialized".to_string())?;
+        let mut adapters = set.lock();
+        for (path, scale) in desired {
+            let adapter = adapters
+                .get_mut(path)
+                .ok_or_else(|| format!("LoRA adapter {:?} not initialized", path))?;
+            ctx.lora_adapter_set(adapter, *scale)
+                .map_err(|e| format!("Failed to apply LoRA adapter {:?}: {}", path, e))?;
+        }
+
+        Ok(())
+    }
+
     /// Background loop for stateful inference
-    fn background_loop(rx: std::sync::mpsc::Receiver<InferenceCommand>) {
+    fn background_loop(rx: std::sync::mpsc::Receiver<WorkerMessage>) {
         info!("🧵 Background inference thread started");
 
         // Primary context state
@@ -439,16 +1132,41 @@ impl InferenceEngine {
         let mut kv_cache_pos: usize = 0; // How many tokens are in the KV cache
         let mut kv_offset: usize = 0; // Position offset: KV[0] = tokens[kv_offset]
 
-        'request_loop: while let Ok(cmd) = rx.recv() {
+        // LoRA adapters applied to the context, and the set they come from
+        let mut active_loras: Vec<(PathBuf, f32)> = Vec::new();
+        let mut applied_lora_set: Option<Arc<LoraSet>> = None;
+
+        'request_loop: while let Ok(msg) = rx.recv() {
+            let cmd = match msg {
+                WorkerMessage::Infer(cmd) => *cmd,
+                WorkerMessage::Unload(ack) => {
+                    // Context and adapters reference the model, so drop them first
+                    cached_ctx = None;
+                    applied_lora_set = None;
+                    active_loras.clear();
+                    cached_model = None;
+                    cached_tokens.clear();
+                    kv_cache_pos = 0;
+                    kv_offset = 0;
+                    info!("📤 Inference thread released its context and model");
+                    let _ = ack.send(());
+                    continue 'request_loop;
+                }
+            };
+
             let InferenceCommand {
                 model: cmd_model,
                 backend,
                 config,
                 prompt,
                 params,
+                loras,
+                lora_set,
                 token_tx,
                 completion_tx,
                 request_id,
+                prompt_progress,
+                activity: _activity,
             } = cmd;
 
             info!("🔄 Processing request {} in background", request_id);
@@ -465,6 +1183,9 @@ impl InferenceEngine {
             if needs_reset {
                 info!("🔄 Model changed or not initialized, resetting context");
                 cached_ctx = None;
+                // Adapters belong to the old model and must be freed before it
+                applied_lora_set = None;
+                active_loras.clear();
                 cached_tokens.clear();
                 kv_cache_pos = 0;
                 kv_offset = 0;
@@ -497,7 +1218,10 @@ impl InferenceEngine {
                     .with_n_threads_batch(config.n_threads as i32);
 
                 match model_ref.new_context(&backend, ctx_params) {
-                    Ok(ctx) => cached_ctx = Some(ctx),
+                    Ok(ctx) => {
+                        cached_ctx = Some(ctx);
+                        active_loras.clear();
+                    }
                     Err(e) => {
                         let _ = completion_tx.send(Err(format!("Failed to create context: {}", e)));
                         continue 'request_loop;
@@ -515,6 +1239,35 @@ impl InferenceEngine {
                 }
             };
 
+            // Switch LoRA adapters if this request wants a different set.
+            // KV entries computed under other adapters can't be reused.
+            if loras != active_loras {
+                if let Err(e) = Self::apply_lora_adapters(
+                    ctx,
+                    applied_lora_set.as_deref(),
+                    &active_loras,
+                    lora_set.as_deref(),
+                    &loras,
+                ) {
+                    // The context may be left with a partial adapter set: rebuild it next time.
+                    cached_ctx = None;
+                    applied_lora_set = None;
+                    active_loras.clear();
+                    cached_tokens.clear();
+                    kv_cache_pos = 0;
+                    kv_offset = 0;
+                    let _ = completion_tx.send(Err(e));
+                    continue 'request_loop;
+                }
+                info!("🧩 Active LoRA adapters: {:?}", loras);
+                active_loras = loras.clone();
+                applied_lora_set = lora_set;
+                ctx.clear_kv_cache();
+                cached_tokens.clear();
+                kv_cache_pos = 0;
+                kv_offset = 0;
+            }
+
             // Tokenize prompt
             // Use AddBos::Never if prompt already starts with a BOS token (common for chat templates)
             // This fixes the "double BOS" issue that causes KV cache position mismatches
@@ -629,8 +1382,17 @@ impl InferenceEngine {
                     cached_tokens = tokens.clone();
                 }
 
+                if let Some(progress) = &prompt_progress {
+                    progress.start(tokens.len(), n_past);
+                }
+
                 // Decode new tokens (those beyond n_past)
                 // CRITICAL: positions must remain consecutive in the KV cache.
+                //
+                // Chunks run back-to-back: the context holds a single sequence (seq 0)
+                // and the queue hands over one request at a time, so there is no other
+                // decode step to interleave them with. Mixing prefill chunks into decode
+                // batches under a per-step token budget needs multi-sequence support first.
                 if n_past < tokens.len() {
                     let tokens_to_decode = tokens.len() - n_past;
                     let last_new_idx = tokens_to_decode - 1;
@@ -676,6 +1438,9 @@ impl InferenceEngine {
                                 let _ = completion_tx.send(Err(format!("Decode failed: {}", e)));
                                 continue 'request_loop;
                             }
+                            if let Some(progress) = &prompt_progress {
+                                progress.advance(chunk_end);
+                            }
                         }
                     }
 
@@ -796,6 +1561,11 @@ impl InferenceEngine {
             let mut sent_text = String::new();
             let max_tokens = params.max_tokens as i32;
 
+            // Reasoning budget: past it, `</think>` is fed instead of sampled tokens
+            let mut reasoning_open = prompt.trim_end().ends_with(THINK_START);
+            let mut reasoning_tokens = 0usize;
+            let mut forced_tokens: VecDeque<LlamaToken> = VecDeque::new();
+
             // Extract sampling parameters
             let _temperature = params.temperature;
             let _top_k = params.top_k;
@@ -857,8 +1627,26 @@ impl InferenceEngine {
                     break;
                 }
 
+                // Close the reasoning block instead of sampling past the budget
+                // (a budget of 0 closes it before any reasoning token)
+                if reasoning_open
+                    && forced_tokens.is_empty()
+                    && params
+                        .reasoning_budget
+                        .is_some_and(|budget| reasoning_tokens >= budget)
+                {
+                    info!("🧠 Reasoning budget of {} tokens reached", reasoning_tokens);
+                    forced_tokens = model_ref
+                        .str_to_token(FORCED_THINK_END, AddBos::Never)
+                        .unwrap_or_default()
+                        .into();
+                }
+
                 // Use -1 to sample from the last logits position (llama.cpp convention)
-                let new_token = sampler.sample(ctx, -1);
+                let new_token = match forced_tokens.pop_front() {
+                    Some(token) => token,
+                    None => sampler.sample(ctx, -1),
+                };
                 sampler.accept(new_token);
 
                 // NOTE: We track this token in cached_tokens AFTER decode succeeds
@@ -870,6 +1658,16 @@ impl InferenceEngine {
 
                 generated_text.push_str(&token_str);
 
+                if params.reasoning_budget.is_some() {
+                    if generated_text.ends_with(THINK_START) {
+                        reasoning_open = true;
+                    } else if generated_text.ends_with(THINK_END) {
+                        reasoning_open = false;
+                    } else if reasoning_open && forced_tokens.is_empty() {
+                        reasoning_tokens += 1;
+                    }
+                }
+
                 // Check stop sequences
                 let mut hit_stop = false;
                 for stop_seq in &params.stop_sequences {
@@ -0,0 +1,234 @@
+//! Background model load jobs
+//!
+//! `POST /v1/models/load` with `background: true` loads the new model while the
+//! current one keeps serving, then switches over. Each load is tracked as a job
+//! whose progress (from llama.cpp's load callback) can be polled or streamed.
+
+use crate::api::schema::ModelInfo;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Finished jobs kept for status queries
+const MAX_FINISHED_JOBS: usize = 32;
+
+/// Minimum progress step published to subscribers (llama.cpp reports per tensor)
+const PROGRESS_STEP: f32 = 0.01;
+
+/// Stage of a background load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadJobStatus {
+    /// Waiting for an earlier load or switch to finish
+    Queued,
+
+    /// Reading weights; `progress` goes from 0 to 1
+    Loading,
+
+    /// Running the warm-up decode before the switch (EXSA_WARMUP)
+    Warming,
+
+    /// New model is active
+    Completed,
+
+    /// Load failed; the previous model is still active
+    Failed,
+}
+
+impl LoadJobStatus {
+    /// Whether the job will not change any more
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+/// Snapshot of a load job
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadJob {
+    pub id: Uuid,
+    pub model_path: String,
+    pub status: LoadJobStatus,
+
+    /// Load progress in `0.0..=1.0`
+    pub progress: f32,
+
+    /// Unix timestamps (seconds)
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_info: Option<ModelInfo>,
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or(std::time::Duration::from_secs(0))
+        .as_secs()
+}
+
+/// Writer side of a job, moved into the task that performs the load
+#[derive(Clone)]
+pub struct LoadJobHandle {
+    tx: Arc<watch::Sender<LoadJob>>,
+}
+
+impl LoadJobHandle {
+    pub fn id(&self) -> Uuid {
+        self.tx.borrow().id
+    }
+
+    /// Current state of the job
+    pub fn snapshot(&self) -> LoadJob {
+        self.tx.borrow().clone()
+    }
+
+    pub fn set_status(&self, status: LoadJobStatus) {
+        self.tx.send_modify(|job| job.status = status);
+    }
+
+    /// Record load progress (ignores steps smaller than 1%)
+    pub fn set_progress(&self, progress: f32) {
+        let progress = progress.clamp(0.0, 1.0);
+        self.tx.send_if_modified(|job| {
+            if progress - job.progress >= PROGRESS_STEP || (progress >= 1.0 && job.progress < 1.0) {
+                job.progress = progress;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    pub fn complete(&self, info: ModelInfo) {
+        self.tx.send_modify(|job| {
+            job.status = LoadJobStatus::Completed;
+            job.progress = 1.0;
+            job.finished_at = Some(unix_now());
+            job.model_info = Some(info);
+        });
+    }
+
+    pub fn fail(&self, error: impl Into<String>) {
+        self.tx.send_modify(|job| {
+            job.status = LoadJobStatus::Failed;
+            job.finished_at = Some(unix_now());
+            job.error = Some(error.into());
+        });
+    }
+}
+
+/// Registry of recent load jobs
+#[derive(Default)]
+pub struct LoadJobs {
+    jobs: RwLock<VecDeque<Arc<watch::Sender<LoadJob>>>>,
+}
+
+impl LoadJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a queued job for `model_path`
+    pub fn create(&self, model_path: impl Into<String>) -> LoadJobHandle {
+        let (tx, _) = watch::channel(LoadJob {
+            id: Uuid::new_v4(),
+            model_path: model_path.into(),
+            status: LoadJobStatus::Queued,
+            progress: 0.0,
+            created_at: unix_now(),
+            finished_at: None,
+            error: None,
+            model_info: None,
+        });
+        let tx = Arc::new(tx);
+
+        if let Ok(mut jobs) = self.jobs.write() {
+            jobs.push_back(tx.clone());
+
+            // Drop the oldest finished jobs beyond the retention limit
+            let mut finished = jobs
+                .iter()
+                .filter(|j| j.borrow().status.is_finished())
+                .count();
+            jobs.retain(|j| {
+                if finished > MAX_FINISHED_JOBS && j.borrow().status.is_finished() {
+                    finished -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        LoadJobHandle { tx }
+    }
+
+    fn find(&self, id: Uuid) -> Option<Arc<watch::Sender<LoadJob>>> {
+        let jobs = self.jobs.read().ok()?;
+        jobs.iter().find(|j| j.borrow().id == id).cloned()
+    }
+
+    /// Current snapshot of a job
+    pub fn get(&self, id: Uuid) -> Option<LoadJob> {
+        self.find(id).map(|tx| tx.borrow().clone())
+    }
+
+    /// Receive every update of a job
+    pub fn subscribe(&self, id: Uuid) -> Option<watch::Receiver<LoadJob>> {
+        self.find(id).map(|tx| tx.subscribe())
+    }
+
+    /// All retained jobs, oldest first
+    pub fn list(&self) -> Vec<LoadJob> {
+        self.jobs
+            .read()
+            .map(|jobs| jobs.iter().map(|j| j.borrow().clone()).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_lifecycle() {
+        let jobs = LoadJobs::new();
+        let handle = jobs.create("/models/a.gguf");
+        let mut rx = jobs.subscribe(handle.id()).unwrap();
+
+        handle.set_status(LoadJobStatus::Loading);
+        handle.set_progress(0.004);
+        assert_eq!(jobs.get(handle.id()).unwrap().progress, 0.0);
+        handle.set_progress(0.5);
+        assert_eq!(rx.borrow_and_update().progress, 0.5);
+
+        handle.fail("out of memory");
+        let job = jobs.get(handle.id()).unwrap();
+        assert_eq!(job.status, LoadJobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("out of memory"));
+        assert!(job.finished_at.is_some());
+        assert!(rx.has_changed().unwrap());
+    }
+
+    #[test]
+    fn test_finished_jobs_pruned() {
+        let jobs = LoadJobs::new();
+        let running = jobs.create("running.gguf");
+        for _ in 0..MAX_FINISHED_JOBS + 5 {
+            jobs.create("x.gguf").fail("nope");
+        }
+        jobs.create("y.gguf");
+
+        let list = jobs.list();
+        assert_eq!(list.len(), MAX_FINISHED_JOBS + 2);
+        assert!(jobs.get(running.id()).is_some());
+    }
+}
@@ -1,18 +1,39 @@
+pub mod batch_jobs;
 pub mod batch_manager;
+pub mod chat_template;
 pub mod context;
 pub mod context_config;
+pub mod embeddings;
 pub mod engine;
 pub mod kv_cache;
+pub mod load_jobs;
 pub mod params;
 pub mod queue;
+pub mod reasoning;
+pub mod request_registry;
+pub mod rerank;
+pub mod router;
+pub mod scheduler;
 pub mod speculative;
 pub mod templates;
 
+pub use batch_jobs::{BatchJob, BatchJobHandle, BatchJobStatus, BatchJobs};
 pub use batch_manager::{BatchConfig, BatchManager, BatchMetrics, SchedulingStrategy};
+pub use chat_template::{JinjaChatTemplate, PromptTemplate};
 pub use context::{ContextMessage, ContextUsage, ContextWindowManager, MessageImportance};
 pub use context_config::{ContextConfig, OverflowPolicy, SlotState};
-pub use engine::InferenceEngine;
+pub use embeddings::{Embedder, EmbeddingsConfig, EmbeddingsRegistry, PoolingMode};
+pub use engine::{EngineState, InferenceEngine};
 pub use kv_cache::{CachePoolStats, KVCachePool, MemoryStats, SharedKVCachePool};
+pub use load_jobs::{LoadJob, LoadJobHandle, LoadJobStatus, LoadJobs};
 pub use params::SamplingParams;
-pub use queue::{InferenceRequest, QueueHandle, QueuedRequest, TokenResponse};
+pub use queue::{
+    InferenceRequest, PromptProgress, PromptTracker, QueueHandle, QueuePosition, QueuedRequest,
+    ShedStats, TokenResponse,
+};
+pub use reasoning::{ReasoningDelta, ReasoningParser};
+pub use request_registry::{RequestRecord, RequestRegistry, RequestStatus};
+pub use rerank::{RerankConfig, Reranker};
+pub use router::{ModelRouter, RouterConfig, ServedModel, ServedModelInfo};
+pub use scheduler::{Priority, Scheduler, SchedulerConfig, UserQueueStats};
 pub use speculative::{SpeculativeConfig, SpeculativeEngine};
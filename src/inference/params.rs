@@ -1,5 +1,7 @@
 //! Sampling parameters for inference
 
+use crate::inference::scheduler::Priority;
+use crate::model::lora::LoraRequest;
 use crate::utils::error::{ExsaError, Result};
 use serde::{Deserialize, Serialize};
 
@@ -65,6 +67,27 @@ pub struct SamplingParams {
     /// If provided, enables session-based context reuse
     #[serde(default)]
     pub session_id: Option<String>,
+
+    /// LoRA adapters to apply on top of the base model (by name, with scale)
+    #[serde(default)]
+    pub lora: Vec<LoraRequest>,
+
+    /// Maximum tokens inside a `<think>` block before `</think>` is forced (None = unlimited)
+    #[serde(default)]
+    pub reasoning_budget: Option<usize>,
+
+    /// Scheduling class in the request queue (set from `X-Priority` or the API key)
+    #[serde(default)]
+    pub priority: Priority,
+
+    /// Fair-share identity in the request queue (API key label, else the request `user`)
+    #[serde(default)]
+    pub user: Option<String>,
+
+    /// Milliseconds the request may wait in the queue before it fails with a timeout
+    /// (set from `X-Deadline-Ms`; capped by EXSA_MAX_QUEUE_WAIT_SECS)
+    #[serde(default)]
+    pub deadline_ms: Option<u64>,
 }
 
 impl Default for SamplingParams {
@@ -89,6 +112,11 @@ impl Default for SamplingParams {
             // Context management defaults
             n_keep: None,
             session_id: None,
+            lora: vec![],
+            reasoning_budget: None,
+            priority: Priority::default(),
+            user: None,
+            deadline_ms: None,
         }
     }
 }
@@ -186,6 +214,15 @@ impl SamplingParams {
             )));
         }
 
+        for adapter in &self.lora {
+            if !adapter.scale.is_finite() {
+                return Err(ExsaError::InvalidParameters(format!(
+                    "LoRA scale for '{}' must be a finite number",
+                    adapter.name
+                )));
+            }
+        }
+
         Ok(())
     }
 }
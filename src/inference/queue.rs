@@ -1,15 +1,26 @@
 //! Request queue for managing concurrent inference requests
+//!
+//! Requests wait in a `Scheduler` (interactive before batch before background,
+//! with aging; weighted fair share across users) and are handed to the engine
+//! one at a time. Requests still queued past their deadline are failed with
+//! `ExsaError::Timeout` instead of being run.
 
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{mpsc, oneshot};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, Notify, Semaphore};
 use tokio_util::sync::CancellationToken;
-use tracing::debug;
+use tracing::{debug, warn};
 use uuid::Uuid;
 
 use crate::inference::engine::InferenceEngine;
 use crate::inference::params::SamplingParams;
+use crate::inference::request_registry::{RequestRecord, RequestRegistry};
+use crate::inference::scheduler::{
+    Priority, Scheduler, SchedulerConfig, UserQueueStats, ANONYMOUS_USER,
+};
+use crate::utils::error::ExsaError;
 
 /// A single inference request
 #[derive(Debug)]
@@ -34,6 +45,16 @@ pub struct InferenceRequest {
 
     /// Request timeout duration (None = no timeout)
     pub timeout_duration: Option<Duration>,
+
+    /// Prompt processing progress, updated by the engine during prefill
+    pub prompt_progress: Arc<PromptTracker>,
+}
+
+impl InferenceRequest {
+    /// Fair-share identity (request `user` or API key label)
+    pub fn user(&self) -> &str {
+        self.params.user.as_deref().unwrap_or(ANONYMOUS_USER)
+    }
 }
 
 /// Response for a single generated token
@@ -49,35 +70,593 @@ pub struct TokenResponse {
     pub request_id: Uuid,
 }
 
+/// How far the engine is through a request's prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PromptProgress {
+    /// Prompt tokens in the KV cache so far, including reused ones
+    pub processed: usize,
+
+    /// Prompt tokens in total
+    pub total: usize,
+
+    /// Prompt tokens reused from the KV cache instead of decoded
+    pub cached: usize,
+}
+
+impl PromptProgress {
+    pub fn is_done(&self) -> bool {
+        self.processed >= self.total
+    }
+}
+
+/// Prompt progress shared between the engine thread and the API
+#[derive(Debug, Default)]
+pub struct PromptTracker {
+    processed: AtomicUsize,
+    total: AtomicUsize,
+    cached: AtomicUsize,
+}
+
+impl PromptTracker {
+    /// Prefill of `total` tokens begins, the first `cached` already in the KV cache
+    pub fn start(&self, total: usize, cached: usize) {
+        self.cached.store(cached, Ordering::Relaxed);
+        self.processed.store(cached, Ordering::Relaxed);
+        // Last, so readers never see a total with stale counts
+        self.total.store(total, Ordering::Release);
+    }
+
+    /// `processed` prompt tokens are in the KV cache
+    pub fn advance(&self, processed: usize) {
+        self.processed.store(processed, Ordering::Relaxed);
+    }
+
+    /// Progress so far (None until prefill has started)
+    pub fn get(&self) -> Option<PromptProgress> {
+        let total = self.total.load(Ordering::Acquire);
+        (total > 0).then(|| PromptProgress {
+            processed: self.processed.load(Ordering::Relaxed).min(total),
+            total,
+            cached: self.cached.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Token channel buffer per request
+///
+/// Buffer size of 100 tokens balances memory usage with streaming throughput.
+/// Larger buffers reduce backpressure but increase memory; 100 is optimal for most cases.
+const TOKEN_CHANNEL_SIZE: usize = 100;
+
+/// Completion error of cancelled requests
+const CANCELLED: &str = "Request cancelled";
+
+/// Weight of the latest request in the throughput averages
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+/// Longest `Retry-After` suggested to shed requests
+const MAX_RETRY_AFTER_SECS: u64 = 300;
+
+/// Requests turned away without running, by reason
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ShedStats {
+    /// No free slot in the queue
+    pub queue_full: u64,
+
+    /// Predicted wait above EXSA_MAX_PREDICTED_WAIT_SECS
+    pub predicted_wait: u64,
+
+    /// User at EXSA_MAX_REQUESTS_PER_USER
+    pub user_limit: u64,
+
+    /// Deadline passed while queued (client deadline or EXSA_MAX_QUEUE_WAIT_SECS)
+    pub expired: u64,
+}
+
+impl ShedStats {
+    /// Add the counts of another queue
+    pub fn add(&mut self, other: &ShedStats) {
+        self.queue_full += other.queue_full;
+        self.predicted_wait += other.predicted_wait;
+        self.user_limit += other.user_limit;
+        self.expired += other.expired;
+    }
+}
+
+/// `Retry-After` for an expected wait (1s when unknown)
+fn retry_after_secs(wait: Option<Duration>) -> u64 {
+    wait.map(|w| w.as_secs_f64().ceil() as u64)
+        .unwrap_or(1)
+        .clamp(1, MAX_RETRY_AFTER_SECS)
+}
+
+/// Where a waiting request stands in the queue
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueuePosition {
+    /// 1 = runs as soon as the current request finishes
+    pub position: usize,
+
+    /// Requests that run before this one, including the one running now
+    pub ahead: usize,
+
+    /// Estimated wait until the request starts (None until a request has finished)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_seconds: Option<f64>,
+
+    /// Estimated start as a Unix timestamp (seconds)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_start: Option<u64>,
+}
+
+/// Recent generation speed, used for queue ETAs
+#[derive(Debug, Default)]
+struct Throughput {
+    /// Moving averages over finished requests
+    tokens_per_sec: Option<f64>,
+    tokens_per_request: Option<f64>,
+}
+
+impl Throughput {
+    fn record(&mut self, tokens: usize, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if tokens == 0 || secs <= 0.0 {
+            return;
+        }
+        let smooth = |avg: Option<f64>, value: f64| {
+            Some(avg.map_or(value, |a| a + THROUGHPUT_SMOOTHING * (value - a)))
+        };
+        self.tokens_per_sec = smooth(self.tokens_per_sec, tokens as f64 / secs);
+        self.tokens_per_request = smooth(self.tokens_per_request, tokens as f64);
+    }
+
+    /// Tokens a request will probably generate
+    fn expected_tokens(&self, max_tokens: usize) -> f64 {
+        let max = max_tokens as f64;
+        self.tokens_per_request.map_or(max, |avg| avg.min(max))
+    }
+}
+
+/// A request waiting in the scheduler
+#[derive(Debug)]
+struct Pending {
+    request: InferenceRequest,
+
+    /// Engine side of `request.completion_tx`
+    done_rx: oneshot::Receiver<Result<(), String>>,
+
+    /// Outcome reported to the submitter
+    completion_tx: oneshot::Sender<Result<(), ExsaError>>,
+
+    queued_at: Instant,
+
+    /// Fails with `ExsaError::Timeout` if still queued at this point
+    deadline: Option<Instant>,
+}
+
+impl Pending {
+    fn expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|d| now >= d)
+    }
+}
+
+/// The request currently on the engine
+struct Running {
+    id: Uuid,
+    expected_tokens: f64,
+    generated: Arc<AtomicUsize>,
+    prompt_progress: Arc<PromptTracker>,
+}
+
+/// Pending requests shared between the queue handles and the worker
+struct QueueShared {
+    scheduler: Mutex<Scheduler<Pending>>,
+
+    throughput: Mutex<Throughput>,
+    running: Mutex<Option<Running>>,
+
+    /// Queued, running and recently finished requests by id
+    registry: RequestRegistry,
+
+    /// Free slots; submitters are turned away when none is left
+    slots: Semaphore,
+
+    /// Admission limit on the predicted queue wait
+    max_predicted_wait: Option<Duration>,
+    shed: Mutex<ShedStats>,
+
+    /// Longest wait in the queue before a request expires
+    max_queue_wait: Option<Duration>,
+
+    /// Wakes the expiry timer when a request with a deadline is queued
+    deadlines: Arc<Notify>,
+
+    /// Wakes the worker on submit and when the last handle goes away
+    wake: Arc<Notify>,
+
+    capacity: usize,
+}
+
+impl QueueShared {
+    fn pop(&self) -> Option<Pending> {
+        self.expire();
+        let pending = self.scheduler.lock().ok()?.pop()?;
+        self.slots.add_permits(1);
+        Some(pending)
+    }
+
+    /// Earliest deadline of a queued request
+    fn next_deadline(&self) -> Option<Instant> {
+        let scheduler = self.scheduler.lock().ok()?;
+        scheduler.iter().filter_map(|p| p.deadline).min()
+    }
+
+    /// Fail queued requests whose deadline has passed, so nobody waits on them
+    fn expire(&self) {
+        let now = Instant::now();
+        loop {
+            let expired = self
+                .scheduler
+                .lock()
+                .ok()
+                .and_then(|mut s| s.remove(|p| p.expired(now)));
+            let Some(pending) = expired else {
+                break;
+            };
+            self.slots.add_permits(1);
+            self.record_shed(|s| s.expired += 1);
+
+            let id = pending.request.id;
+            warn!(
+                "Request {} expired after {:.1}s in the queue",
+                id,
+                pending.queued_at.elapsed().as_secs_f64()
+            );
+            self.registry
+                .finish(id, &Err(ExsaError::Timeout.to_string()));
+            let _ = pending.completion_tx.send(Err(ExsaError::Timeout));
+        }
+    }
+
+    /// Run one request and wait for it to finish before the next is picked.
+    ///
+    /// The engine's worker thread would otherwise receive every request at once in
+    /// submission order, and the scheduler would have nothing left to reorder.
+    async fn run(&self, engine: &InferenceEngine, pending: Pending) {
+        let Pending {
+            mut request,
+            done_rx,
+            completion_tx,
+            ..
+        } = pending;
+        let user = request.user().to_string();
+        debug!(
+            "Processing inference request: {} ({}, user {})",
+            request.id,
+            request.params.priority.as_str(),
+            user
+        );
+
+        let id = request.id;
+        let cancellation_token = request.cancellation_token.clone();
+        if cancellation_token.is_cancelled() {
+            let result = Err(ExsaError::InferenceError(CANCELLED.to_string()));
+            self.complete(id, &user, completion_tx, result);
+            return;
+        }
+
+        // Relay tokens to the client, counting them for the throughput estimate
+        let (token_tx, mut token_rx) = mpsc::channel(TOKEN_CHANNEL_SIZE);
+        let client_tx = std::mem::replace(&mut request.token_tx, token_tx);
+        let generated = self.registry.start(id);
+        let expected_tokens = self
+            .throughput
+            .lock()
+            .map(|t| t.expected_tokens(request.params.max_tokens))
+            .unwrap_or(request.params.max_tokens as f64);
+        if let Ok(mut running) = self.running.lock() {
+            *running = Some(Running {
+                id,
+                expected_tokens,
+                generated: generated.clone(),
+                prompt_progress: request.prompt_progress.clone(),
+            });
+        }
+
+        let started = Instant::now();
+        let relay = {
+            let generated = generated.clone();
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                loop {
+                    let token = tokio::select! {
+                        token = token_rx.recv() => token,
+                        _ = cancellation_token.cancelled() => None,
+                    };
+                    let Some(token) = token else {
+                        break;
+                    };
+                    if !token.done {
+                        generated.fetch_add(1, Ordering::Relaxed);
+                    }
+                    if client_tx.send(token).await.is_err() {
+                        break;
+                    }
+                }
+                // Client gone or cancelled: dropping token_rx makes the engine stop generating
+            }
+        };
+        let process = async {
+            match engine.process_request(request).await {
+                // The speculative path finishes inside process_request and drops the sender
+                Ok(()) => done_rx
+                    .await
+                    .unwrap_or(Ok(()))
+                    .map_err(ExsaError::InferenceError),
+                Err(e) => {
+                    warn!("Request processing failed: {}", e);
+                    Err(e)
+                }
+            }
+        };
+        let ((), result) = tokio::join!(relay, process);
+
+        if !cancellation_token.is_cancelled() {
+            if let Ok(mut throughput) = self.throughput.lock() {
+                throughput.record(generated.load(Ordering::Relaxed), started.elapsed());
+            }
+        }
+        if let Ok(mut running) = self.running.lock() {
+            *running = None;
+        }
+        self.complete(id, &user, completion_tx, result);
+    }
+
+    /// Record the outcome of a dispatched request and tell the submitter
+    fn complete(
+        &self,
+        id: Uuid,
+        user: &str,
+        completion_tx: oneshot::Sender<Result<(), ExsaError>>,
+        result: Result<(), ExsaError>,
+    ) {
+        self.registry
+            .finish(id, &result.as_ref().map_err(|e| e.to_string()).copied());
+        if let Ok(mut scheduler) = self.scheduler.lock() {
+            scheduler.finish(user);
+        }
+        let _ = completion_tx.send(result);
+    }
+
+    /// Cancel a queued or running request
+    fn cancel(&self, id: Uuid) -> Option<RequestRecord> {
+        let record = self.registry.cancel(id)?;
+
+        // Not started yet: take it out of the queue right away
+        let removed = self
+            .scheduler
+            .lock()
+            .ok()
+            .and_then(|mut s| s.remove(|p| p.request.id == id));
+        if let Some(pending) = removed {
+            self.slots.add_permits(1);
+            let e = ExsaError::InferenceError(CANCELLED.to_string());
+            self.registry.finish(id, &Err(e.to_string()));
+            let _ = pending.completion_tx.send(Err(e));
+        }
+
+        self.registry.get(id).or(Some(record))
+    }
+
+    /// Tokens the running request probably still has to generate
+    fn running_tokens(&self) -> Option<f64> {
+        let running = self.running.lock().ok()?;
+        let running = running.as_ref()?;
+        let generated = running.generated.load(Ordering::Relaxed) as f64;
+        Some((running.expected_tokens - generated).max(0.0))
+    }
+
+    /// Prompt progress of request `id` while it runs
+    fn prompt_progress(&self, id: Uuid) -> Option<PromptProgress> {
+        let running = self.running.lock().ok()?;
+        running
+            .as_ref()
+            .filter(|r| r.id == id)
+            .and_then(|r| r.prompt_progress.get())
+    }
+
+    /// Time to generate `tokens` at the recent speed (None until a request has finished)
+    fn time_for(&self, tokens: f64) -> Option<Duration> {
+        let tokens_per_sec = self.throughput.lock().ok()?.tokens_per_sec?;
+        Some(Duration::from_secs_f64(tokens / tokens_per_sec))
+    }
+
+    fn position(&self, id: Uuid) -> Option<QueuePosition> {
+        // One pass over the cached dispatch order: requests ahead and their tokens
+        let (index, mut tokens) = {
+            let mut scheduler = self.scheduler.lock().ok()?;
+            let throughput = self.throughput.lock().ok()?;
+            let mut tokens = 0.0;
+            let mut found = None;
+            for (index, pending) in scheduler.ordered().enumerate() {
+                if pending.request.id == id {
+                    found = Some(index);
+                    break;
+                }
+                tokens += throughput.expected_tokens(pending.request.params.max_tokens);
+            }
+            (found?, tokens)
+        };
+        let mut ahead = index;
+        if let Some(remaining) = self.running_tokens() {
+            tokens += remaining;
+            ahead += 1;
+        }
+
+        let eta_seconds = self.time_for(tokens).map(|eta| eta.as_secs_f64());
+        let estimated_start = eta_seconds.map(|eta| {
+            (std::time::SystemTime::now() + Duration::from_secs_f64(eta))
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or(Duration::from_secs(0))
+                .as_secs()
+        });
+
+        Some(QueuePosition {
+            position: index + 1,
+            ahead,
+            eta_seconds,
+            estimated_start,
+        })
+    }
+
+    /// Expected wait before a new request of `priority` starts
+    fn predicted_wait(&self, priority: Priority) -> Option<Duration> {
+        let queued: f64 = {
+            let scheduler = self.scheduler.lock().ok()?;
+            let throughput = self.throughput.lock().ok()?;
+            scheduler
+                .ahead_of(priority)
+                .map(|p| throughput.expected_tokens(p.request.params.max_tokens))
+                .sum()
+        };
+        self.time_for(queued + self.running_tokens().unwrap_or(0.0))
+    }
+
+    /// Admit a new request: reject it if its predicted wait is too long, else take a slot
+    fn admit(&self, priority: Priority) -> Result<(), ExsaError> {
+        // Expired requests don't count against the new one
+        self.expire();
+
+        if let Some(max_wait) = self.max_predicted_wait {
+            if let Some(wait) = self.predicted_wait(priority).filter(|w| *w > max_wait) {
+                self.record_shed(|s| s.predicted_wait += 1);
+                return Err(ExsaError::Overloaded {
+                    message: format!(
+                        "Predicted queue wait of {:.0}s exceeds {:.0}s",
+                        wait.as_secs_f64(),
+                        max_wait.as_secs_f64()
+                    ),
+                    retry_after_secs: retry_after_secs(Some(wait - max_wait)),
+                });
+            }
+        }
+
+        match self.slots.try_acquire() {
+            Ok(permit) => {
+                // Returned by the worker on dequeue
+                permit.forget();
+                Ok(())
+            }
+            Err(_) => {
+                self.record_shed(|s| s.queue_full += 1);
+                Err(ExsaError::Overloaded {
+                    message: format!("Queue is full ({} requests)", self.capacity),
+                    retry_after_secs: self.retry_after_running(),
+                })
+            }
+        }
+    }
+
+    /// `Retry-After` until the running request is expected to finish
+    fn retry_after_running(&self) -> u64 {
+        retry_after_secs(self.running_tokens().and_then(|t| self.time_for(t)))
+    }
+
+    fn record_shed(&self, count: impl FnOnce(&mut ShedStats)) {
+        if let Ok(mut shed) = self.shed.lock() {
+            count(&mut shed);
+        }
+    }
+
+    fn pending(&self) -> usize {
+        self.scheduler.lock().map(|s| s.len()).unwrap_or(0)
+    }
+}
+
+impl Drop for QueueShared {
+    fn drop(&mut self) {
+        // Let the worker and the expiry timer notice that the queue is gone
+        self.wake.notify_one();
+        self.deadlines.notify_one();
+    }
+}
+
+/// Fail queued requests when their deadline passes, even while a long generation
+/// keeps the worker busy; exits once the queue is gone
+fn spawn_expiry_timer(shared: &Arc<QueueShared>) {
+    let weak = Arc::downgrade(shared);
+    let deadlines = shared.deadlines.clone();
+    tokio::spawn(async move {
+        while let Some(shared) = weak.upgrade() {
+            shared.expire();
+            let next = shared.next_deadline();
+            drop(shared);
+            match next {
+                Some(at) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(at.into()) => {}
+                        _ = deadlines.notified() => {}
+                    }
+                }
+                None => deadlines.notified().await,
+            }
+        }
+    });
+}
+
 /// Request queue for managing concurrent inference requests
 pub struct RequestQueue {
-    /// Channel for submitting inference requests
-    request_tx: mpsc::Sender<InferenceRequest>,
+    shared: Arc<QueueShared>,
 
     /// Queue capacity
     capacity: usize,
 }
 
 impl RequestQueue {
-    /// Create a new request queue
+    /// Create a new request queue (scheduling settings from the environment)
     pub fn new(capacity: usize, engine: Arc<InferenceEngine>) -> Self {
-        let (request_tx, mut request_rx) = mpsc::channel::<InferenceRequest>(capacity);
+        Self::with_config(capacity, engine, SchedulerConfig::from_env())
+    }
 
-        // Spawn worker task to process requests
-        tokio::spawn(async move {
-            while let Some(request) = request_rx.recv().await {
-                debug!("Processing inference request: {}", request.id);
+    /// Create a new request queue with explicit scheduling settings
+    pub fn with_config(
+        capacity: usize,
+        engine: Arc<InferenceEngine>,
+        config: SchedulerConfig,
+    ) -> Self {
+        let wake = Arc::new(Notify::new());
+        let max_predicted_wait = config.max_predicted_wait;
+        let max_queue_wait = config.max_queue_wait;
+        let shared = Arc::new(QueueShared {
+            scheduler: Mutex::new(Scheduler::new(config)),
+            throughput: Mutex::new(Throughput::default()),
+            running: Mutex::new(None),
+            registry: RequestRegistry::new(),
+            slots: Semaphore::new(capacity),
+            max_predicted_wait,
+            shed: Mutex::new(ShedStats::default()),
+            max_queue_wait,
+            deadlines: Arc::new(Notify::new()),
+            wake: wake.clone(),
+            capacity,
+        });
 
-                if let Err(e) = engine.process_request(request).await {
-                    eprintln!("Request processing failed: {}", e);
+        // Spawn worker task to process requests; it exits once every handle is dropped
+        let weak = Arc::downgrade(&shared);
+        tokio::spawn(async move {
+            while let Some(shared) = weak.upgrade() {
+                match shared.pop() {
+                    Some(pending) => shared.run(&engine, pending).await,
+                    None => {
+                        drop(shared);
+                        wake.notified().await;
+                    }
                 }
             }
         });
 
-        Self {
-            request_tx,
-            capacity,
-        }
+        spawn_expiry_timer(&shared);
+
+        Self { shared, capacity }
     }
 
     /// Get queue capacity
@@ -88,7 +667,7 @@ impl RequestQueue {
     /// Get a handle to submit requests to this queue
     pub fn handle(&self) -> QueueHandle {
         QueueHandle {
-            request_tx: self.request_tx.clone(),
+            shared: self.shared.clone(),
         }
     }
 }
@@ -96,7 +675,7 @@ impl RequestQueue {
 /// Handle for submitting requests to the queue
 #[derive(Clone)]
 pub struct QueueHandle {
-    request_tx: mpsc::Sender<InferenceRequest>,
+    shared: Arc<QueueShared>,
 }
 
 impl QueueHandle {
@@ -105,7 +684,7 @@ impl QueueHandle {
         &self,
         prompt: String,
         params: SamplingParams,
-    ) -> Result<QueuedRequest, String> {
+    ) -> Result<QueuedRequest, ExsaError> {
         self.submit_with_timeout(prompt, params, None).await
     }
 
@@ -115,32 +694,100 @@ impl QueueHandle {
         prompt: String,
         params: SamplingParams,
         timeout: Option<Duration>,
-    ) -> Result<QueuedRequest, String> {
+    ) -> Result<QueuedRequest, ExsaError> {
         let request_id = Uuid::new_v4();
-        // Buffer size of 100 tokens balances memory usage with streaming throughput.
-        // Larger buffers reduce backpressure but increase memory; 100 is optimal for most cases.
-        let (token_tx, token_rx) = mpsc::channel(100);
+        let (token_tx, token_rx) = mpsc::channel(TOKEN_CHANNEL_SIZE);
+        let (done_tx, done_rx) = oneshot::channel();
         let (completion_tx, completion_rx) = oneshot::channel();
         let cancellation_token = CancellationToken::new();
+        let priority = params.priority;
+
+        // The client's deadline, capped by the server-wide queue wait
+        let queued_at = Instant::now();
+        let max_wait = params
+            .deadline_ms
+            .map(Duration::from_millis)
+            .into_iter()
+            .chain(self.shared.max_queue_wait)
+            .min();
 
         let request = InferenceRequest {
             id: request_id,
             prompt,
             params,
             token_tx,
-            completion_tx,
+            completion_tx: done_tx,
             cancellation_token: cancellation_token.clone(),
             timeout_duration: timeout,
+            prompt_progress: Arc::default(),
         };
 
-        self.request_tx
-            .send(request)
-            .await
-            .map_err(|_| "Queue is full or closed".to_string())?;
+        let user = request.user().to_string();
+
+        // Fail fast instead of waiting for a slot, so clients can back off
+        if let Err(e) = self.shared.admit(priority) {
+            warn!(
+                "Shedding request {} ({}): {}",
+                request_id,
+                priority.as_str(),
+                e
+            );
+            return Err(e);
+        }
+
+        let max_tokens = request.params.max_tokens;
+        let pending = Pending {
+            request,
+            done_rx,
+            completion_tx,
+            queued_at,
+            deadline: max_wait.map(|wait| queued_at + wait),
+        };
+        let pushed = match self.shared.scheduler.lock() {
+            Ok(mut scheduler) => {
+                let pushed = scheduler.push(pending, priority, &user).is_ok();
+                // Registered under the scheduler lock, before the worker can pick it up
+                if pushed {
+                    self.shared.registry.register(
+                        request_id,
+                        priority,
+                        &user,
+                        max_tokens,
+                        cancellation_token.clone(),
+                    );
+                }
+                Ok(pushed)
+            }
+            Err(e) => Err(ExsaError::InternalError(format!("Lock error: {}", e))),
+        };
+        if !matches!(pushed, Ok(true)) {
+            self.shared.slots.add_permits(1);
+        }
+        if !pushed? {
+            self.shared.record_shed(|s| s.user_limit += 1);
+            let e = ExsaError::TooManyRequests {
+                message: format!("User '{}' has too many requests queued or running", user),
+                retry_after_secs: self.shared.retry_after_running(),
+            };
+            warn!(
+                "Shedding request {} ({}): {}",
+                request_id,
+                priority.as_str(),
+                e
+            );
+            return Err(e);
+        }
+        self.shared.wake.notify_one();
+        if max_wait.is_some() {
+            self.shared.deadlines.notify_one();
+        }
 
         debug!(
-            "Request {} submitted to queue with timeout: {:?}",
-            request_id, timeout
+            "Request {} submitted to queue ({}) with timeout: {:?}, max queue wait: {:?}",
+            request_id,
+            priority.as_str(),
+            timeout,
+            max_wait
         );
 
         Ok(QueuedRequest {
@@ -153,13 +800,66 @@ impl QueueHandle {
 
     /// Get the current queue capacity
     pub fn capacity(&self) -> usize {
-        self.request_tx.capacity()
+        self.shared.capacity.saturating_sub(self.pending_count())
     }
 
     /// Get the number of pending requests in the queue
     pub fn pending_count(&self) -> usize {
-        // The max_capacity is the initial capacity, capacity() is remaining slots
-        self.request_tx.max_capacity() - self.request_tx.capacity()
+        self.shared.pending()
+    }
+
+    /// Position and estimated start of a waiting request (None once it runs or is unknown)
+    pub fn position(&self, id: Uuid) -> Option<QueuePosition> {
+        self.shared.position(id)
+    }
+
+    /// Prompt processing progress of a running request (None before prefill starts)
+    pub fn prompt_progress(&self, id: Uuid) -> Option<PromptProgress> {
+        self.shared.prompt_progress(id)
+    }
+
+    /// Status and timing of a queued, running or recently finished request
+    pub fn request(&self, id: Uuid) -> Option<RequestRecord> {
+        self.shared.registry.get(id)
+    }
+
+    /// Queued and running requests, oldest first
+    pub fn in_flight(&self) -> Vec<RequestRecord> {
+        self.shared.registry.in_flight()
+    }
+
+    /// Cancel a request through its `CancellationToken` (None for unknown ids).
+    ///
+    /// A queued request is dropped from the queue; a running one stops at its next token.
+    pub fn cancel(&self, id: Uuid) -> Option<RequestRecord> {
+        self.shared.cancel(id)
+    }
+
+    /// Requests rejected at admission since startup
+    pub fn shed_stats(&self) -> ShedStats {
+        self.shared
+            .shed
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_default()
+    }
+
+    /// Queued and running requests per user
+    pub fn user_stats(&self) -> Vec<UserQueueStats> {
+        self.shared
+            .scheduler
+            .lock()
+            .map(|s| s.user_stats())
+            .unwrap_or_default()
+    }
+
+    /// Number of pending requests in a priority class
+    pub fn pending_with_priority(&self, priority: Priority) -> usize {
+        self.shared
+            .scheduler
+            .lock()
+            .map(|s| s.pending(priority))
+            .unwrap_or(0)
     }
 }
 
@@ -171,9 +871,105 @@ pub struct QueuedRequest {
     /// Channel to receive generated tokens
     pub token_rx: mpsc::Receiver<TokenResponse>,
 
-    /// Channel to receive completion signal
-    pub completion_rx: oneshot::Receiver<Result<(), String>>,
+    /// Channel to receive completion signal (`ExsaError::Timeout` if the request expired)
+    pub completion_rx: oneshot::Receiver<Result<(), ExsaError>>,
 
     /// Cancellation token to cancel this request
     pub cancellation_token: CancellationToken,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_estimate() {
+        let mut throughput = Throughput::default();
+        assert_eq!(throughput.expected_tokens(256), 256.0);
+
+        throughput.record(100, Duration::from_secs(4));
+        assert_eq!(throughput.tokens_per_sec, Some(25.0));
+        assert_eq!(throughput.expected_tokens(256), 100.0);
+        assert_eq!(throughput.expected_tokens(50), 50.0);
+
+        // Moves part of the way towards newer requests; empty ones are ignored
+        throughput.record(200, Duration::from_secs(4));
+        throughput.record(0, Duration::from_secs(1));
+        assert_eq!(throughput.tokens_per_request, Some(130.0));
+    }
+
+    fn queue_shared() -> QueueShared {
+        QueueShared {
+            scheduler: Mutex::new(Scheduler::new(SchedulerConfig::default())),
+            throughput: Mutex::new(Throughput::default()),
+            running: Mutex::new(None),
+            registry: RequestRegistry::new(),
+            slots: Semaphore::new(0),
+            max_predicted_wait: None,
+            shed: Mutex::new(ShedStats::default()),
+            max_queue_wait: None,
+            deadlines: Arc::new(Notify::new()),
+            wake: Arc::new(Notify::new()),
+            capacity: 2,
+        }
+    }
+
+    /// Queue a request that expires after `wait`, returning its completion receiver
+    fn push_pending(
+        shared: &QueueShared,
+        wait: Duration,
+    ) -> oneshot::Receiver<Result<(), ExsaError>> {
+        let (token_tx, _) = mpsc::channel(1);
+        let (done_tx, done_rx) = oneshot::channel();
+        let (completion_tx, completion_rx) = oneshot::channel();
+        let queued_at = Instant::now();
+        let pending = Pending {
+            request: InferenceRequest {
+                id: Uuid::new_v4(),
+                prompt: String::new(),
+                params: SamplingParams::default(),
+                token_tx,
+                completion_tx: done_tx,
+                cancellation_token: CancellationToken::new(),
+                timeout_duration: None,
+                prompt_progress: Arc::default(),
+            },
+            done_rx,
+            completion_tx,
+            queued_at,
+            deadline: Some(queued_at + wait),
+        };
+        let mut scheduler = shared.scheduler.lock().unwrap();
+        scheduler
+            .push(pending, Priority::Interactive, ANONYMOUS_USER)
+            .unwrap();
+        completion_rx
+    }
+
+    #[test]
+    fn test_expired_requests_fail_with_timeout() {
+        let shared = queue_shared();
+        let mut expired = push_pending(&shared, Duration::ZERO);
+        push_pending(&shared, Duration::from_secs(60));
+
+        let pending = shared.pop().unwrap();
+        assert!(!pending.expired(Instant::now()));
+        assert!(shared.pop().is_none());
+        assert_eq!(shared.slots.available_permits(), 2);
+        assert_eq!(shared.shed.lock().unwrap().expired, 1);
+        assert!(matches!(expired.try_recv(), Ok(Err(ExsaError::Timeout))));
+    }
+
+    #[tokio::test]
+    async fn test_expiry_timer_fails_request_at_deadline() {
+        // Nothing pops: the worker would be busy with a long generation
+        let shared = Arc::new(queue_shared());
+        spawn_expiry_timer(&shared);
+
+        let expired = push_pending(&shared, Duration::from_millis(50));
+        shared.deadlines.notify_one();
+        let result = tokio::time::timeout(Duration::from_secs(2), expired).await;
+        assert!(matches!(result, Ok(Ok(Err(ExsaError::Timeout)))));
+        assert_eq!(shared.next_deadline(), None);
+    }
+}
@@ -0,0 +1,240 @@
+//! Reasoning (`<think>`) output handling
+//!
+//! DeepSeek-R1, Qwen3 and similar models write their chain of thought inside
+//! `<think>…</think>` before the answer. `ReasoningParser` splits the streamed
+//! text into reasoning and content deltas so clients can show (or hide) the two
+//! separately. Some templates open the block in the prompt itself, in which case
+//! the output starts inside the reasoning and only `</think>` appears.
+
+/// Opening reasoning tag
+pub const THINK_START: &str = "<think>";
+
+/// Closing reasoning tag
+pub const THINK_END: &str = "</think>";
+
+/// Text injected to end reasoning early (budget exhausted)
+pub const FORCED_THINK_END: &str = "\n</think>\n\n";
+
+/// A piece of streamed output, split by kind
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReasoningDelta {
+    pub reasoning: String,
+    pub content: String,
+}
+
+impl ReasoningDelta {
+    pub fn is_empty(&self) -> bool {
+        self.reasoning.is_empty() && self.content.is_empty()
+    }
+
+    /// Reasoning text, if any
+    pub fn reasoning(&self) -> Option<String> {
+        Some(self.reasoning.clone()).filter(|s| !s.is_empty())
+    }
+
+    /// Content text, if any
+    pub fn content(&self) -> Option<String> {
+        Some(self.content.clone()).filter(|s| !s.is_empty())
+    }
+}
+
+/// Streaming splitter for `<think>` blocks.
+///
+/// Tags may arrive split across tokens; a possible partial tag at the end of
+/// the input is held back until the next `feed` (or `finish`).
+#[derive(Debug, Clone, Default)]
+pub struct ReasoningParser {
+    in_reasoning: bool,
+    buffer: String,
+
+    /// Drop whitespace right after a tag (models put newlines around it)
+    trim_start: bool,
+}
+
+impl ReasoningParser {
+    /// Create a parser; `in_reasoning` when the prompt already opened the block
+    pub fn new(in_reasoning: bool) -> Self {
+        Self {
+            in_reasoning,
+            buffer: String::new(),
+            trim_start: true,
+        }
+    }
+
+    /// Feed the next piece of generated text
+    pub fn feed(&mut self, text: &str) -> ReasoningDelta {
+        self.buffer.push_str(text);
+        let mut delta = ReasoningDelta::default();
+
+        loop {
+            let tag = if self.in_reasoning {
+                THINK_END
+            } else {
+                THINK_START
+            };
+
+            if let Some(pos) = self.buffer.find(tag) {
+                let before: String = self.buffer.drain(..pos + tag.len()).collect();
+                self.emit(&before[..pos], &mut delta);
+                self.in_reasoning = !self.in_reasoning;
+                self.trim_start = true;
+                continue;
+            }
+
+            // Hold back a suffix that could be the start of the tag
+            let keep = (1..tag.len())
+                .rev()
+                .find(|&n| self.buffer.ends_with(&tag[..n]))
+                .unwrap_or(0);
+            let emit_len = self.buffer.len() - keep;
+            let ready: String = self.buffer.drain(..emit_len).collect();
+            self.emit(&ready, &mut delta);
+            return delta;
+        }
+    }
+
+    /// Flush held-back text at the end of generation
+    pub fn finish(&mut self) -> ReasoningDelta {
+        let rest = std::mem::take(&mut self.buffer);
+        let mut delta = ReasoningDelta::default();
+        self.emit(&rest, &mut delta);
+        delta
+    }
+
+    fn emit(&mut self, text: &str, delta: &mut ReasoningDelta) {
+        let text = if self.trim_start {
+            text.trim_start()
+        } else {
+            text
+        };
+        if text.is_empty() {
+            return;
+        }
+        self.trim_start = false;
+
+        if self.in_reasoning {
+            delta.reasoning.push_str(text);
+        } else {
+            delta.content.push_str(text);
+        }
+    }
+}
+
+/// Whether a rendered prompt leaves a reasoning block open for the model
+pub fn prompt_opens_reasoning(prompt: &str) -> bool {
+    prompt.trim_end().ends_with(THINK_START)
+}
+
+/// Close (or pre-fill an empty) reasoning block so the model answers directly
+pub fn disable_thinking(prompt: String) -> String {
+    let trimmed = prompt.trim_end();
+    if trimmed.ends_with(THINK_END) {
+        // The template already rendered an empty block (Qwen3 `enable_thinking=false`)
+        prompt
+    } else if trimmed.ends_with(THINK_START) {
+        format!("{}{}", trimmed, FORCED_THINK_END)
+    } else {
+        format!("{}{}\n\n{}\n\n", prompt, THINK_START, THINK_END)
+    }
+}
+
+/// Remove reasoning from an earlier assistant turn before it is sent back to the model
+pub fn strip_reasoning(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    // Output of a prompt-opened block has no `<think>`: drop everything before `</think>`
+    if let Some(end) = rest.find(THINK_END) {
+        if !rest[..end].contains(THINK_START) {
+            rest = &rest[end + THINK_END.len()..];
+        }
+    }
+
+    while let Some(start) = rest.find(THINK_START) {
+        out.push_str(&rest[..start]);
+        match rest[start..].find(THINK_END) {
+            Some(end) => rest = &rest[start + end + THINK_END.len()..],
+            None => {
+                rest = "";
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out.trim_start().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(parser: &mut ReasoningParser, pieces: &[&str]) -> ReasoningDelta {
+        let mut total = ReasoningDelta::default();
+        for piece in pieces {
+            let delta = parser.feed(piece);
+            total.reasoning.push_str(&delta.reasoning);
+            total.content.push_str(&delta.content);
+        }
+        let delta = parser.finish();
+        total.reasoning.push_str(&delta.reasoning);
+        total.content.push_str(&delta.content);
+        total
+    }
+
+    #[test]
+    fn test_split_tags_across_tokens() {
+        let mut parser = ReasoningParser::new(false);
+        let out = feed_all(
+            &mut parser,
+            &[
+                "<th",
+                "ink>\nLet me",
+                " think.</",
+                "think>\n\nThe answer",
+                " is 4.",
+            ],
+        );
+        assert_eq!(out.reasoning, "Let me think.");
+        assert_eq!(out.content, "The answer is 4.");
+    }
+
+    #[test]
+    fn test_prompt_opened_reasoning() {
+        assert!(prompt_opens_reasoning("<｜Assistant｜><think>\n"));
+
+        let mut parser = ReasoningParser::new(true);
+        let out = feed_all(&mut parser, &["Hmm", ".\n</think>", "\n\nHi"]);
+        assert_eq!(out.reasoning, "Hmm.\n");
+        assert_eq!(out.content, "Hi");
+    }
+
+    #[test]
+    fn test_plain_output_untouched() {
+        let mut parser = ReasoningParser::new(false);
+        let out = feed_all(&mut parser, &["a < b", " and <b>bold</b>"]);
+        assert_eq!(out.reasoning, "");
+        assert_eq!(out.content, "a < b and <b>bold</b>");
+    }
+
+    #[test]
+    fn test_strip_reasoning() {
+        assert_eq!(strip_reasoning("<think>x</think>\n\nAnswer"), "Answer");
+        assert_eq!(strip_reasoning("x\n</think>\n\nAnswer"), "Answer");
+        assert_eq!(strip_reasoning("No reasoning"), "No reasoning");
+    }
+
+    #[test]
+    fn test_disable_thinking() {
+        assert_eq!(
+            disable_thinking("<|im_start|>assistant\n".to_string()),
+            "<|im_start|>assistant\n<think>\n\n</think>\n\n"
+        );
+        assert_eq!(
+            disable_thinking("<｜Assistant｜><think>\n".to_string()),
+            "<｜Assistant｜><think>\n</think>\n\n"
+        );
+        let closed = "<|im_start|>assistant\n<think>\n\n</think>\n\n".to_string();
+        assert_eq!(disable_thinking(closed.clone()), closed);
+    }
+}
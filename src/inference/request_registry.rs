@@ -0,0 +1,295 @@
+//! Registry of queued, running and recently finished requests
+//!
+//! Each `RequestQueue` records its requests here so operators can look one up
+//! by id (`GET /v1/requests/:id`), list in-flight work and cancel a request
+//! through its `CancellationToken`.
+
+use crate::inference::scheduler::Priority;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// Finished requests kept for status queries
+const MAX_FINISHED_REQUESTS: usize = 256;
+
+/// Stage of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStatus {
+    /// Waiting in the queue
+    Queued,
+
+    /// On the engine
+    Running,
+
+    /// Finished normally
+    Done,
+
+    /// Finished with an error
+    Failed,
+
+    /// Cancelled by an operator (or while still queued); a running request
+    /// stops at its next token
+    Cancelled,
+}
+
+impl RequestStatus {
+    /// Whether the request is still queued or running
+    pub fn is_in_flight(self) -> bool {
+        matches!(self, Self::Queued | Self::Running)
+    }
+}
+
+/// Snapshot of a request
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestRecord {
+    pub id: Uuid,
+    pub status: RequestStatus,
+    pub priority: Priority,
+    pub user: String,
+    pub max_tokens: usize,
+    pub generated_tokens: usize,
+
+    /// Unix timestamps (milliseconds)
+    pub created_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+
+    /// Time spent waiting in the queue, then on the engine (so far, while running)
+    pub queued_ms: u64,
+    pub running_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Tracked {
+    record: RequestRecord,
+    created: Instant,
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    generated: Arc<AtomicUsize>,
+    cancellation_token: CancellationToken,
+}
+
+impl Tracked {
+    fn snapshot(&self) -> RequestRecord {
+        let now = Instant::now();
+        let started = self.started.unwrap_or(now);
+        let mut record = self.record.clone();
+        record.generated_tokens = self.generated.load(Ordering::Relaxed);
+        record.queued_ms = started.duration_since(self.created).as_millis() as u64;
+        record.running_ms = self
+            .started
+            .map(|s| self.finished.unwrap_or(now).duration_since(s).as_millis() as u64)
+            .unwrap_or(0);
+        record
+    }
+}
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Default)]
+struct Inner {
+    requests: HashMap<Uuid, Tracked>,
+
+    /// Finished ids, oldest first (for pruning)
+    finished: VecDeque<Uuid>,
+}
+
+/// Requests of one queue by id
+#[derive(Default)]
+pub struct RequestRegistry {
+    inner: Mutex<Inner>,
+}
+
+impl RequestRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a newly queued request
+    pub fn register(
+        &self,
+        id: Uuid,
+        priority: Priority,
+        user: &str,
+        max_tokens: usize,
+        cancellation_token: CancellationToken,
+    ) {
+        let tracked = Tracked {
+            record: RequestRecord {
+                id,
+                status: RequestStatus::Queued,
+                priority,
+                user: user.to_string(),
+                max_tokens,
+                generated_tokens: 0,
+                created_at: unix_millis(),
+                started_at: None,
+                finished_at: None,
+                queued_ms: 0,
+                running_ms: 0,
+                error: None,
+            },
+            created: Instant::now(),
+            started: None,
+            finished: None,
+            generated: Arc::new(AtomicUsize::new(0)),
+            cancellation_token,
+        };
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.requests.insert(id, tracked);
+        }
+    }
+
+    /// Mark a request as running; returns its generated-token counter
+    pub fn start(&self, id: Uuid) -> Arc<AtomicUsize> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Arc::default();
+        };
+        match inner.requests.get_mut(&id) {
+            Some(tracked) => {
+                tracked.record.status = RequestStatus::Running;
+                tracked.record.started_at = Some(unix_millis());
+                tracked.started = Some(Instant::now());
+                tracked.generated.clone()
+            }
+            None => Arc::default(),
+        }
+    }
+
+    /// Mark a request as finished (cancelled if its token was cancelled)
+    pub fn finish(&self, id: Uuid, result: &Result<(), String>) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let Some(tracked) = inner.requests.get_mut(&id) else {
+            return;
+        };
+        if !tracked.record.status.is_in_flight() && tracked.finished.is_some() {
+            return;
+        }
+
+        tracked.record.status = if tracked.cancellation_token.is_cancelled() {
+            RequestStatus::Cancelled
+        } else if result.is_ok() {
+            RequestStatus::Done
+        } else {
+            RequestStatus::Failed
+        };
+        if let Err(e) = result {
+            tracked.record.error = Some(e.clone());
+        }
+        tracked.record.finished_at = Some(unix_millis());
+        tracked.finished = Some(Instant::now());
+
+        inner.finished.push_back(id);
+        while inner.finished.len() > MAX_FINISHED_REQUESTS {
+            if let Some(old) = inner.finished.pop_front() {
+                inner.requests.remove(&old);
+            }
+        }
+    }
+
+    /// Cancel a request through its token; `None` for unknown ids
+    pub fn cancel(&self, id: Uuid) -> Option<RequestRecord> {
+        let mut inner = self.inner.lock().ok()?;
+        let tracked = inner.requests.get_mut(&id)?;
+        if tracked.record.status.is_in_flight() {
+            tracked.cancellation_token.cancel();
+            tracked.record.status = RequestStatus::Cancelled;
+        }
+        Some(tracked.snapshot())
+    }
+
+    /// Current snapshot of a request
+    pub fn get(&self, id: Uuid) -> Option<RequestRecord> {
+        let inner = self.inner.lock().ok()?;
+        inner.requests.get(&id).map(Tracked::snapshot)
+    }
+
+    /// Queued and running requests, oldest first
+    pub fn in_flight(&self) -> Vec<RequestRecord> {
+        let Ok(inner) = self.inner.lock() else {
+            return Vec::new();
+        };
+        let mut records: Vec<RequestRecord> = inner
+            .requests
+            .values()
+            .filter(|t| t.finished.is_none())
+            .map(Tracked::snapshot)
+            .collect();
+        records.sort_by_key(|r| r.created_at);
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_lifecycle() {
+        let registry = RequestRegistry::new();
+        let id = Uuid::new_v4();
+        registry.register(id, Priority::Batch, "alice", 64, CancellationToken::new());
+        assert_eq!(registry.get(id).unwrap().status, RequestStatus::Queued);
+
+        let generated = registry.start(id);
+        generated.fetch_add(3, Ordering::Relaxed);
+        let running = registry.get(id).unwrap();
+        assert_eq!(running.status, RequestStatus::Running);
+        assert_eq!(running.generated_tokens, 3);
+        assert_eq!(registry.in_flight().len(), 1);
+
+        registry.finish(id, &Err("decode failed".to_string()));
+        let done = registry.get(id).unwrap();
+        assert_eq!(done.status, RequestStatus::Failed);
+        assert_eq!(done.error.as_deref(), Some("decode failed"));
+        assert!(done.finished_at.is_some());
+        assert!(registry.in_flight().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_running_request() {
+        let registry = RequestRegistry::new();
+        let token = CancellationToken::new();
+        let id = Uuid::new_v4();
+        registry.register(id, Priority::Interactive, "bob", 8192, token.clone());
+        registry.start(id);
+
+        let record = registry.cancel(id).unwrap();
+        assert!(token.is_cancelled());
+        assert_eq!(record.status, RequestStatus::Cancelled);
+        // Still in flight until the engine stops
+        assert_eq!(registry.in_flight().len(), 1);
+
+        registry.finish(id, &Ok(()));
+        assert_eq!(registry.get(id).unwrap().status, RequestStatus::Cancelled);
+        assert!(registry.cancel(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn test_finished_requests_pruned() {
+        let registry = RequestRegistry::new();
+        let first = Uuid::new_v4();
+        for i in 0..MAX_FINISHED_REQUESTS + 1 {
+            let id = if i == 0 { first } else { Uuid::new_v4() };
+            registry.register(id, Priority::Interactive, "u", 1, CancellationToken::new());
+            registry.finish(id, &Ok(()));
+        }
+        assert!(registry.get(first).is_none());
+    }
+}
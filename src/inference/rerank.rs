@@ -0,0 +1,320 @@
+//! Cross-encoder reranking with a local GGUF reranker model
+//!
+//! Scores (query, document) pairs with llama.cpp rank pooling. Used by the
+//! `/v1/rerank` endpoint and as a second-stage ranker for RAG retrieval.
+
+use crate::inference::InferenceEngine;
+use crate::model::ModelConfig;
+use crate::utils::error::{ExsaError, Result};
+use llama_cpp_2::context::params::LlamaPoolingType;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::{AddBos, LlamaModel};
+use llama_cpp_2::token::LlamaToken;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Maximum number of (query, document) pairs scored in a single decode
+const MAX_PAIRS_PER_BATCH: usize = 16;
+
+/// Reranker configuration
+#[derive(Debug, Clone)]
+pub struct RerankConfig {
+    /// Path to the reranker GGUF (e.g. bge-reranker-v2-m3)
+    pub model_path: String,
+
+    /// Context size used for scoring (query + document tokens per pair)
+    pub n_ctx: u32,
+
+    /// Number of GPU layers to offload (0 = CPU only)
+    pub n_gpu_layers: u32,
+}
+
+impl RerankConfig {
+    /// Load reranker configuration from environment variables.
+    ///
+    /// - EXSA_RERANK_MODEL_PATH=... (reranking disabled when unset)
+    /// - EXSA_RERANK_CONTEXT_SIZE=... (default: 2048)
+    /// - EXSA_RERANK_GPU_LAYERS=... (default: 0)
+    pub fn from_env() -> Option<Self> {
+        let model_path = std::env::var("EXSA_RERANK_MODEL_PATH")
+            .ok()
+            .filter(|v| !v.trim().is_empty())?;
+
+        let n_ctx = std::env::var("EXSA_RERANK_CONTEXT_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2048);
+
+        let n_gpu_layers = std::env::var("EXSA_RERANK_GPU_LAYERS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        Some(Self {
+            model_path,
+            n_ctx,
+            n_gpu_layers,
+        })
+    }
+}
+
+/// A single reranked document
+#[derive(Debug, Clone, Copy)]
+pub struct RerankScore {
+    /// Index of the document in the input list
+    pub index: usize,
+
+    /// Relevance score in [0, 1] (sigmoid of the reranker logit)
+    pub score: f32,
+}
+
+/// Local cross-encoder reranker
+pub struct Reranker {
+    name: String,
+    model: Arc<LlamaModel>,
+    backend: Arc<LlamaBackend>,
+    config: ModelConfig,
+
+    /// Serialize scoring (llama.cpp backends can be sensitive to concurrent contexts).
+    lock: Mutex<()>,
+}
+
+impl Reranker {
+    /// Load the reranker GGUF as an auxiliary model on the engine's backend.
+    ///
+    /// It bypasses the `ModelManager`, so it neither takes a cache slot nor counts
+    /// against the resident-model memory budget. This is CPU/IO heavy and should be
+    /// called from a blocking context.
+    pub fn load(engine: &InferenceEngine, cfg: &RerankConfig) -> Result<Self> {
+        let name = std::path::Path::new(&cfg.model_path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("reranker")
+            .to_string();
+
+        let config = ModelConfig::new(cfg.model_path.clone())
+            .with_gpu_layers(cfg.n_gpu_layers)
+            .with_context_size(cfg.n_ctx)
+            .with_batch_size(cfg.n_ctx);
+
+        let model = engine.load_auxiliary_model(name.clone(), config.clone())?;
+        info!("✅ Reranker model loaded: {}", name);
+
+        Ok(Self {
+            name,
+            model,
+            backend: engine.llama_backend(),
+            config,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Reranker model name (file stem)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Score every document against the query.
+    ///
+    /// Returns one score per document (same order as the input) and the total
+    /// number of tokens processed. Blocking: call from `spawn_blocking`.
+    pub fn score(&self, query: &str, documents: &[String]) -> Result<(Vec<f32>, usize)> {
+        if documents.is_empty() {
+            return Ok((vec![], 0));
+        }
+
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+
+        let n_ctx = self.config.n_ctx as usize;
+        let pairs = documents
+            .iter()
+            .map(|doc| self.tokenize_pair(query, doc, n_ctx))
+            .collect::<Result<Vec<_>>>()?;
+        let total_tokens = pairs.iter().map(|p| p.len()).sum();
+
+        // Non-causal rerankers need the whole pair in one ubatch.
+        let threads = (self.config.n_threads as i32).max(1);
+        let ctx_params = self
+            .config
+            .into_context_params()
+            .with_n_ubatch(self.config.n_ctx)
+            .with_n_seq_max(MAX_PAIRS_PER_BATCH as u32)
+            .with_embeddings(true)
+            .with_pooling_type(LlamaPoolingType::Rank)
+            .with_n_threads(threads)
+            .with_n_threads_batch(threads);
+
+        let mut ctx = self
+            .model
+            .new_context(&self.backend, ctx_params)
+            .map_err(|e| {
+                ExsaError::InternalError(format!("Failed to create rerank context: {e}"))
+            })?;
+
+        let mut scores = Vec::with_capacity(pairs.len());
+        let mut start = 0usize;
+
+        while start < pairs.len() {
+            // Greedily pack pairs until the token or sequence budget is reached.
+            let mut end = start;
+            let mut batch_tokens = 0usize;
+            while end < pairs.len()
+                && end - start < MAX_PAIRS_PER_BATCH
+                && (end == start || batch_tokens + pairs[end].len() <= n_ctx)
+            {
+                batch_tokens += pairs[end].len();
+                end += 1;
+            }
+
+            ctx.clear_kv_cache();
+            let mut batch = LlamaBatch::new(batch_tokens, (end - start) as i32);
+            for (seq, tokens) in pairs[start..end].iter().enumerate() {
+                batch
+                    .add_sequence(tokens, seq as i32, false)
+                    .map_err(|e| ExsaError::InternalError(format!("Batch build failed: {e}")))?;
+            }
+
+            ctx.encode(&mut batch)
+                .map_err(|e| ExsaError::InternalError(format!("Rerank encode failed: {e}")))?;
+
+            for seq in 0..(end - start) {
+                let logit = ctx
+                    .embeddings_seq_ith(seq as i32)
+                    .map_err(|e| ExsaError::InternalError(format!("Rerank read failed: {e}")))?
+                    .first()
+                    .copied()
+                    .unwrap_or(f32::NEG_INFINITY);
+                scores.push(sigmoid(logit));
+            }
+
+            start = end;
+        }
+
+        Ok((scores, total_tokens))
+    }
+
+    /// Score documents and return them sorted by descending relevance,
+    /// truncated to `top_n` when provided.
+    pub fn rerank(
+        &self,
+        query: &str,
+        documents: &[String],
+        top_n: Option<usize>,
+    ) -> Result<(Vec<RerankScore>, usize)> {
+        let (scores, total_tokens) = self.score(query, documents)?;
+        Ok((rank_scores(&scores, top_n), total_tokens))
+    }
+
+    /// Build the cross-encoder input: `[BOS] query [EOS] [SEP] document [EOS]`.
+    ///
+    /// The document is truncated so the pair fits in the context window.
+    fn tokenize_pair(&self, query: &str, document: &str, n_ctx: usize) -> Result<Vec<LlamaToken>> {
+        let query_tokens = self
+            .model
+            .str_to_token(query, AddBos::Always)
+            .map_err(|e| ExsaError::InvalidParameters(format!("Tokenization failed: {e}")))?;
+        let doc_tokens = self
+            .model
+            .str_to_token(document, AddBos::Never)
+            .map_err(|e| ExsaError::InvalidParameters(format!("Tokenization failed: {e}")))?;
+
+        Ok(pair_tokens(
+            query_tokens,
+            doc_tokens,
+            self.model.token_eos(),
+            n_ctx,
+        ))
+    }
+}
+
+/// Join tokenized query and document as `query [EOS] [SEP] document [EOS]`.
+///
+/// XLM-R / BERT-style rerankers use EOS as the separator token. Tokenizers that
+/// add special tokens already end the query with EOS, so it is only added when
+/// missing.
+fn pair_tokens(
+    mut tokens: Vec<LlamaToken>,
+    mut doc_tokens: Vec<LlamaToken>,
+    eos: LlamaToken,
+    n_ctx: usize,
+) -> Vec<LlamaToken> {
+    if tokens.last() != Some(&eos) {
+        tokens.push(eos);
+    }
+    tokens.push(eos);
+
+    if doc_tokens.last() == Some(&eos) {
+        doc_tokens.pop();
+    }
+    let room = n_ctx.saturating_sub(tokens.len() + 1);
+    tokens.extend(doc_tokens.into_iter().take(room));
+    tokens.push(eos);
+    tokens.truncate(n_ctx.max(1));
+    tokens
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Sort scores by descending relevance (stable for ties) and keep the first `top_n`.
+pub fn rank_scores(scores: &[f32], top_n: Option<usize>) -> Vec<RerankScore> {
+    let mut ranked: Vec<RerankScore> = scores
+        .iter()
+        .enumerate()
+        .map(|(index, &score)| RerankScore { index, score })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    if let Some(n) = top_n {
+        ranked.truncate(n);
+    }
+
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_scores_orders_and_truncates() {
+        let ranked = rank_scores(&[0.1, 0.9, 0.5, 0.9], Some(3));
+        let order: Vec<usize> = ranked.iter().map(|r| r.index).collect();
+        assert_eq!(order, vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_pair_tokens_adds_eos_only_when_missing() {
+        let t = |ids: &[i32]| ids.iter().map(|&id| LlamaToken(id)).collect::<Vec<_>>();
+        let eos = LlamaToken(2);
+
+        // Tokenizer already closed the query with EOS
+        let pair = pair_tokens(t(&[0, 7, 2]), t(&[8, 9]), eos, 64);
+        assert_eq!(pair, t(&[0, 7, 2, 2, 8, 9, 2]));
+
+        // Tokenizer added no EOS
+        let pair = pair_tokens(t(&[0, 7]), t(&[8, 9]), eos, 64);
+        assert_eq!(pair, t(&[0, 7, 2, 2, 8, 9, 2]));
+
+        // Long documents are truncated to fit, keeping the final EOS
+        let pair = pair_tokens(t(&[0, 7]), t(&[8, 9, 10, 11]), eos, 6);
+        assert_eq!(pair, t(&[0, 7, 2, 2, 8, 2]));
+    }
+
+    #[test]
+    fn test_sigmoid_range() {
+        assert!((sigmoid(0.0) - 0.5).abs() < f32::EPSILON);
+        assert!(sigmoid(10.0) > 0.99);
+        assert!(sigmoid(-10.0) < 0.01);
+    }
+}
@@ -0,0 +1,640 @@
+//! Multi-model routing
+//!
+//! Keeps several models resident at once, each with its own `InferenceEngine`
+//! (inference thread + context) and request queue. Requests are routed by the
+//! `model` field (name or alias); unknown names go to the default model.
+//! Names and aliases declared in the models manifest (`models.toml`) are routed
+//! to their declared file with the entry's runtime settings, and loaded on first
+//! use when on-demand loading is enabled, evicting least-recently-used models
+//! to stay within the configured memory budget. The default model (MODEL_PATH)
+//! is never evicted.
+
+use crate::inference::chat_template::PromptTemplate;
+use crate::inference::queue::{QueueHandle, RequestQueue, ShedStats};
+use crate::inference::templates::TemplateType;
+use crate::inference::InferenceEngine;
+use crate::model::{estimate_loaded_bytes, LoraRegistry, ModelEntry, ModelManifest};
+use crate::utils::error::{ExsaError, Result};
+use llama_cpp_2::llama_backend::LlamaBackend;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, info, warn};
+
+/// Router configuration
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    /// Memory budget for resident models in bytes, counting weights and context
+    /// (None = unlimited)
+    pub memory_budget_bytes: Option<u64>,
+
+    /// Maximum number of resident models, including the default
+    pub max_resident_models: usize,
+
+    /// Load models declared in the manifest on their first request
+    pub load_on_demand: bool,
+
+    /// Queue capacity for each model's request queue
+    pub queue_capacity: usize,
+
+    /// Render the chat template embedded in the GGUF file when the manifest names none
+    pub gguf_chat_templates: bool,
+}
+
+impl RouterConfig {
+    /// Load router configuration from environment variables.
+    ///
+    /// - EXSA_MODEL_MEMORY_BUDGET_MB=... (default: unlimited)
+    /// - EXSA_MAX_RESIDENT_MODELS=... (default: 3)
+    /// - EXSA_MODEL_LOAD_ON_DEMAND=true|false (default: false)
+    /// - EXSA_GGUF_CHAT_TEMPLATE=true|false (default: true)
+    pub fn from_env(queue_capacity: usize) -> Self {
+        let memory_budget_bytes = std::env::var("EXSA_MODEL_MEMORY_BUDGET_MB")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|mb| *mb > 0)
+            .map(|mb| mb.saturating_mul(1024 * 1024));
+
+        let max_resident_models = std::env::var("EXSA_MAX_RESIDENT_MODELS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3usize)
+            .max(1);
+
+        let load_on_demand = std::env::var("EXSA_MODEL_LOAD_ON_DEMAND")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+
+        let gguf_chat_templates = std::env::var("EXSA_GGUF_CHAT_TEMPLATE")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(true);
+
+        Self {
+            memory_budget_bytes,
+            max_resident_models,
+            load_on_demand,
+            queue_capacity,
+            gguf_chat_templates,
+        }
+    }
+}
+
+/// A resident model with its own engine and queue
+pub struct ServedModel {
+    /// Routing name
+    pub name: String,
+
+    /// Alternative names routed to this model
+    pub aliases: Vec<String>,
+
+    pub engine: Arc<InferenceEngine>,
+    pub queue: QueueHandle,
+
+    /// Model file size
+    pub size_bytes: u64,
+
+    /// Estimated memory once loaded: weights, KV cache and compute buffers
+    /// (used for the memory budget)
+    pub memory_bytes: u64,
+
+    /// Unix millis of the last routed request
+    last_used: AtomicU64,
+}
+
+impl ServedModel {
+    fn touch(&self) {
+        self.last_used.store(now_millis(), Ordering::Relaxed);
+    }
+
+    /// Unix millis of the last routed request
+    pub fn last_used(&self) -> u64 {
+        self.last_used.load(Ordering::Relaxed)
+    }
+}
+
+/// Resident model summary (for `/v1/models`)
+#[derive(Debug, Clone, Serialize)]
+pub struct ServedModelInfo {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub model_path: String,
+    pub size_bytes: u64,
+    pub memory_bytes: u64,
+    pub is_default: bool,
+    pub queued: usize,
+    pub last_used: u64,
+}
+
+/// Routes requests to resident models by name or alias
+pub struct ModelRouter {
+    backend: Arc<LlamaBackend>,
+    config: RouterConfig,
+    lora_registry: Option<Arc<LoraRegistry>>,
+    manifest: Option<Arc<ModelManifest>>,
+
+    /// The startup model; always resident and used when `model` is absent or unknown.
+    default: Arc<ServedModel>,
+
+    /// Additional resident models by name
+    models: RwLock<HashMap<String, Arc<ServedModel>>>,
+
+    /// Serialize loads/evictions
+    load_lock: tokio::sync::Mutex<()>,
+}
+
+impl ModelRouter {
+    /// Create a router around the default engine and its queue
+    pub fn new(engine: Arc<InferenceEngine>, queue: QueueHandle, config: RouterConfig) -> Self {
+        let model_config = engine.current_model_config();
+        let model_path = model_config.model_path.clone();
+        let size_bytes = std::fs::metadata(&model_path).map(|m| m.len()).unwrap_or(0);
+
+        let default = Arc::new(ServedModel {
+            name: model_name_from_path(&model_path),
+            aliases: vec![],
+            engine: engine.clone(),
+            queue,
+            size_bytes,
+            memory_bytes: estimate_loaded_bytes(Path::new(&model_path), &model_config),
+            last_used: AtomicU64::new(now_millis()),
+        });
+
+        Self {
+            backend: engine.llama_backend(),
+            lora_registry: engine.lora_registry(),
+            manifest: None,
+            config,
+            default,
+            models: RwLock::new(HashMap::new()),
+            load_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Route manifest names and aliases, and apply per-model settings
+    pub fn with_manifest(mut self, manifest: ModelManifest) -> Self {
+        self.manifest = Some(Arc::new(manifest));
+        self
+    }
+
+    /// Models manifest, if one was found
+    pub fn manifest(&self) -> Option<Arc<ModelManifest>> {
+        self.manifest.clone()
+    }
+
+    /// Manifest entry for a served model (by routing name, then by model file)
+    pub fn manifest_entry(&self, served: &ServedModel) -> Option<ModelEntry> {
+        let manifest = self.manifest.as_ref()?;
+        manifest
+            .find(&served.name)
+            .filter(|_| !Arc::ptr_eq(&served.engine, &self.default.engine))
+            .or_else(|| manifest.find_by_path(&served.engine.current_model_config().model_path))
+            .cloned()
+    }
+
+    /// Chat template for a served model: the manifest's `chat_template`, else the
+    /// Jinja template embedded in the GGUF file, else detected from the file name
+    pub fn template_for(&self, served: &ServedModel) -> PromptTemplate {
+        let manifest_template = self
+            .manifest_entry(served)
+            .and_then(|entry| entry.chat_template)
+            .and_then(|name| {
+                let template = TemplateType::from_name(&name);
+                if template.is_none() {
+                    warn!("Unknown chat_template '{}' in models manifest", name);
+                }
+                template
+            });
+        if let Some(template_type) = manifest_template {
+            return PromptTemplate::Builtin(template_type);
+        }
+
+        let fallback =
+            TemplateType::from_model_name(&served.engine.current_model_config().model_path);
+        if self.config.gguf_chat_templates {
+            if let Some(template) = served.engine.embedded_chat_template() {
+                return PromptTemplate::Jinja(template, fallback);
+            }
+        }
+        PromptTemplate::Builtin(fallback)
+    }
+
+    /// The default model (MODEL_PATH)
+    pub fn default_model(&self) -> Arc<ServedModel> {
+        self.default.clone()
+    }
+
+    /// Current name of the default model (follows `/v1/models/load` switches)
+    pub fn default_name(&self) -> String {
+        model_name_from_path(&self.default.engine.current_model_config().model_path)
+    }
+
+    /// Route a request's `model` field to a resident model.
+    ///
+    /// Missing and unknown names use the default model (OpenAI clients always
+    /// send some `model`). Manifest models that aren't resident are loaded when
+    /// on-demand loading is enabled, and rejected otherwise, so clients can't
+    /// load or evict arbitrary models.
+    pub async fn resolve(&self, requested: Option<&str>) -> Result<Arc<ServedModel>> {
+        let served = self.route(requested).await?;
+        if !served.engine.accepts_requests() {
+            return Err(served.engine.state().not_ready_error());
+        }
+        // Reload an idle-unloaded model now, so its chat template is available
+        served.engine.reload_if_idle().await?;
+        Ok(served)
+    }
+
+    async fn route(&self, requested: Option<&str>) -> Result<Arc<ServedModel>> {
+        let Some(name) = requested.map(str::trim).filter(|n| !n.is_empty()) else {
+            self.default.touch();
+            return Ok(self.default.clone());
+        };
+
+        if let Some(served) = self.lookup(name) {
+            served.touch();
+            return Ok(served);
+        }
+
+        if let Some(entry) = self.manifest.as_ref().and_then(|m| m.find(name)).cloned() {
+            let default_path = self.default.engine.current_model_config().model_path;
+            let is_default = self
+                .manifest
+                .as_ref()
+                .and_then(|m| m.find_by_path(&default_path))
+                .is_some_and(|e| e.name == entry.name);
+            if is_default {
+                self.default.touch();
+                return Ok(self.default.clone());
+            }
+
+            if !self.config.load_on_demand {
+                return Err(ExsaError::InvalidParameters(format!(
+                    "Model '{}' is not loaded (on-demand loading is disabled)",
+                    name
+                )));
+            }
+            let served = self
+                .load(
+                    entry.name.clone(),
+                    entry.path.to_string_lossy().to_string(),
+                    entry.aliases.clone(),
+                )
+                .await?;
+            served.touch();
+            return Ok(served);
+        }
+
+        debug!(
+            "Model '{}' is not resident or in the manifest, routing to default model",
+            name
+        );
+        self.default.touch();
+        Ok(self.default.clone())
+    }
+
+    /// All resident models, default first
+    pub fn served_models(&self) -> Vec<Arc<ServedModel>> {
+        let mut out = vec![self.default.clone()];
+        if let Ok(models) = self.models.read() {
+            let mut others: Vec<Arc<ServedModel>> = models.values().cloned().collect();
+            others.sort_by(|a, b| a.name.cmp(&b.name));
+            out.extend(others);
+        }
+        out
+    }
+
+    /// Find a resident model by name or alias
+    pub fn lookup(&self, name: &str) -> Option<Arc<ServedModel>> {
+        if name == self.default_name() || self.default.aliases.iter().any(|a| a == name) {
+            return Some(self.default.clone());
+        }
+
+        let models = self.models.read().ok()?;
+        if let Some(served) = models.get(name) {
+            return Some(served.clone());
+        }
+        models
+            .values()
+            .find(|m| m.aliases.iter().any(|a| a == name))
+            .cloned()
+    }
+
+    /// Load a model alongside the default one (no-op if already resident).
+    ///
+    /// Evicts least-recently-used models first if the memory budget or the
+    /// resident model limit would be exceeded.
+    pub async fn load(
+        &self,
+        name: String,
+        model_path: String,
+        aliases: Vec<String>,
+    ) -> Result<Arc<ServedModel>> {
+        let _guard = self.load_lock.lock().await;
+
+        if let Some(served) = self.lookup(&name) {
+            return Ok(served);
+        }
+
+        let size_bytes = std::fs::metadata(&model_path)
+            .map(|m| m.len())
+            .map_err(|_| {
+                ExsaError::InvalidParameters(format!("Model file not found: {}", model_path))
+            })?;
+
+        // Inherit runtime settings (GPU layers, context, batch, KV quantization) from the default
+        // model, then apply the manifest entry's overrides.
+        let mut config = self.default.engine.current_model_config();
+        config.model_path = model_path.clone();
+        let mut aliases = aliases;
+        if let Some(entry) = self.manifest.as_ref().and_then(|m| m.find(&name)) {
+            entry.apply_to_config(&mut config);
+            config.model_path = model_path.clone();
+            for alias in &entry.aliases {
+                if !aliases.contains(alias) {
+                    aliases.push(alias.clone());
+                }
+            }
+        }
+        crate::model::ModelLoader::new(config.clone()).validate()?;
+
+        let memory_bytes = estimate_loaded_bytes(Path::new(&model_path), &config);
+        self.evict_for(memory_bytes)?;
+
+        info!("📦 Loading resident model '{}' from {}", name, model_path);
+        let backend = self.backend.clone();
+        let engine_name = name.clone();
+        let lora_registry = self.lora_registry.clone();
+        let engine = tokio::task::spawn_blocking(move || {
+            InferenceEngine::with_backend(backend, engine_name, model_path, config, lora_registry)
+        })
+        .await
+        .map_err(|e| ExsaError::InternalError(format!("Model load task failed: {e}")))??;
+
+        let engine = Arc::new(engine);
+        let queue = RequestQueue::new(self.config.queue_capacity, engine.clone()).handle();
+
+        let served = Arc::new(ServedModel {
+            name: name.clone(),
+            aliases,
+            engine,
+            queue,
+            size_bytes,
+            memory_bytes,
+            last_used: AtomicU64::new(now_millis()),
+        });
+
+        self.models
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .insert(name.clone(), served.clone());
+
+        info!("✅ Model '{}' resident", name);
+        Ok(served)
+    }
+
+    /// Remove a resident model. Queued and in-flight requests still complete;
+    /// the model memory is released once its worker drains.
+    pub fn unload(&self, name: &str) -> Result<()> {
+        let mut models = self
+            .models
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+
+        let key = models
+            .iter()
+            .find(|(k, m)| k.as_str() == name || m.aliases.iter().any(|a| a == name))
+            .map(|(k, _)| k.clone());
+
+        match key {
+            Some(key) => {
+                models.remove(&key);
+                info!("📤 Model '{}' removed from router", key);
+                Ok(())
+            }
+            None => Err(ExsaError::InvalidParameters(format!(
+                "Model '{}' is not resident (the default model cannot be unloaded here)",
+                name
+            ))),
+        }
+    }
+
+    /// Resident models, default first
+    pub fn list(&self) -> Vec<ServedModelInfo> {
+        let info = |m: &ServedModel, name: String, is_default: bool| ServedModelInfo {
+            name,
+            aliases: m.aliases.clone(),
+            model_path: m.engine.current_model_config().model_path,
+            size_bytes: m.size_bytes,
+            memory_bytes: m.memory_bytes,
+            is_default,
+            queued: m.queue.pending_count(),
+            last_used: m.last_used(),
+        };
+
+        let mut out = vec![info(&self.default, self.default_name(), true)];
+        if let Ok(models) = self.models.read() {
+            let mut others: Vec<ServedModelInfo> = models
+                .values()
+                .map(|m| info(m, m.name.clone(), false))
+                .collect();
+            others.sort_by(|a, b| a.name.cmp(&b.name));
+            out.extend(others);
+        }
+        out
+    }
+
+    /// Sum of active requests across all resident models
+    pub fn active_requests(&self) -> usize {
+        let others: usize = self
+            .models
+            .read()
+            .map(|m| m.values().map(|s| s.engine.active_requests()).sum())
+            .unwrap_or(0);
+        self.default.engine.active_requests() + others
+    }
+
+    /// Requests shed by the queues of all resident models
+    pub fn shed_stats(&self) -> ShedStats {
+        let mut total = ShedStats::default();
+        for served in self.served_models() {
+            total.add(&served.queue.shed_stats());
+        }
+        total
+    }
+
+    /// Evict LRU models until a model of `incoming_bytes` fits
+    fn evict_for(&self, incoming_bytes: u64) -> Result<()> {
+        let resident: Vec<(String, u64, u64)> = self
+            .models
+            .read()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .values()
+            .map(|m| (m.name.clone(), m.memory_bytes, m.last_used()))
+            .collect();
+
+        let victims = plan_evictions(
+            &resident,
+            self.default_memory_bytes(),
+            incoming_bytes,
+            self.config.memory_budget_bytes,
+            self.config.max_resident_models,
+        )
+        .ok_or_else(|| {
+            ExsaError::ResourceExhausted(format!(
+                "Model ({} MB) does not fit in the model memory budget",
+                incoming_bytes / (1024 * 1024)
+            ))
+        })?;
+
+        for name in victims {
+            warn!("♻️ Evicting least recently used model '{}'", name);
+            self.unload(&name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Estimated memory of the default model, which `/v1/models/load` may have switched
+    fn default_memory_bytes(&self) -> u64 {
+        let config = self.default.engine.current_model_config();
+        estimate_loaded_bytes(Path::new(&config.model_path), &config)
+    }
+}
+
+/// Choose which resident models to evict (least recently used first) so that
+/// `incoming` fits within the memory budget and the resident model limit.
+///
+/// `resident` is `(name, memory_bytes, last_used)` for evictable models; the default
+/// model is never evicted. Returns `None` if the model can't fit even after
+/// evicting everything.
+pub fn plan_evictions(
+    resident: &[(String, u64, u64)],
+    default_size: u64,
+    incoming: u64,
+    budget: Option<u64>,
+    max_models: usize,
+) -> Option<Vec<String>> {
+    let mut candidates: Vec<&(String, u64, u64)> = resident.iter().collect();
+    candidates.sort_by_key(|(_, _, last_used)| *last_used);
+
+    let mut total = resident.iter().fold(default_size, |total, (_, size, _)| {
+        total.saturating_add(*size)
+    });
+    let mut count = resident.len() + 1;
+    let mut victims = Vec::new();
+
+    let fits = |total: u64, count: usize| {
+        count < max_models
+            && budget
+                .map(|b| total.saturating_add(incoming) <= b)
+                .unwrap_or(true)
+    };
+
+    for (name, size, _) in candidates {
+        if fits(total, count) {
+            break;
+        }
+        victims.push(name.clone());
+        total = total.saturating_sub(*size);
+        count -= 1;
+    }
+
+    fits(total, count).then_some(victims)
+}
+
+/// A model to preload at startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServedModelSpec {
+    pub name: String,
+    pub model_path: String,
+    pub aliases: Vec<String>,
+}
+
+/// Parse `name=path|alias1|alias2` entries separated by commas (EXSA_SERVED_MODELS)
+pub fn parse_served_models(s: &str) -> Vec<ServedModelSpec> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let (name, rest) = entry.split_once('=')?;
+            let mut parts = rest.split('|').map(str::trim);
+            let model_path = parts.next().filter(|p| !p.is_empty())?.to_string();
+            Some(ServedModelSpec {
+                name: name.trim().to_string(),
+                model_path,
+                aliases: parts.filter(|a| !a.is_empty()).map(String::from).collect(),
+            })
+        })
+        .collect()
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn model_name_from_path(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("model")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn model(name: &str, size_mb: u64, last_used: u64) -> (String, u64, u64) {
+        (name.to_string(), size_mb * MB, last_used)
+    }
+
+    #[test]
+    fn test_plan_evictions_lru_order() {
+        let resident = vec![
+            model("a", 4000, 30),
+            model("b", 4000, 10),
+            model("c", 4000, 20),
+        ];
+        let victims = plan_evictions(&resident, 4000 * MB, 4000 * MB, Some(13000 * MB), 10);
+        assert_eq!(victims, Some(vec!["b".to_string(), "c".to_string()]));
+    }
+
+    #[test]
+    fn test_plan_evictions_max_models() {
+        let resident = vec![model("a", 1, 2), model("b", 1, 1)];
+        let victims = plan_evictions(&resident, MB, MB, None, 3);
+        assert_eq!(victims, Some(vec!["b".to_string()]));
+
+        assert_eq!(plan_evictions(&resident, MB, MB, None, 10), Some(vec![]));
+    }
+
+    #[test]
+    fn test_parse_served_models() {
+        let specs = parse_served_models("qwen=/m/qwen.gguf|gpt-4o|chat, bad, coder=/m/coder.gguf");
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].name, "qwen");
+        assert_eq!(specs[0].aliases, vec!["gpt-4o", "chat"]);
+        assert_eq!(specs[1].model_path, "/m/coder.gguf");
+        assert!(specs[1].aliases.is_empty());
+    }
+
+    #[test]
+    fn test_plan_evictions_does_not_fit() {
+        // Default model alone already leaves no room
+        let victims = plan_evictions(
+            &[model("a", 100, 1)],
+            8000 * MB,
+            4000 * MB,
+            Some(10000 * MB),
+            10,
+        );
+        assert_eq!(victims, None);
+    }
+}
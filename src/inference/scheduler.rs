@@ -0,0 +1,653 @@
+//! Priority scheduling for the request queue
+//!
+//! Every request belongs to a `Priority` class. Interactive traffic is served
+//! ahead of batch and background jobs, but a class is only a head start, not an
+//! absolute rank: a request is due at its arrival time plus `aging × rank`, and
+//! the earliest due request runs next. Background work that has waited
+//! `2 × aging` longer than newer interactive requests goes first, so a steady
+//! stream of chat traffic can't starve it.
+//!
+//! Within a class, users (request `user` or API key) share the engine by weighted
+//! fair queueing, so one client flooding the queue only delays its own requests.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Default head start per priority class (EXSA_PRIORITY_AGING_SECS)
+const DEFAULT_AGING_SECS: u64 = 10;
+
+/// Scheduling class of a request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// A user is waiting on the response (chat, completions)
+    #[default]
+    Interactive,
+
+    /// Throughput work submitted in bulk
+    Batch,
+
+    /// Best-effort jobs that may wait (indexing, evaluation, warm-ups)
+    Background,
+}
+
+impl Priority {
+    /// Distance from the top class (0 = interactive)
+    pub fn rank(self) -> u32 {
+        match self {
+            Priority::Interactive => 0,
+            Priority::Batch => 1,
+            Priority::Background => 2,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Batch => "batch",
+            Priority::Background => "background",
+        }
+    }
+
+    /// Parse from string, returning `None` on unknown input
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "interactive" | "high" | "realtime" => Some(Priority::Interactive),
+            "batch" | "normal" | "bulk" => Some(Priority::Batch),
+            "background" | "low" | "idle" => Some(Priority::Background),
+            _ => None,
+        }
+    }
+
+    /// The lower of two classes
+    pub fn lower(self, other: Priority) -> Priority {
+        if other.rank() > self.rank() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Fair-share identity of requests that name no user and carry no API key
+pub const ANONYMOUS_USER: &str = "anonymous";
+
+/// Scheduler configuration
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Head start of each class over the next one down
+    pub aging: Duration,
+
+    /// Relative share of the engine per user id (others get 1.0)
+    pub user_weights: HashMap<String, f64>,
+
+    /// Queued plus running requests allowed per user (None = unlimited)
+    pub max_requests_per_user: Option<usize>,
+
+    /// Reject requests whose predicted wait in the queue is longer (None = no limit)
+    pub max_predicted_wait: Option<Duration>,
+
+    /// Longest a request may wait in the queue before it expires (None = no limit)
+    pub max_queue_wait: Option<Duration>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            aging: Duration::from_secs(DEFAULT_AGING_SECS),
+            user_weights: HashMap::new(),
+            max_requests_per_user: None,
+            max_predicted_wait: None,
+            max_queue_wait: None,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Load scheduler configuration from environment variables.
+    ///
+    /// - EXSA_PRIORITY_AGING_SECS=... (default: 10; 0 = plain FIFO)
+    /// - EXSA_USER_WEIGHTS=user=weight,... (default: every user 1.0)
+    /// - EXSA_MAX_REQUESTS_PER_USER=... (default: unlimited)
+    /// - EXSA_MAX_PREDICTED_WAIT_SECS=... (default: unlimited)
+    /// - EXSA_MAX_QUEUE_WAIT_SECS=... (default: unlimited)
+    pub fn from_env() -> Self {
+        let aging = std::env::var("EXSA_PRIORITY_AGING_SECS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|s| s.is_finite() && *s >= 0.0)
+            .map(Duration::from_secs_f64)
+            .unwrap_or(Duration::from_secs(DEFAULT_AGING_SECS));
+
+        let user_weights = std::env::var("EXSA_USER_WEIGHTS")
+            .map(|spec| parse_user_weights(&spec))
+            .unwrap_or_default();
+
+        let max_requests_per_user = std::env::var("EXSA_MAX_REQUESTS_PER_USER")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|n| *n > 0);
+
+        let max_predicted_wait = std::env::var("EXSA_MAX_PREDICTED_WAIT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|s| s.is_finite() && *s > 0.0)
+            .map(Duration::from_secs_f64);
+
+        let max_queue_wait = std::env::var("EXSA_MAX_QUEUE_WAIT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|s| s.is_finite() && *s > 0.0)
+            .map(Duration::from_secs_f64);
+
+        Self {
+            aging,
+            user_weights,
+            max_requests_per_user,
+            max_predicted_wait,
+            max_queue_wait,
+        }
+    }
+
+    /// Share of a user relative to the default of 1.0
+    pub fn weight(&self, user: &str) -> f64 {
+        self.user_weights.get(user).copied().unwrap_or(1.0)
+    }
+}
+
+/// Parse `user=weight` pairs, skipping malformed or non-positive weights
+pub fn parse_user_weights(spec: &str) -> HashMap<String, f64> {
+    spec.split(',')
+        .filter_map(|entry| {
+            let (user, weight) = entry.split_once('=')?;
+            let weight = weight.trim().parse::<f64>().ok();
+            match weight {
+                Some(w) if w.is_finite() && w > 0.0 && !user.trim().is_empty() => {
+                    Some((user.trim().to_string(), w))
+                }
+                _ => {
+                    tracing::warn!("Ignoring invalid EXSA_USER_WEIGHTS entry: {}", entry);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Queue depth of one user
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserQueueStats {
+    pub user: String,
+    pub queued: usize,
+    pub running: usize,
+    pub weight: f64,
+}
+
+struct Entry<T> {
+    item: T,
+    priority: Priority,
+    user: String,
+    due: Instant,
+    seq: u64,
+}
+
+/// Per-user fair-share bookkeeping
+#[derive(Debug, Default)]
+struct Flow {
+    /// Virtual time at which the user's last dispatched request "finished"
+    finish: f64,
+    queued: usize,
+    running: usize,
+}
+
+/// Pending requests ordered by priority class with aging, and by weighted
+/// fair queueing across users within a class.
+///
+/// Each dispatch advances the user's virtual finish time by `1 / weight`; the
+/// user with the earliest finish time goes next, so backlogged users are served
+/// in proportion to their weights no matter how many requests each one queued.
+/// A user that was idle restarts at the current virtual time and can't bank
+/// credit for later.
+pub struct Scheduler<T> {
+    config: SchedulerConfig,
+    entries: Vec<Entry<T>>,
+    next_seq: u64,
+    flows: HashMap<String, Flow>,
+    virtual_time: f64,
+
+    /// Entry indices in dispatch order, until the queue changes
+    order: Option<Vec<usize>>,
+}
+
+impl<T> Scheduler<T> {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config,
+            entries: Vec::new(),
+            next_seq: 0,
+            flows: HashMap::new(),
+            virtual_time: 0.0,
+            order: None,
+        }
+    }
+
+    /// Add an item arriving now; gives it back when `user` is at the per-user limit
+    pub fn push(&mut self, item: T, priority: Priority, user: &str) -> Result<(), T> {
+        self.push_at(item, priority, user, Instant::now())
+    }
+
+    /// Add an item that arrived at `enqueued_at`
+    pub fn push_at(
+        &mut self,
+        item: T,
+        priority: Priority,
+        user: &str,
+        enqueued_at: Instant,
+    ) -> Result<(), T> {
+        let flow = self.flows.entry(user.to_string()).or_default();
+        if let Some(max) = self.config.max_requests_per_user {
+            if flow.queued + flow.running >= max {
+                return Err(item);
+            }
+        }
+        flow.queued += 1;
+
+        let due = enqueued_at + self.config.aging * priority.rank();
+        self.entries.push(Entry {
+            item,
+            priority,
+            user: user.to_string(),
+            due,
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+        self.order = None;
+        Ok(())
+    }
+
+    /// Remove the item to run next.
+    ///
+    /// The earliest due item (priority class plus aging) decides which class is
+    /// served; within that class the user with the earliest virtual finish time
+    /// goes first, then the first submitted. The caller reports the end of the
+    /// item with [`Scheduler::finish`].
+    pub fn pop(&mut self) -> Option<T> {
+        let (index, finish) = self.pick(
+            0..self.entries.len(),
+            |user| self.flows.get(user).map_or(0.0, |f| f.finish),
+            self.virtual_time,
+        )?;
+
+        let entry = self.entries.remove(index);
+        self.order = None;
+        self.virtual_time = finish - 1.0 / self.config.weight(&entry.user);
+        if let Some(flow) = self.flows.get_mut(&entry.user) {
+            flow.queued -= 1;
+            flow.running += 1;
+            flow.finish = finish;
+        }
+        self.prune();
+        Some(entry.item)
+    }
+
+    /// Pending items in the order `pop` would return them if nothing else arrived.
+    ///
+    /// The order only changes when items are added or removed, so it is
+    /// computed once and reused until then.
+    pub fn ordered(&mut self) -> impl Iterator<Item = &T> {
+        if self.order.is_none() {
+            self.order = Some(self.dispatch_order());
+        }
+        let entries = &self.entries;
+        self.order
+            .iter()
+            .flatten()
+            .map(move |&index| &entries[index].item)
+    }
+
+    /// Entry indices in the order `pop` would take them
+    fn dispatch_order(&self) -> Vec<usize> {
+        let mut remaining: Vec<usize> = (0..self.entries.len()).collect();
+        let mut finishes: HashMap<&str, f64> = HashMap::new();
+        let mut virtual_time = self.virtual_time;
+        let mut order = Vec::with_capacity(remaining.len());
+
+        loop {
+            let next = self.pick(
+                remaining.iter().copied(),
+                |user| {
+                    finishes
+                        .get(user)
+                        .copied()
+                        .or_else(|| self.flows.get(user).map(|f| f.finish))
+                        .unwrap_or(0.0)
+                },
+                virtual_time,
+            );
+            let Some((index, finish)) = next else {
+                break;
+            };
+            let entry = &self.entries[index];
+            virtual_time = finish - 1.0 / self.config.weight(&entry.user);
+            finishes.insert(&entry.user, finish);
+            remaining.retain(|&i| i != index);
+            order.push(index);
+        }
+        order
+    }
+
+    /// Take a pending item out of the queue (e.g. cancelled before it ran)
+    pub fn remove(&mut self, matches: impl Fn(&T) -> bool) -> Option<T> {
+        let index = self.entries.iter().position(|e| matches(&e.item))?;
+        let entry = self.entries.remove(index);
+        self.order = None;
+        if let Some(flow) = self.flows.get_mut(&entry.user) {
+            flow.queued -= 1;
+        }
+        self.prune();
+        Some(entry.item)
+    }
+
+    /// Record that a dispatched item of `user` has finished
+    pub fn finish(&mut self, user: &str) {
+        if let Some(flow) = self.flows.get_mut(user) {
+            flow.running = flow.running.saturating_sub(1);
+        }
+        self.prune();
+    }
+
+    /// Index of the entry to run next among `candidates`, with its virtual finish time
+    fn pick(
+        &self,
+        candidates: impl Iterator<Item = usize> + Clone,
+        last_finish: impl Fn(&str) -> f64,
+        virtual_time: f64,
+    ) -> Option<(usize, f64)> {
+        let class = candidates
+            .clone()
+            .map(|i| &self.entries[i])
+            .min_by_key(|e| (e.due, e.seq))?
+            .priority;
+
+        let mut best: Option<(f64, u64, usize)> = None;
+        for index in candidates {
+            let entry = &self.entries[index];
+            if entry.priority != class {
+                continue;
+            }
+            let start = last_finish(&entry.user).max(virtual_time);
+            let finish = start + 1.0 / self.config.weight(&entry.user);
+            let better = match best {
+                None => true,
+                Some((best_finish, best_seq, _)) => {
+                    finish < best_finish || (finish == best_finish && entry.seq < best_seq)
+                }
+            };
+            if better {
+                best = Some((finish, entry.seq, index));
+            }
+        }
+        best.map(|(finish, _, index)| (index, finish))
+    }
+
+    /// Forget idle users that have no service history ahead of the virtual clock
+    fn prune(&mut self) {
+        let now = self.virtual_time;
+        self.flows
+            .retain(|_, f| f.queued > 0 || f.running > 0 || f.finish > now);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Pending items in arrival order
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().map(|e| &e.item)
+    }
+
+    /// Number of pending items in a class
+    pub fn pending(&self, priority: Priority) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| e.priority == priority)
+            .count()
+    }
+
+    /// Items a new request of `priority` would wait behind: those of its class
+    /// and above (aging and fair share ignored)
+    pub fn ahead_of(&self, priority: Priority) -> impl Iterator<Item = &T> {
+        self.entries
+            .iter()
+            .filter(move |e| e.priority.rank() <= priority.rank())
+            .map(|e| &e.item)
+    }
+
+    /// Queued and running items per user, busiest first
+    pub fn user_stats(&self) -> Vec<UserQueueStats> {
+        let mut stats: Vec<UserQueueStats> = self
+            .flows
+            .iter()
+            .filter(|(_, f)| f.queued > 0 || f.running > 0)
+            .map(|(user, f)| UserQueueStats {
+                user: user.clone(),
+                queued: f.queued,
+                running: f.running,
+                weight: self.config.weight(user),
+            })
+            .collect();
+        stats.sort_by(|a, b| {
+            (b.queued + b.running)
+                .cmp(&(a.queued + a.running))
+                .then_with(|| a.user.cmp(&b.user))
+        });
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduler(aging_secs: u64) -> Scheduler<&'static str> {
+        Scheduler::new(SchedulerConfig {
+            aging: Duration::from_secs(aging_secs),
+            ..Default::default()
+        })
+    }
+
+    fn drain<T>(s: &mut Scheduler<T>) -> Vec<T> {
+        std::iter::from_fn(|| s.pop()).collect()
+    }
+
+    #[test]
+    fn test_interactive_served_first_under_load() {
+        let mut s = scheduler(10);
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+
+        s.push_at("bg1", Priority::Background, "a", at(0)).unwrap();
+        s.push_at("batch1", Priority::Batch, "a", at(10)).unwrap();
+        s.push_at("bg2", Priority::Background, "a", at(20)).unwrap();
+        s.push_at("chat1", Priority::Interactive, "a", at(30))
+            .unwrap();
+        s.push_at("batch2", Priority::Batch, "a", at(40)).unwrap();
+        s.push_at("chat2", Priority::Interactive, "a", at(50))
+            .unwrap();
+
+        assert_eq!(s.pending(Priority::Background), 2);
+        assert_eq!(s.ahead_of(Priority::Interactive).count(), 2);
+        assert_eq!(s.ahead_of(Priority::Batch).count(), 4);
+        assert_eq!(
+            drain(&mut s),
+            ["chat1", "chat2", "batch1", "batch2", "bg1", "bg2"]
+        );
+    }
+
+    #[test]
+    fn test_aging_prevents_starvation() {
+        let mut s = scheduler(10);
+        let t0 = Instant::now();
+
+        // One background job, then a steady interactive stream (one per second)
+        s.push_at("bg", Priority::Background, "a", t0).unwrap();
+        for i in 0..30 {
+            s.push_at(
+                "chat",
+                Priority::Interactive,
+                "a",
+                t0 + Duration::from_secs(i),
+            )
+            .unwrap();
+        }
+
+        let order = drain(&mut s);
+        let bg = order.iter().position(|x| *x == "bg").unwrap();
+        // Runs before every interactive request that arrived 20 s or more after it
+        assert_eq!(bg, 20);
+    }
+
+    #[test]
+    fn test_fifo_within_class_and_without_aging() {
+        let mut s = scheduler(0);
+        let t0 = Instant::now();
+        s.push_at("a", Priority::Background, "a", t0).unwrap();
+        s.push_at("b", Priority::Interactive, "a", t0).unwrap();
+        s.push_at("c", Priority::Batch, "a", t0).unwrap();
+        assert_eq!(drain(&mut s), ["a", "b", "c"]);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    fn test_flooding_user_gets_fair_share() {
+        let mut s = scheduler(10);
+        for _ in 0..10 {
+            s.push("flood", Priority::Interactive, "flood").unwrap();
+        }
+        s.push("alice", Priority::Interactive, "alice").unwrap();
+        s.push("alice", Priority::Interactive, "alice").unwrap();
+        s.push("bob", Priority::Interactive, "bob").unwrap();
+
+        let order = drain(&mut s);
+        assert_eq!(
+            order[..6],
+            ["flood", "alice", "bob", "flood", "alice", "flood"]
+        );
+        assert!(order[6..].iter().all(|x| *x == "flood"));
+    }
+
+    #[test]
+    fn test_weighted_shares() {
+        let mut s = Scheduler::new(SchedulerConfig {
+            user_weights: parse_user_weights("heavy=2, light=1, bad=-1"),
+            ..Default::default()
+        });
+        assert_eq!(s.config.weight("bad"), 1.0);
+        for _ in 0..6 {
+            s.push("heavy", Priority::Batch, "heavy").unwrap();
+            s.push("light", Priority::Batch, "light").unwrap();
+        }
+
+        let first: Vec<_> = (0..6).filter_map(|_| s.pop()).collect();
+        assert_eq!(first.iter().filter(|x| **x == "heavy").count(), 4);
+        assert_eq!(first.iter().filter(|x| **x == "light").count(), 2);
+    }
+
+    #[test]
+    fn test_priority_before_fair_share() {
+        let mut s = scheduler(10);
+        s.push("a-batch", Priority::Batch, "a").unwrap();
+        s.push("b-chat", Priority::Interactive, "b").unwrap();
+        s.push("b-chat", Priority::Interactive, "b").unwrap();
+        assert_eq!(drain(&mut s), ["b-chat", "b-chat", "a-batch"]);
+    }
+
+    #[test]
+    fn test_per_user_limit_and_stats() {
+        let mut s = Scheduler::new(SchedulerConfig {
+            max_requests_per_user: Some(2),
+            ..Default::default()
+        });
+        s.push(1, Priority::Interactive, "alice").unwrap();
+        s.push(2, Priority::Interactive, "alice").unwrap();
+        assert_eq!(s.push(3, Priority::Interactive, "alice"), Err(3));
+        s.push(4, Priority::Interactive, "bob").unwrap();
+
+        // Running requests still count against the limit
+        assert_eq!(s.pop(), Some(1));
+        assert_eq!(s.push(5, Priority::Interactive, "alice"), Err(5));
+        assert_eq!(
+            s.user_stats(),
+            [
+                UserQueueStats {
+                    user: "alice".into(),
+                    queued: 1,
+                    running: 1,
+                    weight: 1.0
+                },
+                UserQueueStats {
+                    user: "bob".into(),
+                    queued: 1,
+                    running: 0,
+                    weight: 1.0
+                },
+            ]
+        );
+
+        s.finish("alice");
+        s.push(6, Priority::Interactive, "alice").unwrap();
+    }
+
+    #[test]
+    fn test_ordered_matches_pop() {
+        let mut s = scheduler(10);
+        for (item, priority, user) in [
+            ("f1", Priority::Interactive, "flood"),
+            ("f2", Priority::Interactive, "flood"),
+            ("bg", Priority::Background, "alice"),
+            ("f3", Priority::Interactive, "flood"),
+            ("b1", Priority::Interactive, "bob"),
+            ("a1", Priority::Batch, "alice"),
+        ] {
+            s.push(item, priority, user).unwrap();
+        }
+        s.pop();
+
+        let predicted: Vec<_> = s.ordered().copied().collect();
+        assert_eq!(predicted, ["b1", "f2", "f3", "a1", "bg"]);
+
+        // The cached order follows later pushes and removals
+        s.push("c1", Priority::Interactive, "carol").unwrap();
+        s.remove(|item| *item == "f3");
+        let predicted: Vec<_> = s.ordered().copied().collect();
+        assert_eq!(predicted, ["b1", "c1", "f2", "a1", "bg"]);
+        assert_eq!(drain(&mut s), predicted);
+    }
+
+    #[test]
+    fn test_remove_pending() {
+        let mut s = scheduler(10);
+        s.push("a", Priority::Interactive, "alice").unwrap();
+        s.push("b", Priority::Interactive, "bob").unwrap();
+        assert_eq!(s.remove(|x| *x == "a"), Some("a"));
+        assert_eq!(s.remove(|x| *x == "a"), None);
+        assert_eq!(s.user_stats().len(), 1);
+        assert_eq!(drain(&mut s), ["b"]);
+    }
+
+    #[test]
+    fn test_priority_parse() {
+        assert_eq!(Priority::parse(" Batch "), Some(Priority::Batch));
+        assert_eq!(Priority::parse("low"), Some(Priority::Background));
+        assert_eq!(Priority::parse("urgent"), None);
+        assert_eq!(
+            Priority::Interactive.lower(Priority::Batch),
+            Priority::Batch
+        );
+    }
+}
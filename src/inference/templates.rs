@@ -25,6 +25,30 @@ pub enum TemplateType {
     /// Format: <start_of_turn>role\ncontent<end_of_turn>\n
     Gemma,
 
+    /// Mistral / Mixtral instruct format (no system role)
+    /// Format: [INST] user [/INST]assistant</s>
+    Mistral,
+
+    /// Phi-3 format
+    /// Format: <|role|>\ncontent<|end|>\n
+    Phi3,
+
+    /// DeepSeek V3 / R1 format
+    /// Format: system<｜User｜>user<｜Assistant｜>assistant<｜end▁of▁sentence｜>
+    DeepSeek,
+
+    /// Cohere Command-R format
+    /// Format: <|START_OF_TURN_TOKEN|><|USER_TOKEN|>content<|END_OF_TURN_TOKEN|>
+    CommandR,
+
+    /// Vicuna v1.1 format
+    /// Format: system USER: user ASSISTANT: assistant</s>
+    Vicuna,
+
+    /// Zephyr format
+    /// Format: <|role|>\ncontent</s>\n
+    Zephyr,
+
     /// Raw/no template (for completion models)
     Raw,
 }
@@ -40,17 +64,53 @@ impl TemplateType {
     /// Auto-detect template type from model name/path
     pub fn from_model_name(model_name: &str) -> Self {
         let name_lower = model_name.to_lowercase();
-
-        // Check for Llama 3.x variants (llama3, llama-3, llama3.1, llama3.2, etc.)
-        if name_lower.contains("llama-3") || name_lower.contains("llama3") {
+        // Name parts such as "r1", "v2.5" or "qwen", for version-specific matches
+        let parts: Vec<&str> = name_lower
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '.')
+            .collect();
+
+        // DeepSeek R1 distills carry the base model's name (qwen, llama) but use DeepSeek's
+        // format; only V2.5, V3 and R1 use it, older DeepSeek chat models don't
+        let deepseek_chat = parts
+            .iter()
+            .any(|p| matches!(*p, "r1" | "v2.5" | "v3") || p.starts_with("v3."));
+        // Fine-tunes that switch their base model (often Mistral or Llama) to ChatML
+        let chatml_finetune = ["hermes", "dolphin", "openorca", "chatml"]
+            .iter()
+            .any(|marker| name_lower.contains(marker));
+
+        if name_lower.contains("deepseek") && deepseek_chat {
+            tracing::info!("Detected DeepSeek model from name: {}", model_name);
+            Self::DeepSeek
+        } else if chatml_finetune || name_lower.contains("lfm2") || name_lower.contains("qwen") {
+            tracing::info!("Detected ChatML model from name: {}", model_name);
+            Self::ChatML
+        } else if name_lower.contains("deepseek-coder") && !name_lower.contains("v2") {
+            // DeepSeek Coder 1.x instruct: "### Instruction:" / "### Response:"
+            tracing::info!("Detected DeepSeek Coder model from name: {}", model_name);
+            Self::Alpaca
+        } else if name_lower.contains("zephyr") {
+            tracing::info!("Detected Zephyr model from name: {}", model_name);
+            Self::Zephyr
+        } else if name_lower.contains("vicuna") {
+            tracing::info!("Detected Vicuna model from name: {}", model_name);
+            Self::Vicuna
+        } else if name_lower.contains("command-r") || name_lower.contains("c4ai") {
+            tracing::info!("Detected Command-R model from name: {}", model_name);
+            Self::CommandR
+        } else if name_lower.contains("phi-3") || name_lower.contains("phi3") {
+            tracing::info!("Detected Phi-3 model from name: {}", model_name);
+            Self::Phi3
+        } else if name_lower.contains("mistral") || name_lower.contains("mixtral") {
+            tracing::info!("Detected Mistral model from name: {}", model_name);
+            Self::Mistral
+        } else if name_lower.contains("llama-3") || name_lower.contains("llama3") {
+            // Llama 3.x variants (llama3, llama-3, llama3.1, llama3.2, etc.)
             tracing::info!("Detected Llama 3 model from name: {}", model_name);
             Self::Llama3
         } else if name_lower.contains("gemma") {
             tracing::info!("Detected Gemma model from name: {}", model_name);
             Self::Gemma
-        } else if name_lower.contains("lfm2") || name_lower.contains("qwen") {
-            tracing::info!("Detected ChatML model from name: {}", model_name);
-            Self::ChatML
         } else if name_lower.contains("alpaca") {
             tracing::info!("Detected Alpaca model from name: {}", model_name);
             Self::Alpaca
@@ -61,6 +121,24 @@ impl TemplateType {
         }
     }
 
+    /// Parse an explicit template name (e.g. from the models manifest)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "chatml" => Some(Self::ChatML),
+            "llama3" | "llama-3" => Some(Self::Llama3),
+            "alpaca" => Some(Self::Alpaca),
+            "gemma" => Some(Self::Gemma),
+            "mistral" | "mixtral" => Some(Self::Mistral),
+            "phi3" | "phi-3" => Some(Self::Phi3),
+            "deepseek" => Some(Self::DeepSeek),
+            "command-r" | "commandr" | "cohere" => Some(Self::CommandR),
+            "vicuna" => Some(Self::Vicuna),
+            "zephyr" => Some(Self::Zephyr),
+            "raw" | "none" => Some(Self::Raw),
+            _ => None,
+        }
+    }
+
     /// Get template-specific stop sequences
     /// These prevent models from continuing past the response boundary
     pub fn stop_sequences(&self) -> Vec<String> {
@@ -69,6 +147,12 @@ impl TemplateType {
             Self::Llama3 => vec!["<|eot_id|>".to_string()],
             Self::Alpaca => vec!["###".to_string(), "\n###".to_string()],
             Self::Gemma => vec!["<end_of_turn>".to_string()],
+            Self::Mistral => vec!["</s>".to_string(), "[INST]".to_string()],
+            Self::Phi3 => vec!["<|end|>".to_string(), "<|endoftext|>".to_string()],
+            Self::DeepSeek => vec!["<｜end▁of▁sentence｜>".to_string()],
+            Self::CommandR => vec!["<|END_OF_TURN_TOKEN|>".to_string()],
+            Self::Vicuna => vec!["</s>".to_string(), "USER:".to_string()],
+            Self::Zephyr => vec!["</s>".to_string(), "<|user|>".to_string()],
             Self::Raw => vec![],
         }
     }
@@ -81,6 +165,12 @@ pub fn apply_chat_template(messages: &[ChatMessage], template_type: TemplateType
         TemplateType::Llama3 => apply_llama3_template(messages),
         TemplateType::Alpaca => apply_alpaca_template(messages),
         TemplateType::Gemma => apply_gemma_template(messages),
+        TemplateType::Mistral => apply_mistral_template(messages),
+        TemplateType::Phi3 => apply_phi3_template(messages),
+        TemplateType::DeepSeek => apply_deepseek_template(messages),
+        TemplateType::CommandR => apply_command_r_template(messages),
+        TemplateType::Vicuna => apply_vicuna_template(messages),
+        TemplateType::Zephyr => apply_zephyr_template(messages),
         TemplateType::Raw => apply_raw_template(messages),
     }
 }
@@ -123,16 +213,34 @@ fn apply_llama3_template(messages: &[ChatMessage]) -> String {
 
 /// Apply Alpaca template
 /// Format: ### Instruction:\ncontent\n\n### Response:\n
+///
+/// System messages become the preamble; earlier turns are kept as
+/// instruction/response pairs.
 fn apply_alpaca_template(messages: &[ChatMessage]) -> String {
+    let (system, turns) = split_system(messages);
     let mut formatted = String::new();
 
-    // Alpaca typically uses last message as instruction
-    if let Some(last_message) = messages.last() {
-        formatted.push_str("### Instruction:\n");
-        formatted.push_str(&last_message.content);
-        formatted.push_str("\n\n### Response:\n");
+    if let Some(system) = system {
+        formatted.push_str(&system);
+        formatted.push_str("\n\n");
+    }
+
+    for message in turns {
+        match message.role.as_str() {
+            "assistant" => {
+                formatted.push_str("### Response:\n");
+                formatted.push_str(&message.content);
+            }
+            _ => {
+                formatted.push_str("### Instruction:\n");
+                formatted.push_str(&message.content);
+            }
+        }
+        formatted.push_str("\n\n");
     }
 
+    formatted.push_str("### Response:\n");
+
     formatted
 }
 
@@ -164,6 +272,155 @@ fn apply_raw_template(messages: &[ChatMessage]) -> String {
         .join("\n")
 }
 
+/// Apply Mistral template
+/// Format: [INST] user [/INST]assistant</s>
+///
+/// Mistral has no system role: system messages are prepended to the first user turn.
+/// BOS is left to the tokenizer.
+fn apply_mistral_template(messages: &[ChatMessage]) -> String {
+    let (system, turns) = split_system(messages);
+    let mut formatted = String::new();
+    let mut system = system;
+
+    for message in turns {
+        if message.role == "assistant" {
+            formatted.push_str(&message.content);
+            formatted.push_str("</s>");
+        } else {
+            let content = match system.take() {
+                Some(system) => format!("{}\n\n{}", system, message.content),
+                None => message.content.clone(),
+            };
+            formatted.push_str(&format!("[INST] {} [/INST]", content));
+        }
+    }
+
+    // System prompt without any user turn
+    if let Some(system) = system {
+        formatted.push_str(&format!("[INST] {} [/INST]", system));
+    }
+
+    formatted
+}
+
+/// Apply Phi-3 template
+/// Format: <|role|>\ncontent<|end|>\n
+fn apply_phi3_template(messages: &[ChatMessage]) -> String {
+    let mut formatted = String::new();
+
+    for message in messages {
+        formatted.push_str(&format!(
+            "<|{}|>\n{}<|end|>\n",
+            message.role, message.content
+        ));
+    }
+
+    formatted.push_str("<|assistant|>\n");
+
+    formatted
+}
+
+/// Apply DeepSeek V3 / R1 template
+/// Format: system<｜User｜>user<｜Assistant｜>assistant<｜end▁of▁sentence｜>
+///
+/// The system prompt is plain text ahead of the first turn. BOS is left to the tokenizer.
+fn apply_deepseek_template(messages: &[ChatMessage]) -> String {
+    let (system, turns) = split_system(messages);
+    let mut formatted = system.unwrap_or_default();
+
+    for message in turns {
+        if message.role == "assistant" {
+            formatted.push_str(&format!(
+                "<｜Assistant｜>{}<｜end▁of▁sentence｜>",
+                message.content
+            ));
+        } else {
+            formatted.push_str(&format!("<｜User｜>{}", message.content));
+        }
+    }
+
+    formatted.push_str("<｜Assistant｜>");
+
+    formatted
+}
+
+/// Apply Cohere Command-R template
+/// Format: <|START_OF_TURN_TOKEN|><|USER_TOKEN|>content<|END_OF_TURN_TOKEN|>
+fn apply_command_r_template(messages: &[ChatMessage]) -> String {
+    let mut formatted = String::new();
+
+    for message in messages {
+        let role_token = match message.role.as_str() {
+            "system" => "<|SYSTEM_TOKEN|>",
+            "assistant" => "<|CHATBOT_TOKEN|>",
+            _ => "<|USER_TOKEN|>",
+        };
+        formatted.push_str(&format!(
+            "<|START_OF_TURN_TOKEN|>{}{}<|END_OF_TURN_TOKEN|>",
+            role_token, message.content
+        ));
+    }
+
+    formatted.push_str("<|START_OF_TURN_TOKEN|><|CHATBOT_TOKEN|>");
+
+    formatted
+}
+
+/// Vicuna's default system prompt (FastChat `vicuna_v1.1`)
+const VICUNA_SYSTEM_PROMPT: &str =
+    "A chat between a curious user and an artificial intelligence assistant. \
+The assistant gives helpful, detailed, and polite answers to the user's questions.";
+
+/// Apply Vicuna v1.1 template
+/// Format: system USER: user ASSISTANT: assistant</s>
+fn apply_vicuna_template(messages: &[ChatMessage]) -> String {
+    let (system, turns) = split_system(messages);
+    let mut formatted = system.unwrap_or_else(|| VICUNA_SYSTEM_PROMPT.to_string());
+    formatted.push(' ');
+
+    for message in turns {
+        if message.role == "assistant" {
+            formatted.push_str(&format!("ASSISTANT: {}</s>", message.content));
+        } else {
+            formatted.push_str(&format!("USER: {} ", message.content));
+        }
+    }
+
+    formatted.push_str("ASSISTANT:");
+
+    formatted
+}
+
+/// Apply Zephyr template
+/// Format: <|role|>\ncontent</s>\n
+fn apply_zephyr_template(messages: &[ChatMessage]) -> String {
+    let mut formatted = String::new();
+
+    for message in messages {
+        formatted.push_str(&format!("<|{}|>\n{}</s>\n", message.role, message.content));
+    }
+
+    formatted.push_str("<|assistant|>\n");
+
+    formatted
+}
+
+/// Split leading system messages (joined) from the conversation turns, for
+/// formats without a system role
+fn split_system(messages: &[ChatMessage]) -> (Option<String>, &[ChatMessage]) {
+    let n_system = messages.iter().take_while(|m| m.role == "system").count();
+    if n_system == 0 {
+        return (None, messages);
+    }
+
+    let system = messages[..n_system]
+        .iter()
+        .map(|m| m.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    (Some(system), &messages[n_system..])
+}
+
 /// Helper to create a single-message prompt
 pub fn create_single_message(role: &str, content: &str) -> Vec<ChatMessage> {
     vec![ChatMessage {
@@ -190,6 +447,101 @@ mod tests {
         assert!(result.contains("<|im_start|>assistant"));
     }
 
+    fn conversation() -> Vec<ChatMessage> {
+        [
+            ("system", "Be brief."),
+            ("user", "Hi"),
+            ("assistant", "Hello!"),
+            ("user", "How are you?"),
+        ]
+        .iter()
+        .map(|(role, content)| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_mistral_template() {
+        assert_eq!(
+            apply_mistral_template(&conversation()),
+            "[INST] Be brief.\n\nHi [/INST]Hello!</s>[INST] How are you? [/INST]"
+        );
+    }
+
+    #[test]
+    fn test_phi3_template() {
+        assert_eq!(
+            apply_phi3_template(&conversation()),
+            "<|system|>\nBe brief.<|end|>\n<|user|>\nHi<|end|>\n<|assistant|>\nHello!<|end|>\n\
+<|user|>\nHow are you?<|end|>\n<|assistant|>\n"
+        );
+    }
+
+    #[test]
+    fn test_deepseek_template() {
+        assert_eq!(
+            apply_deepseek_template(&conversation()),
+            "Be brief.<｜User｜>Hi<｜Assistant｜>Hello!<｜end▁of▁sentence｜><｜User｜>How are you?<｜Assistant｜>"
+        );
+    }
+
+    #[test]
+    fn test_command_r_template() {
+        assert_eq!(
+            apply_command_r_template(&conversation()),
+            "<|START_OF_TURN_TOKEN|><|SYSTEM_TOKEN|>Be brief.<|END_OF_TURN_TOKEN|>\
+<|START_OF_TURN_TOKEN|><|USER_TOKEN|>Hi<|END_OF_TURN_TOKEN|>\
+<|START_OF_TURN_TOKEN|><|CHATBOT_TOKEN|>Hello!<|END_OF_TURN_TOKEN|>\
+<|START_OF_TURN_TOKEN|><|USER_TOKEN|>How are you?<|END_OF_TURN_TOKEN|>\
+<|START_OF_TURN_TOKEN|><|CHATBOT_TOKEN|>"
+        );
+    }
+
+    #[test]
+    fn test_vicuna_template() {
+        assert_eq!(
+            apply_vicuna_template(&conversation()),
+            "Be brief. USER: Hi ASSISTANT: Hello!</s>USER: How are you? ASSISTANT:"
+        );
+
+        let default_system = apply_vicuna_template(&conversation()[1..2]);
+        assert!(default_system.starts_with(VICUNA_SYSTEM_PROMPT));
+        assert!(default_system.ends_with(" USER: Hi ASSISTANT:"));
+    }
+
+    #[test]
+    fn test_zephyr_template() {
+        assert_eq!(
+            apply_zephyr_template(&conversation()),
+            "<|system|>\nBe brief.</s>\n<|user|>\nHi</s>\n<|assistant|>\nHello!</s>\n\
+<|user|>\nHow are you?</s>\n<|assistant|>\n"
+        );
+    }
+
+    #[test]
+    fn test_alpaca_template_keeps_history() {
+        assert_eq!(
+            apply_alpaca_template(&conversation()),
+            "Be brief.\n\n### Instruction:\nHi\n\n### Response:\nHello!\n\n\
+### Instruction:\nHow are you?\n\n### Response:\n"
+        );
+    }
+
+    #[test]
+    fn test_template_from_name() {
+        assert_eq!(
+            TemplateType::from_name("ChatML"),
+            Some(TemplateType::ChatML)
+        );
+        assert_eq!(
+            TemplateType::from_name("llama3"),
+            Some(TemplateType::Llama3)
+        );
+        assert_eq!(TemplateType::from_name("unknown"), None);
+    }
+
     #[test]
     fn test_template_type_detection() {
         assert_eq!(
@@ -204,5 +556,41 @@ mod tests {
             TemplateType::from_model_name("alpaca-7b"),
             TemplateType::Alpaca
         );
+        assert_eq!(
+            TemplateType::from_model_name("Mistral-7B-Instruct-v0.3.Q4_K_M.gguf"),
+            TemplateType::Mistral
+        );
+        assert_eq!(
+            TemplateType::from_model_name("DeepSeek-R1-Distill-Qwen-7B-Q4_K_M.gguf"),
+            TemplateType::DeepSeek
+        );
+        assert_eq!(
+            TemplateType::from_model_name("Phi-3.5-mini-instruct.gguf"),
+            TemplateType::Phi3
+        );
+    }
+
+    #[test]
+    fn test_template_detection_prefers_finetunes_and_versions() {
+        for name in [
+            "Hermes-2-Pro-Mistral-7B.Q4_K_M.gguf",
+            "openhermes-2.5-mistral-7b.Q5_K_M.gguf",
+            "dolphin-2.8-mistral-7b-v02.Q4_0.gguf",
+            "Hermes-3-Llama-3.1-8B.Q4_K_M.gguf",
+            "deepseek-llm-7b-chat.Q4_K_M.gguf",
+        ] {
+            assert_eq!(TemplateType::from_model_name(name), TemplateType::ChatML);
+        }
+        for name in [
+            "DeepSeek-V3-0324-Q2_K.gguf",
+            "DeepSeek-V2.5-1210-Q4_K_M.gguf",
+            "DeepSeek-R1-0528-Qwen3-8B-Q4_K_M.gguf",
+        ] {
+            assert_eq!(TemplateType::from_model_name(name), TemplateType::DeepSeek);
+        }
+        assert_eq!(
+            TemplateType::from_model_name("deepseek-coder-6.7b-instruct.Q4_K_M.gguf"),
+            TemplateType::Alpaca
+        );
     }
 }
@@ -3,9 +3,22 @@
 //! Production-grade inference engine for local LLM hosting
 
 use exsa_engine::{
-    api::{build_router, AppState},
-    inference::{queue::RequestQueue, InferenceEngine},
-    model::{ModelConfig, ModelLoader},
+    api::{
+        build_router,
+        lifecycle::{idle_unload_ttl, load_warm_and_switch, spawn_idle_unloader, WarmupSettings},
+        priority::PriorityPolicy,
+        resume::ResumableStreams,
+        AppState,
+    },
+    inference::{
+        queue::RequestQueue, router::parse_served_models, BatchJobs, EmbeddingsConfig,
+        EmbeddingsRegistry, InferenceEngine, LoadJobs, ModelRouter, RerankConfig, Reranker,
+        RouterConfig,
+    },
+    model::{
+        plan_model, LoraRegistry, ModelConfig, ModelLoader, ModelManifest, PlanOptions,
+        RopeScalingType,
+    },
     utils::{RateLimiter, ServerConfig},
 };
 use std::net::SocketAddr;
@@ -105,6 +118,34 @@ async fn main() {
         }
     };
 
+    // Models manifest (models.toml): named models with aliases and per-model defaults
+    let models_dir = exsa_engine::api::lifecycle::resolve_models_dir().ok();
+    let manifest = match ModelManifest::locate(models_dir.as_deref()) {
+        Some(path) => match ModelManifest::load(&path) {
+            Ok(manifest) => {
+                info!(
+                    "📒 Models manifest: {} model(s) from {:?}",
+                    manifest.entries().len(),
+                    path
+                );
+                manifest
+            }
+            Err(e) => {
+                error!("Failed to load models manifest: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => ModelManifest::default(),
+    };
+
+    // MODEL_PATH may name a manifest entry instead of a file
+    let model_path = match manifest.find(&model_path) {
+        Some(entry) if !std::path::Path::new(&model_path).is_file() => {
+            entry.path.to_string_lossy().to_string()
+        }
+        _ => model_path,
+    };
+
     let gpu_layers = std::env::var("GPU_LAYERS")
         .ok()
         .and_then(|v| v.parse().ok())
@@ -122,21 +163,104 @@ async fn main() {
 
     let max_queue_size = std::env::var("MAX_QUEUE_SIZE")
         .ok()
-        .and_then(|s| s.parse().ok())
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|n| *n > 0)
         .unwrap_or(server_config.max_queue_size);
 
     // Create model configuration
-    let model_config = ModelConfig::new(model_path.clone())
+    let mut model_config = ModelConfig::new(model_path.clone())
         .with_gpu_layers(gpu_layers)
         .with_context_size(n_ctx)
         .with_batch_size(n_batch); // BEAST MODE: Configure batch size
 
+    // Loading and RoPE settings
+    let env_flag = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+    };
+    if let Some(mmap) = env_flag("USE_MMAP") {
+        model_config = model_config.with_mmap(mmap);
+    }
+    if let Some(mlock) = env_flag("USE_MLOCK") {
+        model_config = model_config.with_mlock(mlock);
+    }
+    if let Ok(scaling) = std::env::var("ROPE_SCALING_TYPE") {
+        let factor = std::env::var("ROPE_SCALE_FACTOR")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(model_config.rope_scale_factor);
+        model_config =
+            model_config.with_rope_scaling(RopeScalingType::from_str_lossy(&scaling), factor);
+    }
+    if let Some(base) = std::env::var("ROPE_FREQ_BASE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        model_config = model_config.with_rope_freq_base(base);
+    }
+
+    // Per-model settings from the manifest take precedence over the global env defaults
+    if let Some(entry) = manifest.find_by_path(&model_path) {
+        info!("📒 Applying manifest settings for '{}'", entry.name);
+        entry.apply_to_config(&mut model_config);
+    }
+
+    // EXSA_AUTO_FIT: pick the largest context and KV cache type that fit in free memory,
+    // and the GPU layers when EXSA_PLAN_VRAM_MB gives a VRAM budget.
+    // CONTEXT_SIZE / the manifest's context_size become the upper bound instead of the value.
+    let auto_fit = std::env::var("EXSA_AUTO_FIT")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false);
+    if auto_fit {
+        let entry = manifest.find_by_path(&model_path);
+        let options = PlanOptions {
+            max_context: std::env::var("CONTEXT_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .or_else(|| entry.and_then(|e| e.context_size)),
+            kv_cache_type: entry.and_then(|e| e.kv_cache_type),
+            // Batch size follows the context unless set explicitly
+            n_batch: std::env::var("BATCH_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(u32::MAX),
+            ..PlanOptions::from_env()
+        };
+
+        match plan_model(std::path::Path::new(&model_path), &options) {
+            Ok(plan) if plan.fits => {
+                info!("🧮 Auto-fit: {}", plan.explanation);
+                plan.apply_to(&mut model_config);
+            }
+            Ok(plan) => {
+                error!("Model does not fit in memory: {}", plan.explanation);
+                error!("Use a smaller model or quantization, or set EXSA_PLAN_MEMORY_MB / EXSA_PLAN_VRAM_MB");
+                std::process::exit(1);
+            }
+            Err(e) => warn!("Auto-fit skipped: {}", e),
+        }
+    }
+
     info!("📊 Model Configuration (BEAST MODE ENABLED):");
     info!("  Path: {}", model_config.model_path);
     info!("  Context size: {} (optimized)", model_config.n_ctx);
     info!("  Batch size: {} (optimized)", model_config.n_batch);
+    info!("  KV cache: {:?}", model_config.kv_cache_type_k);
     info!("  GPU layers: {}", model_config.n_gpu_layers);
     info!("  CPU threads: {}", model_config.n_threads);
+    info!(
+        "  mmap: {}, mlock: {}",
+        model_config.use_mmap, model_config.use_mlock
+    );
+    if model_config.rope_scaling_type.is_active() || model_config.rope_freq_base > 0.0 {
+        info!(
+            "  RoPE: {:?} x{} (freq base {})",
+            model_config.rope_scaling_type,
+            model_config.rope_scale_factor,
+            model_config.rope_freq_base
+        );
+    }
 
     // BEAST MODE Phase 3: Check if continuous batching is enabled
     let enable_batching = std::env::var("ENABLE_CONTINUOUS_BATCHING").unwrap_or_default() == "true";
@@ -166,18 +290,20 @@ async fn main() {
         std::process::exit(1);
     }
 
-    // Extract model name from path (for ModelManager)
-    let model_name = std::path::Path::new(&model_path)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("default-model")
-        .to_string();
-
-    info!("Model name: {}", model_name);
-
-    // Initialize inference engine with ModelManager
-    let engine = match InferenceEngine::new(model_name, model_path.clone(), model_config) {
-        Ok(engine) => Arc::new(engine),
+    // Initialize inference engine with ModelManager; the model itself loads once
+    // the server is listening, so /v1/health can report "loading" meanwhile
+    // LoRA adapters: EXSA_LORA_DIR, or <models dir>/loras
+    let lora_dir = std::env::var("EXSA_LORA_DIR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(std::path::PathBuf::from)
+        .or_else(|| models_dir.as_ref().map(|d| d.join("loras")));
+
+    let engine = match InferenceEngine::new_unloaded(model_config.clone()) {
+        Ok(engine) => match lora_dir {
+            Some(dir) => Arc::new(engine.with_lora_registry(Arc::new(LoraRegistry::new(dir)))),
+            None => Arc::new(engine),
+        },
         Err(e) => {
             error!("Failed to initialize inference engine: {}", e);
             std::process::exit(1);
@@ -187,20 +313,99 @@ async fn main() {
     info!("✅ Inference engine initialized");
 
     // Create request queue with engine
-    let queue = RequestQueue::new(server_config.max_queue_size, Arc::clone(&engine));
+    let queue = RequestQueue::new(max_queue_size, Arc::clone(&engine));
     let queue_handle = queue.handle();
 
     info!("✅ Request queue created (max size: {})", max_queue_size);
 
+    // Model router: the startup model is the default; others are resident alongside it
+    let model_router = Arc::new(
+        ModelRouter::new(
+            engine.clone(),
+            queue_handle.clone(),
+            RouterConfig::from_env(max_queue_size),
+        )
+        .with_manifest(manifest),
+    );
+
+    // Preload additional models: EXSA_SERVED_MODELS=name=path|alias,...
+    if let Ok(spec) = std::env::var("EXSA_SERVED_MODELS") {
+        for served in parse_served_models(&spec) {
+            info!(
+                "📦 Loading resident model '{}': {}",
+                served.name, served.model_path
+            );
+            if let Err(e) = model_router
+                .load(served.name.clone(), served.model_path, served.aliases)
+                .await
+            {
+                error!("❌ Failed to load resident model '{}': {}", served.name, e);
+            }
+        }
+    }
+
     // Create application state with shutdown coordination
     let shutdown_flag = Arc::new(AtomicBool::new(false));
 
+    // Optional reranker model (used by /v1/rerank and RAG second-stage ranking)
+    let reranker = if let Some(rerank_cfg) = RerankConfig::from_env() {
+        info!("🔎 Loading reranker model: {}", rerank_cfg.model_path);
+        let engine_for_rerank = engine.clone();
+        match tokio::task::spawn_blocking(move || Reranker::load(&engine_for_rerank, &rerank_cfg))
+            .await
+        {
+            Ok(Ok(r)) => Some(Arc::new(r)),
+            Ok(Err(e)) => {
+                error!(
+                    "❌ Reranker load failed: {}. Continuing without reranking.",
+                    e
+                );
+                None
+            }
+            Err(e) => {
+                error!(
+                    "❌ Reranker load task failed: {}. Continuing without reranking.",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Embedding models (dedicated GGUFs, or the chat model on CPU when none configured)
+    let embeddings_cfg = EmbeddingsConfig::from_env();
+    if !embeddings_cfg.models.is_empty() {
+        info!(
+            "🧬 Loading {} embedding model(s)",
+            embeddings_cfg.models.len()
+        );
+    }
+    let engine_for_embed = engine.clone();
+    let embeddings = match tokio::task::spawn_blocking(move || {
+        EmbeddingsRegistry::load(&engine_for_embed, embeddings_cfg)
+    })
+    .await
+    {
+        Ok(registry) => Arc::new(registry),
+        Err(e) => {
+            error!("Embeddings registry init failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     // Optional RAG service
     let rag_cfg = exsa_engine::rag::RagConfig::from_env();
     let rag = if rag_cfg.enabled {
         info!("🧠 RAG enabled: initializing Postgres + Qdrant");
         let timeout = std::time::Duration::from_secs(rag_cfg.init_timeout_secs.max(1));
-        match tokio::time::timeout(timeout, exsa_engine::rag::RagService::new(rag_cfg)).await {
+        match tokio::time::timeout(
+            timeout,
+            exsa_engine::rag::RagService::new(rag_cfg, reranker.clone()),
+        )
+        .await
+        {
             Ok(Ok(r)) => {
                 info!("✅ RAG initialized");
                 Some(r)
@@ -227,14 +432,28 @@ async fn main() {
     let app_state = AppState {
         queue: queue_handle,
         engine: engine.clone(),
+        router: model_router.clone(),
         rag,
+        reranker,
+        embeddings,
         model_switch_lock: Arc::new(tokio::sync::Mutex::new(())),
+        load_jobs: Arc::new(LoadJobs::new()),
+        batch_jobs: Arc::new(BatchJobs::new()),
+        priorities: Arc::new(PriorityPolicy::from_env()),
+        streams: Arc::new(ResumableStreams::from_env()),
         embeddings_lock: Arc::new(tokio::sync::Mutex::new(())),
         shutdown_flag: shutdown_flag.clone(),
         start_time: std::time::Instant::now(),
     };
 
+    // Optional idle unload: free the model's memory when unused, reload on the next request
+    if let Some(ttl) = idle_unload_ttl() {
+        info!("💤 Idle unload after {}s without requests", ttl.as_secs());
+        spawn_idle_unloader(app_state.clone(), ttl);
+    }
+
     // Build router
+    let startup_state = app_state.clone();
     let mut app = build_router(app_state);
 
     // Add CORS if enabled
@@ -296,6 +515,7 @@ async fn main() {
     };
 
     info!("✅ Server listening on http://{}", socket_addr);
+    spawn_startup_load(startup_state, model_config);
     info!("");
     info!("API endpoints:");
     info!(
@@ -304,10 +524,7 @@ async fn main() {
     );
     info!("  GET  http://{}/v1/health - Health check", socket_addr);
     info!("  GET  http://{}/v1/status - Server status", socket_addr);
-    info!(
-        "  GET  http://{}/v1/models - Model information",
-        socket_addr
-    );
+    info!("  GET  http://{}/v1/models - Resident models", socket_addr);
     info!("");
     info!("🔒 Security status:");
     info!("  Bind address: {}", server_config.host);
@@ -330,7 +547,7 @@ async fn main() {
     info!("  Privacy: 100% local, no telemetry ✓");
 
     // Run server with graceful shutdown
-    let shutdown_signal_future = shutdown_signal(shutdown_flag.clone(), engine.clone());
+    let shutdown_signal_future = shutdown_signal(shutdown_flag.clone(), model_router.clone());
 
     if let Err(e) = axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal_future)
@@ -343,8 +560,39 @@ async fn main() {
     info!("Server shut down gracefully");
 }
 
+/// Load the startup model in the background.
+///
+/// /v1/health reports "loading" (then "warming" with EXSA_WARMUP) and requests get
+/// 503 until it is ready. Exits the process if the model fails to load.
+fn spawn_startup_load(state: AppState, model_config: ModelConfig) {
+    info!("📥 Loading model: {}", model_config.model_path);
+    tokio::spawn(async move {
+        // Model switches requested meanwhile wait for the startup model
+        let lock = state.model_switch_lock.clone();
+        let _guard = lock.lock().await;
+
+        let warmup = WarmupSettings::from_env();
+        let result = tokio::task::spawn_blocking(move || {
+            load_warm_and_switch(&state, model_config, warmup, None)
+        })
+        .await;
+
+        match result {
+            Ok(Ok(info)) => info!("✅ Model ready: {}", info.model_path),
+            Ok(Err(e)) => {
+                error!("Failed to load model: {}", e);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                error!("Model load task failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    });
+}
+
 /// Wait for shutdown signal and drain active requests
-async fn shutdown_signal(shutdown_flag: Arc<AtomicBool>, engine: Arc<InferenceEngine>) {
+async fn shutdown_signal(shutdown_flag: Arc<AtomicBool>, router: Arc<ModelRouter>) {
     let ctrl_c = async {
         if let Err(e) = signal::ctrl_c().await {
             error!("Failed to install Ctrl+C handler: {}", e);
@@ -382,13 +630,13 @@ async fn shutdown_signal(shutdown_flag: Arc<AtomicBool>, engine: Arc<InferenceEn
     let max_wait = std::time::Duration::from_secs(30);
     let start = std::time::Instant::now();
 
-    while engine.active_requests() > 0 && start.elapsed() < max_wait {
-        let active = engine.active_requests();
+    while router.active_requests() > 0 && start.elapsed() < max_wait {
+        let active = router.active_requests();
         info!("Waiting for {} active requests to complete...", active);
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 
-    let final_active = engine.active_requests();
+    let final_active = router.active_requests();
     if final_active > 0 {
         warn!(
             "Shutdown timeout reached with {} requests still active",
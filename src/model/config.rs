@@ -109,6 +109,23 @@ impl RopeScalingType {
     pub fn is_active(self) -> bool {
         !matches!(self, Self::None)
     }
+
+    /// Whether llama.cpp implements this scaling type (it has no dynamic NTK mode)
+    pub fn is_supported(self) -> bool {
+        !matches!(self, Self::NtkDynamic)
+    }
+
+    /// Convert to the llama.cpp scaling type (`None` leaves the model's own setting)
+    ///
+    /// Unsupported types are rejected by `ModelLoader::validate` before a load.
+    pub fn to_llama_type(self) -> Option<llama_cpp_2::context::params::RopeScalingType> {
+        use llama_cpp_2::context::params::RopeScalingType as LlamaRope;
+        match self {
+            Self::None | Self::NtkDynamic => None,
+            Self::Linear => Some(LlamaRope::Linear),
+            Self::Yarn => Some(LlamaRope::Yarn),
+        }
+    }
 }
 
 /// Model configuration
@@ -144,13 +161,13 @@ pub struct ModelConfig {
     /// KV cache quantization for values (affects memory usage)
     pub kv_cache_type_v: KvCacheQuantization,
 
-    /// RoPE scaling type for extended context (None = no scaling)
+    /// RoPE scaling type for extended context (None = the model's own setting)
     pub rope_scaling_type: RopeScalingType,
 
     /// RoPE scale factor (used with Linear/Yarn scaling, e.g., 2.0 for 2x context)
     pub rope_scale_factor: f32,
 
-    /// RoPE frequency base (default: 10000.0, higher for extended context)
+    /// RoPE frequency base override (0 = the model's own value, from GGUF metadata)
     pub rope_freq_base: f32,
 }
 
@@ -169,7 +186,7 @@ impl Default for ModelConfig {
             kv_cache_type_v: KvCacheQuantization::F16,
             rope_scaling_type: RopeScalingType::None,
             rope_scale_factor: 1.0,
-            rope_freq_base: 10000.0,
+            rope_freq_base: 0.0,
         }
     }
 }
@@ -253,6 +270,19 @@ impl ModelConfig {
         self
     }
 
+    /// Set RoPE scaling for running past the trained context (e.g. Yarn, 4.0)
+    pub fn with_rope_scaling(mut self, scaling: RopeScalingType, factor: f32) -> Self {
+        self.rope_scaling_type = scaling;
+        self.rope_scale_factor = factor;
+        self
+    }
+
+    /// Override the RoPE frequency base (0 = model default)
+    pub fn with_rope_freq_base(mut self, freq_base: f32) -> Self {
+        self.rope_freq_base = freq_base;
+        self
+    }
+
     /// MEMORY SAVER MODE: Aggressive memory optimization via KV quantization
     pub fn with_memory_saver(mut self) -> Self {
         self.kv_cache_type_k = KvCacheQuantization::Q4_0;
@@ -262,18 +292,34 @@ impl ModelConfig {
 
     /// Convert to llama.cpp model parameters
     pub fn into_params(&self) -> llama_cpp_2::model::params::LlamaModelParams {
-        llama_cpp_2::model::params::LlamaModelParams::default().with_n_gpu_layers(self.n_gpu_layers)
+        llama_cpp_2::model::params::LlamaModelParams::default()
+            .with_n_gpu_layers(self.n_gpu_layers)
+            .with_use_mmap(self.use_mmap)
+            .with_use_mlock(self.use_mlock)
     }
 
     /// Convert to llama.cpp context parameters
     pub fn into_context_params(&self) -> llama_cpp_2::context::params::LlamaContextParams {
         let ctx = std::num::NonZero::new(self.n_ctx);
 
-        llama_cpp_2::context::params::LlamaContextParams::default()
+        let mut params = llama_cpp_2::context::params::LlamaContextParams::default()
             .with_n_ctx(ctx)
             .with_n_batch(self.n_batch)
             .with_type_k(self.kv_cache_type_k.to_llama_type())
-            .with_type_v(self.kv_cache_type_v.to_llama_type())
+            .with_type_v(self.kv_cache_type_v.to_llama_type());
+
+        if let Some(scaling) = self.rope_scaling_type.to_llama_type() {
+            params = params.with_rope_scaling_type(scaling);
+            // llama.cpp takes the inverse: freq_scale 0.25 = 4x context
+            if self.rope_scale_factor > 0.0 && self.rope_scale_factor != 1.0 {
+                params = params.with_rope_freq_scale(1.0 / self.rope_scale_factor);
+            }
+        }
+        if self.rope_freq_base > 0.0 {
+            params = params.with_rope_freq_base(self.rope_freq_base);
+        }
+
+        params
     }
 
     /// Estimate KV cache memory usage in bytes for given context size
@@ -0,0 +1,550 @@
+//! Native GGUF header reader
+//!
+//! Parses the GGUF header (magic, version, KV metadata and tensor infos) without
+//! loading any weights, to describe model files in the catalog and to reject
+//! corrupt or truncated files before llama.cpp touches them.
+
+use crate::utils::error::{ExsaError, Result};
+use serde::Serialize;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+// Sanity limits so a corrupt header can't make us allocate or loop forever
+const MAX_STRING_LEN: u64 = 16 * 1024 * 1024;
+const MAX_TENSORS: u64 = 1 << 20;
+const MAX_KV: u64 = 1 << 20;
+const MAX_DIMS: u32 = 4;
+
+/// Model description read from a GGUF header
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GgufInfo {
+    /// GGUF format version
+    pub version: u32,
+
+    /// `general.architecture` (llama, qwen2, gemma2, ...)
+    pub architecture: Option<String>,
+
+    /// `general.name`
+    pub name: Option<String>,
+
+    /// Total parameter count (sum of tensor elements)
+    pub n_params: u64,
+
+    pub n_tensors: u64,
+
+    /// Quantization type (from `general.file_type`, else the dominant tensor type)
+    pub quantization: Option<String>,
+
+    /// Trained context length (`<arch>.context_length`)
+    pub context_length: Option<u64>,
+
+    /// Embedding length (`<arch>.embedding_length`)
+    pub embedding_length: Option<u64>,
+
+    /// Layer count (`<arch>.block_count`)
+    pub block_count: Option<u64>,
+
+    /// Attention heads (`<arch>.attention.head_count`)
+    pub head_count: Option<u64>,
+
+    /// Key/value heads (`<arch>.attention.head_count_kv`; fewer than `head_count` with GQA)
+    pub head_count_kv: Option<u64>,
+
+    /// Per-head key size (`<arch>.attention.key_length`, default embedding / heads)
+    pub key_length: Option<u64>,
+
+    /// Per-head value size (`<arch>.attention.value_length`, default embedding / heads)
+    pub value_length: Option<u64>,
+
+    /// Vocabulary size (length of `tokenizer.ggml.tokens`)
+    pub vocab_size: Option<u64>,
+
+    /// Bytes of tensor data (the weights)
+    pub tensor_bytes: u64,
+
+    /// Jinja chat template embedded in the file (`tokenizer.chat_template`)
+    pub chat_template: Option<String>,
+}
+
+/// Metadata value (arrays are skipped, only their length is kept)
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    Array(u64),
+}
+
+impl Value {
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Int(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Read the GGUF header of a model file
+pub fn read_gguf_info(path: &Path) -> Result<GgufInfo> {
+    let file = std::fs::File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    parse_gguf(BufReader::new(file), file_len).map_err(|reason| {
+        ExsaError::ModelError(format!("Invalid GGUF file {}: {}", path.display(), reason))
+    })
+}
+
+/// Parse a GGUF header and check the tensor data fits in `file_len` bytes
+pub fn parse_gguf<R: Read + Seek>(
+    reader: R,
+    file_len: u64,
+) -> std::result::Result<GgufInfo, String> {
+    let mut r = Reader {
+        inner: reader,
+        v1: false,
+    };
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != GGUF_MAGIC {
+        return Err("not a GGUF file (bad magic)".to_string());
+    }
+
+    let version = r.u32()?;
+    if !(1..=3).contains(&version) {
+        return Err(format!("unsupported GGUF version {}", version));
+    }
+    r.v1 = version == 1;
+
+    let n_tensors = r.count()?;
+    let n_kv = r.count()?;
+    if n_tensors > MAX_TENSORS || n_kv > MAX_KV {
+        return Err(format!(
+            "implausible header ({} tensors, {} metadata entries)",
+            n_tensors, n_kv
+        ));
+    }
+
+    let mut kv = std::collections::HashMap::new();
+    for _ in 0..n_kv {
+        let key = r.string()?;
+        let value_type = r.u32()?;
+        let value = r.value(value_type, file_len)?;
+        kv.insert(key, value);
+    }
+
+    let mut n_params: u64 = 0;
+    let mut data_end: u64 = 0;
+    let mut elements_by_type: std::collections::HashMap<u32, u64> = Default::default();
+
+    for _ in 0..n_tensors {
+        let name = r.string()?;
+        let n_dims = r.u32()?;
+        if n_dims == 0 || n_dims > MAX_DIMS {
+            return Err(format!("tensor '{}' has {} dimensions", name, n_dims));
+        }
+
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            let dim = r.count()?;
+            elements = elements
+                .checked_mul(dim)
+                .ok_or_else(|| format!("tensor '{}' is too large", name))?;
+        }
+
+        let ggml_type = r.u32()?;
+        let offset = r.u64()?;
+
+        n_params = n_params.saturating_add(elements);
+        *elements_by_type.entry(ggml_type).or_default() += elements;
+
+        if let Some(size) = tensor_bytes(ggml_type, elements) {
+            data_end = data_end.max(offset.saturating_add(size));
+        }
+    }
+
+    let alignment = kv
+        .get("general.alignment")
+        .and_then(Value::as_u64)
+        .filter(|a| *a > 0)
+        .unwrap_or(DEFAULT_ALIGNMENT);
+    let header_end = r.position()?;
+    let data_start = header_end.div_ceil(alignment) * alignment;
+
+    if data_start.saturating_add(data_end) > file_len {
+        return Err(format!(
+            "file is truncated ({} bytes, tensor data needs {})",
+            file_len,
+            data_start + data_end
+        ));
+    }
+
+    let get_str = |key: &str| match kv.get(key) {
+        Some(Value::Str(s)) => Some(s.clone()),
+        _ => None,
+    };
+    let architecture = get_str("general.architecture");
+    let arch_u64 = |suffix: &str| {
+        architecture
+            .as_ref()
+            .and_then(|arch| kv.get(&format!("{}.{}", arch, suffix)))
+            .and_then(Value::as_u64)
+    };
+
+    let quantization = kv
+        .get("general.file_type")
+        .and_then(Value::as_u64)
+        .and_then(file_type_name)
+        .map(String::from)
+        .or_else(|| {
+            elements_by_type
+                .iter()
+                .max_by_key(|(_, n)| **n)
+                .and_then(|(t, _)| ggml_type_name(*t))
+                .map(String::from)
+        });
+
+    Ok(GgufInfo {
+        version,
+        name: get_str("general.name"),
+        n_params,
+        n_tensors,
+        quantization,
+        context_length: arch_u64("context_length"),
+        embedding_length: arch_u64("embedding_length"),
+        block_count: arch_u64("block_count"),
+        head_count: arch_u64("attention.head_count"),
+        head_count_kv: arch_u64("attention.head_count_kv"),
+        key_length: arch_u64("attention.key_length"),
+        value_length: arch_u64("attention.value_length"),
+        vocab_size: match kv.get("tokenizer.ggml.tokens") {
+            Some(Value::Array(len)) => Some(*len),
+            _ => None,
+        },
+        tensor_bytes: data_end,
+        chat_template: get_str("tokenizer.chat_template"),
+        architecture,
+    })
+}
+
+struct Reader<R> {
+    inner: R,
+    /// GGUF v1 uses 32-bit counts and string lengths
+    v1: bool,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::result::Result<(), String> {
+        self.inner
+            .read_exact(buf)
+            .map_err(|_| "unexpected end of file in header".to_string())
+    }
+
+    fn bytes<const N: usize>(&mut self) -> std::result::Result<[u8; N], String> {
+        let mut buf = [0u8; N];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> std::result::Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn count(&mut self) -> std::result::Result<u64, String> {
+        if self.v1 {
+            Ok(self.u32()? as u64)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> std::result::Result<String, String> {
+        let len = self.count()?;
+        if len > MAX_STRING_LEN {
+            return Err(format!("string of {} bytes in header", len));
+        }
+        let mut buf = vec![0u8; len as usize];
+        self.read_exact(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn skip(&mut self, n: u64) -> std::result::Result<(), String> {
+        let n = i64::try_from(n).map_err(|_| "skip out of range".to_string())?;
+        self.inner
+            .seek(SeekFrom::Current(n))
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn position(&mut self) -> std::result::Result<u64, String> {
+        self.inner.stream_position().map_err(|e| e.to_string())
+    }
+
+    fn value(&mut self, value_type: u32, file_len: u64) -> std::result::Result<Value, String> {
+        Ok(match value_type {
+            0 => Value::Int(self.bytes::<1>()?[0] as i128),
+            1 => Value::Int(i8::from_le_bytes(self.bytes()?) as i128),
+            2 => Value::Int(u16::from_le_bytes(self.bytes()?) as i128),
+            3 => Value::Int(i16::from_le_bytes(self.bytes()?) as i128),
+            4 => Value::Int(self.u32()? as i128),
+            5 => Value::Int(i32::from_le_bytes(self.bytes()?) as i128),
+            6 => Value::Float(f32::from_le_bytes(self.bytes()?) as f64),
+            7 => Value::Bool(self.bytes::<1>()?[0] != 0),
+            8 => Value::Str(self.string()?),
+            9 => {
+                let elem_type = self.u32()?;
+                let len = self.count()?;
+                if len > file_len {
+                    return Err(format!("array of {} elements in header", len));
+                }
+                match scalar_size(elem_type) {
+                    Some(size) => self.skip(len.saturating_mul(size))?,
+                    None if elem_type == 8 => {
+                        for _ in 0..len {
+                            let n = self.count()?;
+                            if n > MAX_STRING_LEN {
+                                return Err(format!("string of {} bytes in header", n));
+                            }
+                            self.skip(n)?;
+                        }
+                    }
+                    None => return Err(format!("unsupported array element type {}", elem_type)),
+                }
+                Value::Array(len)
+            }
+            10 => Value::Int(self.u64()? as i128),
+            11 => Value::Int(i64::from_le_bytes(self.bytes()?) as i128),
+            12 => Value::Float(f64::from_le_bytes(self.bytes()?)),
+            other => return Err(format!("unknown metadata value type {}", other)),
+        })
+    }
+}
+
+/// Byte size of fixed-size metadata value types
+fn scalar_size(value_type: u32) -> Option<u64> {
+    match value_type {
+        0 | 1 | 7 => Some(1),
+        2 | 3 => Some(2),
+        4..=6 => Some(4),
+        10..=12 => Some(8),
+        _ => None,
+    }
+}
+
+/// (block size, bytes per block) for ggml tensor types
+fn ggml_block(ggml_type: u32) -> Option<(u64, u64)> {
+    Some(match ggml_type {
+        0 => (1, 4),     // F32
+        1 => (1, 2),     // F16
+        2 => (32, 18),   // Q4_0
+        3 => (32, 20),   // Q4_1
+        6 => (32, 22),   // Q5_0
+        7 => (32, 24),   // Q5_1
+        8 => (32, 34),   // Q8_0
+        9 => (32, 36),   // Q8_1
+        10 => (256, 84), // Q2_K
+        11 => (256, 110),
+        12 => (256, 144),
+        13 => (256, 176),
+        14 => (256, 210),
+        15 => (256, 292),
+        16 => (256, 66), // IQ2_XXS
+        17 => (256, 74),
+        18 => (256, 98),
+        19 => (256, 50),
+        20 => (32, 18),
+        21 => (256, 110),
+        22 => (256, 82),
+        23 => (256, 136),
+        24 => (1, 1), // I8
+        25 => (1, 2),
+        26 => (1, 4),
+        27 => (1, 8),
+        28 => (1, 8), // F64
+        29 => (256, 56),
+        30 => (1, 2), // BF16
+        34 => (256, 54),
+        35 => (256, 66),
+        39 => (32, 17), // MXFP4
+        _ => return None,
+    })
+}
+
+fn tensor_bytes(ggml_type: u32, elements: u64) -> Option<u64> {
+    let (block, size) = ggml_block(ggml_type)?;
+    Some(elements.div_ceil(block).saturating_mul(size))
+}
+
+fn ggml_type_name(ggml_type: u32) -> Option<&'static str> {
+    Some(match ggml_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        6 => "Q5_0",
+        7 => "Q5_1",
+        8 => "Q8_0",
+        9 => "Q8_1",
+        10 => "Q2_K",
+        11 => "Q3_K",
+        12 => "Q4_K",
+        13 => "Q5_K",
+        14 => "Q6_K",
+        15 => "Q8_K",
+        16 => "IQ2_XXS",
+        17 => "IQ2_XS",
+        18 => "IQ3_XXS",
+        19 => "IQ1_S",
+        20 => "IQ4_NL",
+        21 => "IQ3_S",
+        22 => "IQ2_S",
+        23 => "IQ4_XS",
+        29 => "IQ1_M",
+        30 => "BF16",
+        34 => "TQ1_0",
+        35 => "TQ2_0",
+        39 => "MXFP4",
+        _ => return None,
+    })
+}
+
+/// `general.file_type` (llama_ftype) names
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        38 => "MXFP4_MOE",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn put_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn put_kv_str(buf: &mut Vec<u8>, key: &str, value: &str) {
+        put_str(buf, key);
+        buf.extend_from_slice(&8u32.to_le_bytes());
+        put_str(buf, value);
+    }
+
+    fn put_kv_u32(buf: &mut Vec<u8>, key: &str, value: u32) {
+        put_str(buf, key);
+        buf.extend_from_slice(&4u32.to_le_bytes());
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// A tiny llama-style GGUF: one F32 tensor of 4x8 and a vocab array
+    fn sample_gguf() -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"GGUF");
+        buf.extend_from_slice(&3u32.to_le_bytes());
+        buf.extend_from_slice(&1u64.to_le_bytes()); // tensors
+        buf.extend_from_slice(&7u64.to_le_bytes()); // kv
+
+        put_kv_str(&mut buf, "general.architecture", "llama");
+        put_kv_str(&mut buf, "general.name", "Tiny");
+        put_kv_u32(&mut buf, "general.file_type", 15);
+        put_kv_u32(&mut buf, "llama.context_length", 8192);
+        put_kv_u32(&mut buf, "llama.embedding_length", 8);
+        put_kv_str(&mut buf, "tokenizer.chat_template", "{{ messages }}");
+
+        put_str(&mut buf, "tokenizer.ggml.tokens");
+        buf.extend_from_slice(&9u32.to_le_bytes());
+        buf.extend_from_slice(&8u32.to_le_bytes());
+        buf.extend_from_slice(&2u64.to_le_bytes());
+        put_str(&mut buf, "<s>");
+        put_str(&mut buf, "</s>");
+
+        put_str(&mut buf, "token_embd.weight");
+        buf.extend_from_slice(&2u32.to_le_bytes());
+        buf.extend_from_slice(&4u64.to_le_bytes());
+        buf.extend_from_slice(&8u64.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes()); // F32
+        buf.extend_from_slice(&0u64.to_le_bytes());
+
+        while buf.len() % 32 != 0 {
+            buf.push(0);
+        }
+        buf.resize(buf.len() + 4 * 8 * 4, 0);
+        buf
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let data = sample_gguf();
+        let info = parse_gguf(Cursor::new(&data), data.len() as u64).unwrap();
+
+        assert_eq!(info.version, 3);
+        assert_eq!(info.architecture.as_deref(), Some("llama"));
+        assert_eq!(info.name.as_deref(), Some("Tiny"));
+        assert_eq!(info.n_params, 32);
+        assert_eq!(info.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(info.context_length, Some(8192));
+        assert_eq!(info.embedding_length, Some(8));
+        assert_eq!(info.block_count, None);
+        assert_eq!(info.vocab_size, Some(2));
+        assert_eq!(info.tensor_bytes, 4 * 8 * 4);
+        assert_eq!(info.chat_template.as_deref(), Some("{{ messages }}"));
+    }
+
+    #[test]
+    fn test_rejects_truncated_and_bad_magic() {
+        let data = sample_gguf();
+        let truncated = &data[..data.len() - 1];
+        assert!(parse_gguf(Cursor::new(truncated), truncated.len() as u64)
+            .unwrap_err()
+            .contains("truncated"));
+
+        let header_cut = &data[..40];
+        assert!(parse_gguf(Cursor::new(header_cut), header_cut.len() as u64).is_err());
+
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(parse_gguf(Cursor::new(&bad), bad.len() as u64).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 //! Model loading and management
 
 use crate::model::config::ModelConfig;
+use crate::model::gguf::{read_gguf_info, GgufInfo};
 use crate::utils::error::{ExsaError, Result};
 use std::path::Path;
 use tracing::{info, warn};
@@ -12,6 +13,9 @@ pub struct ModelMetadata {
     pub path: String,
     pub size_bytes: u64,
     pub n_params: Option<u64>,
+
+    /// Header metadata (None if the file could not be parsed as GGUF)
+    pub gguf: Option<GgufInfo>,
 }
 
 /// Model loader and manager
@@ -25,8 +29,17 @@ impl ModelLoader {
         Self { config }
     }
 
-    /// Validate that the model file exists and is accessible
+    /// Validate that the model file exists, is accessible and has an intact GGUF header
+    /// (rejects corrupt or truncated files before llama.cpp reads them)
     pub fn validate(&self) -> Result<()> {
+        let scaling = self.config.rope_scaling_type;
+        if !scaling.is_supported() {
+            return Err(ExsaError::InvalidParameters(format!(
+                "RoPE scaling type {:?} is not supported by llama.cpp (use linear or yarn)",
+                scaling
+            )));
+        }
+
         let path = Path::new(&self.config.model_path);
 
         if !path.exists() {
@@ -50,7 +63,14 @@ impl ModelLoader {
             }
         }
 
-        info!("Model validation passed: {}", self.config.model_path);
+        let info = read_gguf_info(path)?;
+        info!(
+            "Model validation passed: {} ({} {}, {:.2}B params)",
+            self.config.model_path,
+            info.architecture.as_deref().unwrap_or("unknown"),
+            info.quantization.as_deref().unwrap_or("?"),
+            info.n_params as f64 / 1e9
+        );
 
         Ok(())
     }
@@ -66,11 +86,20 @@ impl ModelLoader {
             .unwrap_or("unknown")
             .to_string();
 
+        let gguf = match read_gguf_info(path) {
+            Ok(info) => Some(info),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        };
+
         Ok(ModelMetadata {
             name,
             path: self.config.model_path.clone(),
             size_bytes: metadata.len(),
-            n_params: None, // Will be populated after loading
+            n_params: gguf.as_ref().map(|g| g.n_params),
+            gguf,
         })
     }
 
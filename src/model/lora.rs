@@ -0,0 +1,167 @@
+//! LoRA adapter registry
+//!
+//! Adapters are GGUF files in a directory (default: `<models dir>/loras`), named
+//! after their file stem. Every registered adapter is initialized on a model when
+//! it loads; adapters dropped in later are picked up by an explicit rescan
+//! (`POST /v1/loras/rescan`), which reloads the served models with them.
+
+use crate::utils::error::{ExsaError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::info;
+
+/// A LoRA adapter requested for a generation, with its scale
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoraRequest {
+    /// Adapter name (file stem in the LoRA directory)
+    pub name: String,
+
+    /// Adapter scale (1.0 = full strength)
+    #[serde(default = "default_lora_scale")]
+    pub scale: f32,
+}
+
+fn default_lora_scale() -> f32 {
+    1.0
+}
+
+/// Registered adapter
+#[derive(Debug, Clone, Serialize)]
+pub struct LoraAdapterInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// Registry of LoRA adapters available on disk
+pub struct LoraRegistry {
+    dir: PathBuf,
+    adapters: RwLock<HashMap<String, LoraAdapterInfo>>,
+}
+
+impl LoraRegistry {
+    /// Create a registry over `dir` and scan it once
+    pub fn new(dir: PathBuf) -> Self {
+        let registry = Self {
+            dir,
+            adapters: RwLock::new(HashMap::new()),
+        };
+        if let Ok(n) = registry.rescan() {
+            if n > 0 {
+                info!("🧩 {} LoRA adapter(s) found in {:?}", n, registry.dir);
+            }
+        }
+        registry
+    }
+
+    /// Adapter directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Re-read the adapter directory. Returns the number of adapters found.
+    pub fn rescan(&self) -> Result<usize> {
+        let found = scan_dir(&self.dir)?;
+        let n = found.len();
+        let mut adapters = self
+            .adapters
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+        *adapters = found;
+        Ok(n)
+    }
+
+    /// List registered adapters, sorted by name
+    pub fn list(&self) -> Vec<LoraAdapterInfo> {
+        let mut out: Vec<LoraAdapterInfo> = self
+            .adapters
+            .read()
+            .map(|a| a.values().cloned().collect())
+            .unwrap_or_default();
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        out
+    }
+
+    /// Resolve adapter names to paths
+    pub fn resolve(&self, requests: &[LoraRequest]) -> Result<Vec<(PathBuf, f32)>> {
+        requests
+            .iter()
+            .map(|r| {
+                self.lookup(&r.name)
+                    .map(|info| (info.path, r.scale))
+                    .ok_or_else(|| {
+                        ExsaError::InvalidParameters(format!(
+                            "Unknown LoRA adapter '{}' (looked in {:?}; POST /v1/loras/rescan after adding one)",
+                            r.name, self.dir
+                        ))
+                    })
+            })
+            .collect()
+    }
+
+    fn lookup(&self, name: &str) -> Option<LoraAdapterInfo> {
+        self.adapters.read().ok()?.get(name).cloned()
+    }
+}
+
+fn scan_dir(dir: &Path) -> Result<HashMap<String, LoraAdapterInfo>> {
+    let mut out = HashMap::new();
+    if !dir.is_dir() {
+        return Ok(out);
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("gguf") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let size_bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        out.insert(
+            name.to_string(),
+            LoraAdapterInfo {
+                name: name.to_string(),
+                path,
+                size_bytes,
+            },
+        );
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_rescan() {
+        let dir = std::env::temp_dir().join(format!("exsa-lora-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("legal.gguf"), b"x").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"x").unwrap();
+
+        let registry = LoraRegistry::new(dir.clone());
+        assert_eq!(registry.list().len(), 1);
+
+        let req = |name: &str| LoraRequest {
+            name: name.to_string(),
+            scale: 0.5,
+        };
+        let resolved = registry.resolve(&[req("legal")]).unwrap();
+        assert_eq!(resolved, vec![(dir.join("legal.gguf"), 0.5)]);
+        assert!(registry.resolve(&[req("medical")]).is_err());
+
+        // New adapters are only picked up by an explicit rescan
+        std::fs::write(dir.join("medical.gguf"), b"x").unwrap();
+        assert!(registry.resolve(&[req("medical")]).is_err());
+        assert_eq!(registry.rescan().unwrap(), 2);
+        assert!(registry.resolve(&[req("medical")]).is_ok());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,9 +1,13 @@
 use crate::model::config::ModelConfig;
+use crate::model::lora::LoraRegistry;
 use crate::utils::error::{ExsaError, Result};
-use llama_cpp_2::{llama_backend::LlamaBackend, model::LlamaModel};
+use llama_cpp_2::{
+    llama_backend::LlamaBackend,
+    model::{LlamaLoraAdapter, LlamaModel},
+};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 /// Information about a loaded model
 #[derive(Debug, Clone)]
@@ -18,10 +22,61 @@ pub struct ModelInfo {
     pub last_used: std::time::SystemTime, // For LRU eviction
 }
 
+/// A model instance together with the LoRA adapters initialized on it
+pub struct LoraSet {
+    /// The model the adapters belong to; run requests using them on this instance
+    pub model: Arc<LlamaModel>,
+
+    /// Adapter files registered when the model was loaded (sorted)
+    registered: Vec<PathBuf>,
+
+    adapters: Mutex<HashMap<PathBuf, LlamaLoraAdapter>>,
+}
+
+impl LoraSet {
+    /// Whether every adapter in `paths` is initialized on the model
+    pub fn contains_all(&self, paths: &[PathBuf]) -> bool {
+        let adapters = self.lock();
+        paths.iter().all(|p| adapters.contains_key(p))
+    }
+
+    /// Exclusive access to the adapters, for setting them on a context
+    pub fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, LlamaLoraAdapter>> {
+        self.adapters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Initialize LoRA adapters on a freshly loaded model.
+///
+/// llama.cpp registers adapters with the model, so this needs the model uniquely
+/// owned: call it before the model is shared through an `Arc`. Adapters that fail
+/// to load are skipped (and reported as not loaded when a request names them).
+fn init_lora_adapters(
+    model: &mut LlamaModel,
+    paths: &[PathBuf],
+) -> HashMap<PathBuf, LlamaLoraAdapter> {
+    paths
+        .iter()
+        .filter_map(|path| match model.lora_adapter_init(path) {
+            Ok(adapter) => {
+                tracing::info!("🧩 LoRA adapter loaded: {:?}", path);
+                Some((path.clone(), adapter))
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load LoRA adapter {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The active model's name and instance
+type ActiveModel = Option<(String, Arc<LlamaModel>)>;
+
 /// Manages multiple models with hot-swapping capability
 pub struct ModelManager {
-    /// Currently active model
-    active_model: Arc<RwLock<(String, Arc<LlamaModel>)>>,
+    /// Currently active model (None after `unload_active`)
+    active_model: Arc<RwLock<ActiveModel>>,
 
     /// Cached models (name -> model)
     model_cache: Arc<RwLock<HashMap<String, Arc<LlamaModel>>>>,
@@ -32,6 +87,12 @@ pub struct ModelManager {
     /// Model metadata (name -> info)
     model_info: Arc<RwLock<HashMap<String, ModelInfo>>>,
 
+    /// LoRA adapters initialized on cached models (name -> adapters and their instance)
+    lora_sets: Arc<RwLock<HashMap<String, Arc<LoraSet>>>>,
+
+    /// Adapters to initialize on every model loaded (None = LoRA disabled)
+    lora_registry: RwLock<Option<Arc<LoraRegistry>>>,
+
     /// Backend (shared across all models)
     backend: Arc<LlamaBackend>,
 
@@ -40,6 +101,20 @@ pub struct ModelManager {
 }
 
 impl ModelManager {
+    /// Create a model manager with no model loaded yet
+    pub fn empty(backend: Arc<LlamaBackend>, max_cache_size: usize) -> Self {
+        Self {
+            active_model: Arc::new(RwLock::new(None)),
+            model_cache: Arc::new(RwLock::new(HashMap::new())),
+            model_configs: Arc::new(RwLock::new(HashMap::new())),
+            model_info: Arc::new(RwLock::new(HashMap::new())),
+            lora_sets: Arc::new(RwLock::new(HashMap::new())),
+            lora_registry: RwLock::new(None),
+            backend,
+            max_cache_size,
+        }
+    }
+
     /// Create a new model manager with an initial model
     pub fn new(
         initial_name: String,
@@ -90,10 +165,12 @@ impl ModelManager {
         tracing::info!("Model loaded in {}ms", load_time);
 
         Ok(Self {
-            active_model: Arc::new(RwLock::new((initial_name, model_arc))),
+            active_model: Arc::new(RwLock::new(Some((initial_name, model_arc)))),
             model_cache: Arc::new(RwLock::new(cache)),
             model_configs: Arc::new(RwLock::new(configs)),
             model_info: Arc::new(RwLock::new(infos)),
+            lora_sets: Arc::new(RwLock::new(HashMap::new())),
+            lora_registry: RwLock::new(None),
             backend,
             max_cache_size,
         })
@@ -164,10 +241,12 @@ impl ModelManager {
         tracing::info!("Model loaded asynchronously in {}ms", load_time);
 
         Ok(Self {
-            active_model: Arc::new(RwLock::new((initial_name, model_arc))),
+            active_model: Arc::new(RwLock::new(Some((initial_name, model_arc)))),
             model_cache: Arc::new(RwLock::new(cache)),
             model_configs: Arc::new(RwLock::new(configs)),
             model_info: Arc::new(RwLock::new(infos)),
+            lora_sets: Arc::new(RwLock::new(HashMap::new())),
+            lora_registry: RwLock::new(None),
             backend,
             max_cache_size,
         })
@@ -175,30 +254,57 @@ impl ModelManager {
 
     /// Get the currently active model
     pub fn get_active_model(&self) -> Result<Arc<LlamaModel>> {
-        let active = self
+        let (model_name, model) = self
             .active_model
             .read()
-            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .clone()
+            .ok_or(ExsaError::ModelNotLoaded)?;
 
         // Update last_used time
-        let model_name = active.0.clone();
-        drop(active);
         self.update_last_used(&model_name)?;
 
-        let active = self
-            .active_model
-            .read()
-            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
-        Ok(active.1.clone())
+        Ok(model)
+    }
+
+    /// Get a cached model by name (does not change the active model)
+    pub fn get_model(&self, name: &str) -> Result<Arc<LlamaModel>> {
+        let model = {
+            let cache = self
+                .model_cache
+                .read()
+                .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+            cache.get(name).cloned()
+        };
+
+        match model {
+            Some(model) => {
+                self.update_last_used(name)?;
+                Ok(model)
+            }
+            None => Err(ExsaError::ModelLoadError(format!(
+                "Model '{}' not found in cache. Use load_model() first.",
+                name
+            ))),
+        }
     }
 
     /// Get the name of the active model
     pub fn get_active_model_name(&self) -> Result<String> {
+        self.active_name()?.ok_or(ExsaError::ModelNotLoaded)
+    }
+
+    /// Whether a model is currently active
+    pub fn has_active_model(&self) -> bool {
+        matches!(self.active_name(), Ok(Some(_)))
+    }
+
+    fn active_name(&self) -> Result<Option<String>> {
         let active = self
             .active_model
             .read()
             .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
-        Ok(active.0.clone())
+        Ok(active.as_ref().map(|(name, _)| name.clone()))
     }
 
     /// Switch to a different model (hot-swap)
@@ -217,7 +323,7 @@ impl ModelManager {
                 .active_model
                 .write()
                 .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
-            *active = (model_name.to_string(), model.clone());
+            *active = Some((model_name.to_string(), model.clone()));
             drop(active);
             self.update_last_used(model_name)?;
             tracing::info!("✅ Switched to cached model: {}", model_name);
@@ -234,12 +340,23 @@ impl ModelManager {
 
     /// Load a new model into the cache
     pub fn load_model(&self, name: String, path: PathBuf, config: ModelConfig) -> Result<()> {
+        self.load_model_with_progress(name, path, config, |_| {})
+    }
+
+    /// Load a new model into the cache, reporting llama.cpp's load progress (0.0..=1.0)
+    pub fn load_model_with_progress<F: FnMut(f32) + 'static>(
+        &self,
+        name: String,
+        path: PathBuf,
+        config: ModelConfig,
+        on_progress: F,
+    ) -> Result<()> {
         tracing::info!("Loading new model: {} from {:?}", name, path);
 
         // If the model is already cached, we may still need to reload it.
-        // In llama.cpp, GPU offload (n_gpu_layers) is applied at model load time.
-        // If a model was cached with n_gpu_layers=0, switching back to it later will
-        // silently fall back to CPU even if the active runtime config requests GPU.
+        // In llama.cpp, GPU offload (n_gpu_layers), mmap and mlock are applied at model
+        // load time. If a model was cached with n_gpu_layers=0, switching back to it later
+        // would silently fall back to CPU even if the active runtime config requests GPU.
         let reload_needed = {
             let cache = self
                 .model_cache
@@ -256,7 +373,11 @@ impl ModelManager {
                     .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
                 let existing = configs.get(&name);
                 existing
-                    .map(|c| c.n_gpu_layers != config.n_gpu_layers)
+                    .map(|c| {
+                        c.n_gpu_layers != config.n_gpu_layers
+                            || c.use_mmap != config.use_mmap
+                            || c.use_mlock != config.use_mlock
+                    })
                     .unwrap_or(false)
             }
         };
@@ -273,16 +394,40 @@ impl ModelManager {
             }
         } else {
             tracing::info!(
-                "Reloading cached model {} due to GPU layer config change",
+                "Reloading cached model {} due to GPU layer/mmap/mlock config change",
                 name
             );
         }
 
+        self.load_into_cache(name, path, config, on_progress)
+    }
+
+    /// Load `path` from disk with every registered LoRA adapter and cache it as
+    /// `name`, replacing (and updating the active pointer of) any cached instance
+    fn load_into_cache<F: FnMut(f32) + 'static>(
+        &self,
+        name: String,
+        path: PathBuf,
+        config: ModelConfig,
+        mut on_progress: F,
+    ) -> Result<()> {
         // Load the model
         let start = std::time::Instant::now();
-        let model = LlamaModel::load_from_file(&self.backend, &path, &config.into_params())
+        let params = config
+            .into_params()
+            .with_progress_callback(move |progress| {
+                on_progress(progress);
+                true
+            });
+        let mut model = LlamaModel::load_from_file(&self.backend, &path, &params)
             .map_err(|e| ExsaError::ModelLoadError(format!("Failed to load model: {}", e)))?;
 
+        // Adapters can only be added while the model is uniquely owned
+        let registered = self.registered_loras();
+        let adapters = registered
+            .as_ref()
+            .map(|paths| init_lora_adapters(&mut model, paths));
+
         let model_arc = Arc::new(model);
         let load_time = start.elapsed().as_millis() as u64;
 
@@ -322,11 +467,24 @@ impl ModelManager {
             .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
         cache.insert(name.clone(), model_arc.clone());
         drop(cache);
+        match (registered, adapters) {
+            (Some(registered), Some(adapters)) => {
+                let set = Arc::new(LoraSet {
+                    model: model_arc.clone(),
+                    registered,
+                    adapters: Mutex::new(adapters),
+                });
+                if let Ok(mut sets) = self.lora_sets.write() {
+                    sets.insert(name.clone(), set);
+                }
+            }
+            _ => self.drop_lora_set(&name),
+        }
 
         // If this model is currently active, update active pointer as well.
         if let Ok(mut active) = self.active_model.write() {
-            if active.0 == name {
-                *active = (name.clone(), model_arc.clone());
+            if active.as_ref().is_some_and(|(n, _)| *n == name) {
+                *active = Some((name.clone(), model_arc.clone()));
             }
         }
 
@@ -381,9 +539,9 @@ impl ModelManager {
 
     /// Unload a model from cache (except active model)
     pub fn unload_model(&self, name: &str) -> Result<()> {
-        let active_name = self.get_active_model_name()?;
+        let active_name = self.active_name()?;
 
-        if name == active_name {
+        if active_name.as_deref() == Some(name) {
             return Err(ExsaError::InvalidParameters(
                 "Cannot unload the active model".to_string(),
             ));
@@ -395,10 +553,105 @@ impl ModelManager {
             .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
 
         cache.remove(name);
+        drop(cache);
+        self.drop_lora_set(name);
         tracing::info!("Unloaded model: {}", name);
         Ok(())
     }
 
+    /// Deactivate the active model and drop the manager's references to it.
+    ///
+    /// The model memory is freed once every other holder (the inference thread's
+    /// context) has released it. Returns the name of the unloaded model.
+    pub fn unload_active(&self) -> Result<Option<String>> {
+        let previous = self
+            .active_model
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .take();
+
+        let Some((name, _)) = previous else {
+            return Ok(None);
+        };
+
+        self.model_cache
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .remove(&name);
+        self.model_info
+            .write()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .remove(&name);
+        self.drop_lora_set(&name);
+
+        tracing::info!("Unloaded active model: {}", name);
+        Ok(Some(name))
+    }
+
+    /// Initialize the adapters in `registry` on every model loaded from now on
+    pub fn set_lora_registry(&self, registry: Arc<LoraRegistry>) {
+        if let Ok(mut current) = self.lora_registry.write() {
+            *current = Some(registry);
+        }
+    }
+
+    /// The LoRA adapters initialized on the cached model `name` when it was loaded
+    /// (None when LoRA is disabled)
+    pub fn lora_set(&self, name: &str) -> Result<Option<Arc<LoraSet>>> {
+        let sets = self
+            .lora_sets
+            .read()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?;
+        Ok(sets.get(name).cloned())
+    }
+
+    /// Reload the cached model `name` from disk if the registered LoRA adapters
+    /// changed since it was loaded, so the new ones get initialized on it.
+    ///
+    /// Returns whether the model was reloaded. Requests already running keep the
+    /// old instance until they finish. Blocks while loading.
+    pub fn reload_for_loras(&self, name: &str) -> Result<bool> {
+        let Some(registered) = self.registered_loras() else {
+            return Ok(false);
+        };
+        let current = self.lora_set(name)?;
+        if current.is_some_and(|set| set.registered == registered) {
+            return Ok(false);
+        }
+
+        let path = self.get_model_info(name)?.path;
+        let config = self
+            .model_configs
+            .read()
+            .map_err(|e| ExsaError::InternalError(format!("Lock error: {}", e)))?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ExsaError::ModelLoadError(format!("Model {} not found", name)))?;
+
+        tracing::info!(
+            "Reloading model {} with {} LoRA adapter(s)",
+            name,
+            registered.len()
+        );
+        self.load_into_cache(name.to_string(), path, config, |_| {})?;
+        Ok(true)
+    }
+
+    /// Registered adapter files, sorted (None when LoRA is disabled)
+    fn registered_loras(&self) -> Option<Vec<PathBuf>> {
+        let registry = self.lora_registry.read().ok()?.clone()?;
+        let mut paths: Vec<PathBuf> = registry.list().into_iter().map(|a| a.path).collect();
+        paths.sort();
+        Some(paths)
+    }
+
+    /// Forget the adapters of `name` (its cached instance was replaced or dropped)
+    fn drop_lora_set(&self, name: &str) {
+        if let Ok(mut sets) = self.lora_sets.write() {
+            sets.remove(name);
+        }
+    }
+
     /// Update last_used timestamp for a model (for LRU tracking)
     fn update_last_used(&self, name: &str) -> Result<()> {
         let mut infos = self
@@ -416,7 +669,7 @@ impl ModelManager {
     /// Evict least recently used model from cache
     fn evict_lru_model(&self) -> Result<()> {
         // Find LRU model (excluding active model)
-        let active_name = self.get_active_model_name()?;
+        let active_name = self.active_name()?.unwrap_or_default();
 
         let infos = self
             .model_info
@@ -0,0 +1,307 @@
+//! Model manifest (`models.toml`)
+//!
+//! Declares named models with aliases and per-model defaults, read from
+//! `<models dir>/models.toml` or `EXSA_MODELS_MANIFEST`:
+//!
+//! ```toml
+//! [models.qwen]
+//! path = "qwen2.5-7b-instruct-q4_k_m.gguf"   # relative to the manifest
+//! aliases = ["gpt-4o", "assistant"]
+//! chat_template = "chatml"
+//! context_size = 8192
+//! gpu_layers = 99
+//! kv_cache_type = "q8_0"
+//! rope_scaling_type = "yarn"                 # run past the trained context
+//! rope_scale_factor = 4.0
+//! system_prompt = "You are a concise assistant."
+//!
+//! [models.qwen.sampling]
+//! temperature = 0.6
+//! top_p = 0.95
+//! ```
+
+use crate::model::config::{KvCacheQuantization, ModelConfig, RopeScalingType};
+use crate::utils::error::{ExsaError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Manifest file name looked up in the models directory
+pub const MANIFEST_FILE_NAME: &str = "models.toml";
+
+/// Default sampling parameters for a model (applied when the request leaves them unset)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SamplingDefaults {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<i32>,
+    pub max_tokens: Option<usize>,
+    pub repeat_penalty: Option<f32>,
+    #[serde(default)]
+    pub stop: Vec<String>,
+}
+
+impl SamplingDefaults {
+    /// Overwrite the fields set here in `params`
+    pub fn apply_to(&self, params: &mut crate::inference::SamplingParams) {
+        if let Some(v) = self.temperature {
+            params.temperature = v;
+        }
+        if let Some(v) = self.top_p {
+            params.top_p = v;
+        }
+        if let Some(v) = self.top_k {
+            params.top_k = v;
+        }
+        if let Some(v) = self.max_tokens {
+            params.max_tokens = v;
+        }
+        if let Some(v) = self.repeat_penalty {
+            params.repeat_penalty = v;
+        }
+        for stop in &self.stop {
+            if !params.stop_sequences.contains(stop) {
+                params.stop_sequences.push(stop.clone());
+            }
+        }
+    }
+}
+
+/// A named model declared in the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelEntry {
+    /// Entry name (the table key)
+    #[serde(skip_deserializing)]
+    pub name: String,
+
+    /// GGUF path, relative to the manifest's directory unless absolute
+    pub path: PathBuf,
+
+    /// Additional names requests may use for this model
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// Chat template name (chatml, llama3, alpaca, gemma, mistral, phi3, deepseek,
+    /// command-r, vicuna, zephyr, raw)
+    pub chat_template: Option<String>,
+
+    pub context_size: Option<u32>,
+    pub gpu_layers: Option<u32>,
+
+    /// KV cache quantization for both K and V (f16, q8_0, q4_0, ...)
+    pub kv_cache_type: Option<KvCacheQuantization>,
+
+    pub use_mmap: Option<bool>,
+    pub use_mlock: Option<bool>,
+
+    /// RoPE scaling for extended context (none, linear, yarn)
+    pub rope_scaling_type: Option<RopeScalingType>,
+    pub rope_scale_factor: Option<f32>,
+    pub rope_freq_base: Option<f32>,
+
+    #[serde(default)]
+    pub sampling: SamplingDefaults,
+
+    /// Default system prompt (replaces EXSA_DEFAULT_SYSTEM_PROMPT for this model)
+    pub system_prompt: Option<String>,
+}
+
+impl ModelEntry {
+    /// Whether `name` is this entry's name or one of its aliases
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+
+    /// Apply the entry's runtime settings on top of `config` (path included)
+    pub fn apply_to_config(&self, config: &mut ModelConfig) {
+        config.model_path = self.path.to_string_lossy().to_string();
+        if let Some(n_ctx) = self.context_size {
+            // Batch size tracks the context size unless it was set smaller on purpose
+            if config.n_batch >= config.n_ctx || config.n_batch > n_ctx {
+                config.n_batch = n_ctx;
+            }
+            config.n_ctx = n_ctx;
+        }
+        if let Some(layers) = self.gpu_layers {
+            config.n_gpu_layers = layers;
+        }
+        if let Some(kv) = self.kv_cache_type {
+            config.kv_cache_type_k = kv;
+            config.kv_cache_type_v = kv;
+        }
+        if let Some(mmap) = self.use_mmap {
+            config.use_mmap = mmap;
+        }
+        if let Some(mlock) = self.use_mlock {
+            config.use_mlock = mlock;
+        }
+        if let Some(scaling) = self.rope_scaling_type {
+            config.rope_scaling_type = scaling;
+        }
+        if let Some(factor) = self.rope_scale_factor {
+            config.rope_scale_factor = factor;
+        }
+        if let Some(base) = self.rope_freq_base {
+            config.rope_freq_base = base;
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    #[serde(default)]
+    models: BTreeMap<String, ModelEntry>,
+}
+
+/// Parsed `models.toml`
+#[derive(Debug, Clone, Default)]
+pub struct ModelManifest {
+    source: Option<PathBuf>,
+    entries: Vec<ModelEntry>,
+}
+
+impl ModelManifest {
+    /// Manifest location: EXSA_MODELS_MANIFEST, or `models.toml` in the models directory
+    pub fn locate(models_dir: Option<&Path>) -> Option<PathBuf> {
+        if let Ok(path) = std::env::var("EXSA_MODELS_MANIFEST") {
+            if !path.trim().is_empty() {
+                return Some(PathBuf::from(path));
+            }
+        }
+
+        models_dir
+            .map(|dir| dir.join(MANIFEST_FILE_NAME))
+            .filter(|p| p.is_file())
+    }
+
+    /// Read and validate a manifest file
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            ExsaError::InvalidParameters(format!("Failed to read manifest {:?}: {}", path, e))
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut manifest = Self::parse(&contents, base_dir)?;
+        manifest.source = Some(path.to_path_buf());
+        Ok(manifest)
+    }
+
+    /// Parse manifest contents, resolving relative paths against `base_dir`
+    pub fn parse(contents: &str, base_dir: &Path) -> Result<Self> {
+        let file: ManifestFile = toml::from_str(contents)
+            .map_err(|e| ExsaError::InvalidParameters(format!("Invalid models manifest: {}", e)))?;
+
+        let mut entries = Vec::with_capacity(file.models.len());
+        for (name, mut entry) in file.models {
+            entry.name = name;
+            if entry.path.is_relative() {
+                entry.path = base_dir.join(&entry.path);
+            }
+            entries.push(entry);
+        }
+
+        // Names and aliases must be unambiguous across entries
+        let mut seen = std::collections::HashSet::new();
+        for entry in &entries {
+            for name in std::iter::once(&entry.name).chain(entry.aliases.iter()) {
+                if !seen.insert(name.as_str()) {
+                    return Err(ExsaError::InvalidParameters(format!(
+                        "Models manifest: '{}' is declared more than once",
+                        name
+                    )));
+                }
+            }
+        }
+
+        Ok(Self {
+            source: None,
+            entries,
+        })
+    }
+
+    /// File the manifest was read from
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// All entries, sorted by name
+    pub fn entries(&self) -> &[ModelEntry] {
+        &self.entries
+    }
+
+    /// Find an entry by name or alias
+    pub fn find(&self, name: &str) -> Option<&ModelEntry> {
+        self.entries.iter().find(|e| e.matches(name))
+    }
+
+    /// Find the entry declaring the given model file
+    pub fn find_by_path(&self, path: &str) -> Option<&ModelEntry> {
+        let canon = |p: &Path| std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let target = canon(Path::new(path));
+        self.entries.iter().find(|e| canon(&e.path) == target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[models.qwen]
+path = "qwen.gguf"
+aliases = ["gpt-4o", "assistant"]
+chat_template = "chatml"
+context_size = 8192
+kv_cache_type = "q8_0"
+use_mlock = true
+rope_scaling_type = "yarn"
+rope_scale_factor = 4.0
+system_prompt = "Be brief."
+
+[models.qwen.sampling]
+temperature = 0.6
+
+[models.llama]
+path = "/abs/llama.gguf"
+"#;
+
+    #[test]
+    fn test_parse_and_lookup() {
+        let manifest = ModelManifest::parse(MANIFEST, Path::new("/models")).unwrap();
+        assert_eq!(manifest.entries().len(), 2);
+
+        let qwen = manifest.find("gpt-4o").unwrap();
+        assert_eq!(qwen.name, "qwen");
+        assert_eq!(qwen.path, PathBuf::from("/models/qwen.gguf"));
+        assert_eq!(qwen.sampling.temperature, Some(0.6));
+        assert_eq!(qwen.system_prompt.as_deref(), Some("Be brief."));
+
+        let llama = manifest.find_by_path("/abs/llama.gguf").unwrap();
+        assert_eq!(llama.name, "llama");
+        assert!(manifest.find("mistral").is_none());
+    }
+
+    #[test]
+    fn test_apply_to_config() {
+        let manifest = ModelManifest::parse(MANIFEST, Path::new("/models")).unwrap();
+        let mut config = ModelConfig::new("other.gguf").with_context_size(2048);
+        manifest.find("qwen").unwrap().apply_to_config(&mut config);
+
+        assert_eq!(config.model_path, "/models/qwen.gguf");
+        assert_eq!(config.n_ctx, 8192);
+        assert_eq!(config.kv_cache_type_k, KvCacheQuantization::Q8_0);
+        assert!(config.use_mmap && config.use_mlock);
+        assert_eq!(config.rope_scaling_type, RopeScalingType::Yarn);
+        assert_eq!(config.rope_scale_factor, 4.0);
+        assert_eq!(config.rope_freq_base, 0.0);
+    }
+
+    #[test]
+    fn test_duplicate_alias_rejected() {
+        let dup = "[models.a]\npath = \"a.gguf\"\naliases = [\"x\"]\n\n[models.b]\npath = \"b.gguf\"\naliases = [\"x\"]\n";
+        assert!(ModelManifest::parse(dup, Path::new(".")).is_err());
+    }
+}
@@ -1,7 +1,15 @@
 pub mod config;
+pub mod gguf;
 pub mod loader;
+pub mod lora;
 pub mod manager;
+pub mod manifest;
+pub mod planner;
 
 pub use config::{KvCacheQuantization, ModelConfig, RopeScalingType};
+pub use gguf::{read_gguf_info, GgufInfo};
 pub use loader::{ModelLoader, ModelMetadata};
-pub use manager::{ModelInfo, ModelManager};
+pub use lora::{LoraAdapterInfo, LoraRegistry, LoraRequest};
+pub use manager::{LoraSet, ModelInfo, ModelManager};
+pub use manifest::{ModelEntry, ModelManifest, SamplingDefaults};
+pub use planner::{estimate_loaded_bytes, plan_model, MemoryPlan, PlanOptions};
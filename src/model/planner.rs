@@ -0,0 +1,535 @@
+//! Memory-fit planner
+//!
+//! Estimates a model's memory footprint from its GGUF header (weights, KV cache
+//! and compute buffers) and picks the largest context size and KV cache type
+//! that fit in available system memory, or explains why the model doesn't fit.
+//! Given a VRAM budget (EXSA_PLAN_VRAM_MB) it also picks how many layers to
+//! offload, with the rest of the model staying in system memory.
+//!
+//! The figures are estimates in the same spirit as
+//! `ModelConfig::estimate_kv_cache_memory`: close enough to choose settings,
+//! not an exact accounting of llama.cpp allocations. VRAM is not detected, so
+//! without a budget the configured GPU layers are left as they are.
+
+use crate::model::config::{KvCacheQuantization, ModelConfig};
+use crate::model::gguf::{read_gguf_info, GgufInfo};
+use crate::utils::error::{ExsaError, Result};
+use crate::utils::MemorySnapshot;
+use serde::Serialize;
+use std::path::Path;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Context sizes are rounded down to a multiple of this
+const CONTEXT_STEP: u32 = 256;
+
+/// llama.cpp's default micro-batch (compute buffers scale with it, not n_batch)
+const DEFAULT_UBATCH: u64 = 512;
+
+/// KV cache types tried in order of preference (quality first)
+const KV_CANDIDATES: &[KvCacheQuantization] = &[
+    KvCacheQuantization::F16,
+    KvCacheQuantization::Q8_0,
+    KvCacheQuantization::Q4_0,
+];
+
+/// Planner inputs besides the model
+#[derive(Debug, Clone)]
+pub struct PlanOptions {
+    /// Largest context to consider (None = the model's trained context)
+    pub max_context: Option<u32>,
+
+    /// Use this KV cache type instead of choosing one
+    pub kv_cache_type: Option<KvCacheQuantization>,
+
+    /// Batch size (capped at the planned context)
+    pub n_batch: u32,
+
+    /// Memory budget in bytes (None = available system memory)
+    pub memory_budget_bytes: Option<u64>,
+
+    /// GPU memory budget in bytes (None = don't plan GPU offload)
+    pub vram_budget_bytes: Option<u64>,
+
+    /// Memory left free for the OS and other processes
+    pub reserve_bytes: u64,
+
+    /// Smallest context worth loading the model for
+    pub min_context: u32,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            max_context: None,
+            kv_cache_type: None,
+            n_batch: 2048,
+            memory_budget_bytes: None,
+            vram_budget_bytes: None,
+            reserve_bytes: 1024 * MIB,
+            min_context: 512,
+        }
+    }
+}
+
+impl PlanOptions {
+    /// Load planner options from environment variables.
+    ///
+    /// - EXSA_PLAN_MEMORY_MB=... (default: available system memory)
+    /// - EXSA_PLAN_VRAM_MB=... (default: unset, no GPU offload planning)
+    /// - EXSA_PLAN_RESERVE_MB=... (default: 1024)
+    /// - EXSA_PLAN_MIN_CONTEXT=... (default: 512)
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let env_u64 = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+
+        Self {
+            memory_budget_bytes: env_u64("EXSA_PLAN_MEMORY_MB")
+                .filter(|mb| *mb > 0)
+                .map(|mb| mb.saturating_mul(MIB)),
+            vram_budget_bytes: env_u64("EXSA_PLAN_VRAM_MB")
+                .filter(|mb| *mb > 0)
+                .map(|mb| mb.saturating_mul(MIB)),
+            reserve_bytes: env_u64("EXSA_PLAN_RESERVE_MB")
+                .map(|mb| mb.saturating_mul(MIB))
+                .unwrap_or(defaults.reserve_bytes),
+            min_context: env_u64("EXSA_PLAN_MIN_CONTEXT")
+                .map(|v| v as u32)
+                .unwrap_or(defaults.min_context),
+            ..defaults
+        }
+    }
+}
+
+/// Model dimensions that drive memory use
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelShape {
+    pub n_layers: u64,
+    pub n_embd: u64,
+    pub n_head: u64,
+    pub n_head_kv: u64,
+    pub key_length: u64,
+    pub value_length: u64,
+    pub n_vocab: u64,
+    pub trained_context: Option<u64>,
+    pub weights_bytes: u64,
+}
+
+impl ModelShape {
+    /// Derive the shape from GGUF metadata
+    pub fn from_gguf(info: &GgufInfo) -> std::result::Result<Self, String> {
+        let n_layers = info
+            .block_count
+            .ok_or("GGUF header has no block_count (layer count)")?;
+        let n_embd = info
+            .embedding_length
+            .ok_or("GGUF header has no embedding_length")?;
+        let n_head = info
+            .head_count
+            .filter(|h| *h > 0)
+            .ok_or("GGUF header has no attention.head_count")?;
+        let head_dim = n_embd / n_head;
+
+        Ok(Self {
+            n_layers,
+            n_embd,
+            n_head,
+            n_head_kv: info.head_count_kv.unwrap_or(n_head),
+            key_length: info.key_length.unwrap_or(head_dim),
+            value_length: info.value_length.unwrap_or(head_dim),
+            n_vocab: info.vocab_size.unwrap_or(32_000),
+            trained_context: info.context_length,
+            weights_bytes: info.tensor_bytes,
+        })
+    }
+
+    /// KV cache bytes per context token
+    pub fn kv_bytes_per_token(&self, kv: KvCacheQuantization) -> u64 {
+        let f16_bytes = 2 * self.n_layers * self.n_head_kv * (self.key_length + self.value_length);
+        (f16_bytes as f64 * kv.memory_ratio() as f64).ceil() as u64
+    }
+
+    /// Weights, KV cache and compute buffers of the model loaded with `config`
+    pub fn loaded_bytes(&self, config: &ModelConfig) -> u64 {
+        let n_ctx = match config.n_ctx {
+            0 => self.trained_context.unwrap_or(4096),
+            n => n as u64,
+        };
+        let kv_per_token = (self.kv_bytes_per_token(config.kv_cache_type_k)
+            + self.kv_bytes_per_token(config.kv_cache_type_v))
+            / 2;
+        let (compute_fixed, compute_per_token) = self.compute_bytes(config.n_batch);
+        self.weights_bytes
+            .saturating_add(compute_fixed)
+            .saturating_add(n_ctx.saturating_mul(kv_per_token + compute_per_token))
+    }
+
+    /// Compute buffer bytes: a fixed part plus a per-context-token part (attention scores)
+    fn compute_bytes(&self, n_batch: u32) -> (u64, u64) {
+        let ubatch = (n_batch as u64).clamp(1, DEFAULT_UBATCH);
+        let fixed = ubatch * (self.n_vocab + 16 * self.n_embd) * 4;
+        let per_token = ubatch * self.n_head * 4;
+        (fixed, per_token)
+    }
+}
+
+/// Result of planning a model load
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryPlan {
+    /// Whether the model fits with at least the minimum context
+    pub fits: bool,
+
+    pub n_ctx: u32,
+    pub n_batch: u32,
+    pub kv_cache_type: KvCacheQuantization,
+
+    /// Layers to offload to the GPU (None = keep the configured value; only
+    /// planned with a VRAM budget)
+    pub n_gpu_layers: Option<u32>,
+
+    /// Context length the model was trained with
+    pub trained_context: Option<u64>,
+
+    pub weights_bytes: u64,
+    pub kv_cache_bytes: u64,
+    pub compute_bytes: u64,
+
+    /// Weights + KV cache + compute buffers
+    pub total_bytes: u64,
+
+    /// Part of `total_bytes` placed in GPU memory
+    pub gpu_bytes: u64,
+
+    /// Memory kept free for the OS and other processes
+    pub reserve_bytes: u64,
+
+    /// Memory the plan was made against
+    pub available_bytes: u64,
+
+    /// GPU memory the offload was planned against
+    pub vram_bytes: Option<u64>,
+
+    /// Human-readable summary (why the model doesn't fit, when it doesn't)
+    pub explanation: String,
+}
+
+impl MemoryPlan {
+    /// Apply the planned context, batch and KV cache settings
+    pub fn apply_to(&self, config: &mut ModelConfig) {
+        config.n_ctx = self.n_ctx;
+        config.n_batch = self.n_batch;
+        config.kv_cache_type_k = self.kv_cache_type;
+        config.kv_cache_type_v = self.kv_cache_type;
+        if let Some(layers) = self.n_gpu_layers {
+            config.n_gpu_layers = layers;
+        }
+    }
+}
+
+/// Layers that fit in `vram_bytes` next to the compute buffers, and the GPU
+/// bytes they use. Each layer carries its share of the weights and KV cache.
+fn plan_offload(
+    shape: &ModelShape,
+    vram_bytes: u64,
+    model_bytes: u64,
+    compute_bytes: u64,
+) -> (u32, u64) {
+    let n_layers = shape.n_layers.max(1);
+    let layer_bytes = model_bytes.div_ceil(n_layers).max(1);
+    let layers = (vram_bytes.saturating_sub(compute_bytes) / layer_bytes).min(n_layers);
+    if layers == 0 {
+        return (0, 0);
+    }
+    (
+        layers.min(u32::MAX as u64) as u32,
+        layers * layer_bytes + compute_bytes,
+    )
+}
+
+/// Plan context size, KV cache type and (with a VRAM budget) GPU layers for a
+/// model shape within `available_bytes` of system memory
+pub fn plan(shape: &ModelShape, available_bytes: u64, options: &PlanOptions) -> MemoryPlan {
+    let trained = shape.trained_context.map(|c| c.min(u32::MAX as u64) as u32);
+    let target = match (options.max_context, trained) {
+        (Some(max), Some(trained)) => max.min(trained),
+        (Some(max), None) => max,
+        (None, Some(trained)) => trained,
+        (None, None) => 4096,
+    }
+    .max(1);
+
+    let ram_usable = available_bytes.saturating_sub(options.reserve_bytes);
+    // Offloaded layers move out of system memory, so the two budgets add up
+    let usable = ram_usable.saturating_add(options.vram_budget_bytes.unwrap_or(0));
+    let (compute_fixed, compute_per_token) = shape.compute_bytes(options.n_batch);
+    let fixed = shape.weights_bytes + compute_fixed;
+
+    // Largest context (rounded to CONTEXT_STEP) that fits with a given KV cache type
+    let max_context_for = |kv: KvCacheQuantization| -> u32 {
+        let per_token = shape.kv_bytes_per_token(kv) + compute_per_token;
+        let n = usable.saturating_sub(fixed) / per_token.max(1);
+        let n = n.min(u32::MAX as u64) as u32;
+        if n >= CONTEXT_STEP {
+            n / CONTEXT_STEP * CONTEXT_STEP
+        } else {
+            n
+        }
+    };
+
+    let candidates: Vec<KvCacheQuantization> = match options.kv_cache_type {
+        Some(kv) => vec![kv],
+        None => KV_CANDIDATES.to_vec(),
+    };
+
+    // Best quality that reaches the target, else whatever gives the most context
+    let (kv, n_ctx) = candidates
+        .iter()
+        .find(|kv| max_context_for(**kv) >= target)
+        .map(|kv| (*kv, target))
+        .unwrap_or_else(|| {
+            let kv = *candidates.last().unwrap_or(&KvCacheQuantization::F16);
+            (kv, max_context_for(kv).min(target))
+        });
+
+    let kv_cache_bytes = shape.kv_bytes_per_token(kv) * n_ctx as u64;
+    let compute_bytes = compute_fixed + compute_per_token * n_ctx as u64;
+    let total_bytes = shape.weights_bytes + kv_cache_bytes + compute_bytes;
+    let (n_gpu_layers, gpu_bytes) = match options.vram_budget_bytes {
+        Some(vram) => {
+            let model_bytes = shape.weights_bytes + kv_cache_bytes;
+            let (layers, bytes) = plan_offload(shape, vram, model_bytes, compute_bytes);
+            (Some(layers), bytes)
+        }
+        None => (None, 0),
+    };
+    // Whole layers are offloaded, so up to one layer's worth can spill back to RAM
+    let ram_fits = total_bytes - gpu_bytes <= ram_usable;
+    let fits = n_ctx >= options.min_context.min(target) && ram_fits;
+
+    let explanation = if shape.weights_bytes > usable {
+        format!(
+            "model weights need {} but only {} is available ({} free minus {} reserve)",
+            format_bytes(shape.weights_bytes),
+            format_bytes(usable),
+            format_bytes(available_bytes),
+            format_bytes(options.reserve_bytes)
+        )
+    } else if !ram_fits {
+        format!(
+            "{} of the model stays in system memory after offloading {} layers, but only {} is \
+             available",
+            format_bytes(total_bytes - gpu_bytes),
+            n_gpu_layers.unwrap_or(0),
+            format_bytes(ram_usable)
+        )
+    } else if !fits {
+        format!(
+            "weights fit ({}), but even with a {:?} KV cache only {} tokens of context fit in {} \
+             (minimum {})",
+            format_bytes(shape.weights_bytes),
+            kv,
+            n_ctx,
+            format_bytes(usable),
+            options.min_context
+        )
+    } else if n_ctx < target {
+        format!(
+            "context reduced from {} to {} tokens with a {:?} KV cache to fit in {}",
+            target,
+            n_ctx,
+            kv,
+            format_bytes(usable)
+        )
+    } else {
+        format!(
+            "{} tokens of context with a {:?} KV cache use {} of {}",
+            n_ctx,
+            kv,
+            format_bytes(total_bytes),
+            format_bytes(usable)
+        )
+    };
+    let explanation = match n_gpu_layers {
+        Some(layers) => format!(
+            "{}; {} of {} layers on the GPU ({})",
+            explanation,
+            layers,
+            shape.n_layers,
+            format_bytes(gpu_bytes)
+        ),
+        None => explanation,
+    };
+
+    MemoryPlan {
+        fits,
+        n_ctx,
+        n_batch: options.n_batch.min(n_ctx).max(1),
+        kv_cache_type: kv,
+        n_gpu_layers,
+        trained_context: shape.trained_context,
+        weights_bytes: shape.weights_bytes,
+        kv_cache_bytes,
+        compute_bytes,
+        total_bytes,
+        gpu_bytes,
+        reserve_bytes: options.reserve_bytes,
+        available_bytes,
+        vram_bytes: options.vram_budget_bytes,
+        explanation,
+    }
+}
+
+/// Plan a model file against the memory budget (or currently available system memory)
+pub fn plan_model(path: &Path, options: &PlanOptions) -> Result<MemoryPlan> {
+    let info = read_gguf_info(path)?;
+    let shape = ModelShape::from_gguf(&info)
+        .map_err(|e| ExsaError::ModelError(format!("Cannot plan {}: {}", path.display(), e)))?;
+
+    let available = match options.memory_budget_bytes {
+        Some(budget) => budget,
+        None => MemorySnapshot::capture()
+            .map(|m| m.available_bytes)
+            .filter(|b| *b > 0)
+            .ok_or_else(|| {
+                ExsaError::ServiceUnavailable(
+                    "Available memory is unknown on this platform (set EXSA_PLAN_MEMORY_MB)"
+                        .to_string(),
+                )
+            })?,
+    };
+
+    Ok(plan(&shape, available, options))
+}
+
+/// Memory a model file takes once loaded with `config` (its file size when the
+/// GGUF header can't be read)
+pub fn estimate_loaded_bytes(path: &Path, config: &ModelConfig) -> u64 {
+    match read_gguf_info(path).and_then(|info| {
+        ModelShape::from_gguf(&info).map_err(|e| ExsaError::ModelError(e.to_string()))
+    }) {
+        Ok(shape) => shape.loaded_bytes(config),
+        Err(_) => std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1024 * MIB {
+        format!("{:.1} GiB", bytes as f64 / (1024 * MIB) as f64)
+    } else {
+        format!("{} MiB", bytes / MIB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Llama 3 8B-like shape (GQA: 8 KV heads), ~4.6 GiB of Q4_K_M weights
+    fn llama3_8b() -> ModelShape {
+        ModelShape {
+            n_layers: 32,
+            n_embd: 4096,
+            n_head: 32,
+            n_head_kv: 8,
+            key_length: 128,
+            value_length: 128,
+            n_vocab: 128_256,
+            trained_context: Some(8192),
+            weights_bytes: 4700 * MIB,
+        }
+    }
+
+    #[test]
+    fn test_kv_bytes_per_token() {
+        let shape = llama3_8b();
+        // 2 (K+V) * 32 layers * 8 heads * 128 dims * 2 bytes = 128 KiB per token
+        assert_eq!(
+            shape.kv_bytes_per_token(KvCacheQuantization::F16),
+            128 * 1024
+        );
+        assert_eq!(
+            shape.kv_bytes_per_token(KvCacheQuantization::Q8_0),
+            64 * 1024
+        );
+    }
+
+    #[test]
+    fn test_loaded_bytes_counts_context() {
+        let shape = llama3_8b();
+        let mut config = ModelConfig {
+            n_ctx: 8192,
+            ..Default::default()
+        };
+        let full = shape.loaded_bytes(&config);
+        // 8192 tokens * 128 KiB of F16 KV cache on top of the weights
+        assert!(full >= shape.weights_bytes + 1024 * MIB);
+
+        config.n_ctx = 2048;
+        assert!(shape.loaded_bytes(&config) < full);
+    }
+
+    #[test]
+    fn test_plan_prefers_full_context_and_f16() {
+        let plan = plan(&llama3_8b(), 16 * 1024 * MIB, &PlanOptions::default());
+        assert!(plan.fits);
+        assert_eq!(plan.n_ctx, 8192);
+        assert_eq!(plan.kv_cache_type, KvCacheQuantization::F16);
+        assert_eq!(plan.n_batch, 2048);
+    }
+
+    #[test]
+    fn test_plan_quantizes_kv_then_shrinks_context() {
+        let options = PlanOptions {
+            max_context: Some(131_072),
+            ..Default::default()
+        };
+        let mut shape = llama3_8b();
+        shape.trained_context = Some(131_072);
+
+        let plan = plan(&shape, 8 * 1024 * MIB, &options);
+        assert!(plan.fits);
+        assert_eq!(plan.kv_cache_type, KvCacheQuantization::Q4_0);
+        assert!(plan.n_ctx < 131_072);
+        assert_eq!(plan.n_ctx % CONTEXT_STEP, 0);
+        assert!(plan.total_bytes + plan.reserve_bytes <= plan.available_bytes);
+    }
+
+    #[test]
+    fn test_plan_offloads_layers_within_vram() {
+        let options = PlanOptions {
+            vram_budget_bytes: Some(4 * 1024 * MIB),
+            ..Default::default()
+        };
+        // 6 GiB of RAM alone is too little; 4 GiB of VRAM takes part of the model
+        let partial = plan(&llama3_8b(), 6 * 1024 * MIB, &options);
+        assert!(partial.fits);
+        assert_eq!(partial.n_ctx, 8192);
+        let layers = partial.n_gpu_layers.unwrap();
+        assert!(layers > 0 && layers < 32);
+        assert!(partial.gpu_bytes <= 4 * 1024 * MIB);
+
+        let mut config = ModelConfig::default();
+        partial.apply_to(&mut config);
+        assert_eq!(config.n_gpu_layers, layers);
+
+        // Enough VRAM for everything offloads every layer
+        let options = PlanOptions {
+            vram_budget_bytes: Some(24 * 1024 * MIB),
+            ..Default::default()
+        };
+        let full = plan(&llama3_8b(), 2 * 1024 * MIB, &options);
+        assert!(full.fits);
+        assert_eq!(full.n_gpu_layers, Some(32));
+    }
+
+    #[test]
+    fn test_plan_refuses_when_weights_do_not_fit() {
+        let plan = plan(&llama3_8b(), 4 * 1024 * MIB, &PlanOptions::default());
+        assert!(!plan.fits);
+        assert!(plan.explanation.contains("model weights need"));
+    }
+}
@@ -39,6 +39,12 @@ pub struct RagConfig {
     /// unstable on some platforms/backends.
     pub vector_search_enabled: bool,
 
+    /// Rerank retrieved chunks with the local reranker model (requires EXSA_RERANK_MODEL_PATH).
+    pub rerank_enabled: bool,
+
+    /// Number of first-stage candidates passed to the reranker before keeping top_k.
+    pub rerank_candidates: usize,
+
     /// Timeout (seconds) for RAG initialization (Postgres connect + schema init).
     pub init_timeout_secs: u64,
 
@@ -69,6 +75,9 @@ impl Default for RagConfig {
 
             vector_search_enabled: true,
 
+            rerank_enabled: true,
+            rerank_candidates: 24,
+
             // Timeouts (safe defaults to prevent hangs)
             init_timeout_secs: 15,
             postgres_connect_timeout_secs: 5,
@@ -93,6 +102,8 @@ impl RagConfig {
     /// - EXSA_RAG_RETRIEVE_TOP_K=...
     /// - EXSA_RAG_MAX_CONTEXT_CHARS=...
     /// - EXSA_RAG_VECTOR_SEARCH_ENABLED=true|false
+    /// - EXSA_RAG_RERANK_ENABLED=true|false
+    /// - EXSA_RAG_RERANK_CANDIDATES=...
     /// - EXSA_RAG_INIT_TIMEOUT_SECS=...
     /// - EXSA_RAG_PG_CONNECT_TIMEOUT_SECS=...
     /// - EXSA_RAG_PG_ACQUIRE_TIMEOUT_SECS=...
@@ -136,6 +147,16 @@ impl RagConfig {
             .and_then(|v| v.parse().ok())
             .unwrap_or(defaults.max_context_chars);
 
+        let rerank_enabled = std::env::var("EXSA_RAG_RERANK_ENABLED")
+            .ok()
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(defaults.rerank_enabled);
+
+        let rerank_candidates = std::env::var("EXSA_RAG_RERANK_CANDIDATES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.rerank_candidates);
+
         let init_timeout_secs = std::env::var("EXSA_RAG_INIT_TIMEOUT_SECS")
             .ok()
             .and_then(|v| v.parse().ok())
@@ -190,6 +211,9 @@ impl RagConfig {
 
             vector_search_enabled,
 
+            rerank_enabled,
+            rerank_candidates,
+
             init_timeout_secs,
             postgres_connect_timeout_secs,
             postgres_acquire_timeout_secs,
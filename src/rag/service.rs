@@ -1,3 +1,4 @@
+use crate::inference::rerank::Reranker;
 use crate::rag::config::RagConfig;
 use crate::rag::embed::EmbeddingsClient;
 use crate::rag::models::{RagDocument, RagIngestResponse, RagSearchResult};
@@ -18,10 +19,13 @@ pub struct RagService {
     pg: PgPool,
     qdrant: Option<QdrantStore>,
     embed: Option<EmbeddingsClient>,
+
+    /// Optional local cross-encoder used as a second-stage ranker.
+    reranker: Option<Arc<Reranker>>,
 }
 
 impl RagService {
-    pub async fn new(cfg: RagConfig) -> Result<Arc<Self>> {
+    pub async fn new(cfg: RagConfig, reranker: Option<Arc<Reranker>>) -> Result<Arc<Self>> {
         if !cfg.enabled {
             return Err(ExsaError::InvalidParameters("RAG disabled".to_string()));
         }
@@ -76,11 +80,25 @@ impl RagService {
             (None, None)
         };
 
+        let reranker = if cfg.rerank_enabled {
+            if let Some(r) = &reranker {
+                info!(
+                    "RAG rerank: enabled with '{}' ({} candidates)",
+                    r.name(),
+                    cfg.rerank_candidates
+                );
+            }
+            reranker
+        } else {
+            None
+        };
+
         Ok(Arc::new(Self {
             cfg,
             pg,
             qdrant,
             embed,
+            reranker,
         }))
     }
 
@@ -292,6 +310,52 @@ impl RagService {
         query: &str,
         top_k: usize,
     ) -> Result<Vec<RagSearchResult>> {
+        let Some(reranker) = self.reranker.clone() else {
+            return self.retrieve(kb, query, top_k).await;
+        };
+
+        // Over-fetch candidates from the first stage, then let the cross-encoder
+        // pick the final top_k.
+        let candidates = self.cfg.rerank_candidates.max(top_k);
+        let mut results = self.retrieve(kb, query, candidates).await?;
+        if results.len() <= 1 {
+            return Ok(results);
+        }
+
+        let docs: Vec<String> = results.iter().map(|r| r.content.clone()).collect();
+        let q = query.to_string();
+        let ranked = match tokio::task::spawn_blocking(move || {
+            reranker.rerank(&q, &docs, Some(top_k))
+        })
+        .await
+        {
+            Ok(Ok((ranked, _))) => ranked,
+            Ok(Err(e)) => {
+                warn!("RAG rerank failed, using first-stage order: {}", e);
+                results.truncate(top_k);
+                return Ok(results);
+            }
+            Err(e) => {
+                warn!("RAG rerank task failed, using first-stage order: {}", e);
+                results.truncate(top_k);
+                return Ok(results);
+            }
+        };
+
+        let mut slots: Vec<Option<RagSearchResult>> = results.into_iter().map(Some).collect();
+        Ok(ranked
+            .into_iter()
+            .filter_map(|r| {
+                slots[r.index].take().map(|mut hit| {
+                    hit.score = r.score;
+                    hit
+                })
+            })
+            .collect())
+    }
+
+    /// First-stage retrieval (Qdrant vectors or Postgres lexical search).
+    async fn retrieve(&self, kb: &str, query: &str, top_k: usize) -> Result<Vec<RagSearchResult>> {
         if query.trim().is_empty() {
             return Ok(vec![]);
         }
@@ -149,6 +149,12 @@ pub struct MemorySnapshot {
 
     /// Virtual memory size in bytes
     pub vms_bytes: u64,
+
+    /// System memory available for new allocations in bytes (0 = unknown)
+    pub available_bytes: u64,
+
+    /// Total system memory in bytes (0 = unknown)
+    pub total_bytes: u64,
 }
 
 impl MemorySnapshot {
@@ -170,6 +176,26 @@ impl MemorySnapshot {
             }
         }
 
+        // System-wide figures from /proc/meminfo (MemAvailable accounts for reclaimable cache)
+        let mut available = 0u64;
+        let mut total = 0u64;
+        if let Ok(meminfo) = fs::read_to_string("/proc/meminfo") {
+            for line in meminfo.lines() {
+                let kb = || {
+                    line.split_whitespace()
+                        .nth(1)
+                        .and_then(|v| v.parse::<u64>().ok())
+                        .map(|v| v * 1024)
+                        .unwrap_or(0)
+                };
+                if line.starts_with("MemAvailable:") {
+                    available = kb();
+                } else if line.starts_with("MemTotal:") {
+                    total = kb();
+                }
+            }
+        }
+
         Some(Self {
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -177,6 +203,8 @@ impl MemorySnapshot {
                 .as_secs(),
             rss_bytes: rss,
             vms_bytes: vms,
+            available_bytes: available,
+            total_bytes: total,
         })
     }
 
@@ -194,6 +222,8 @@ impl MemorySnapshot {
                 .as_secs(),
             rss_bytes: 0, // Platform-specific implementation needed
             vms_bytes: 0,
+            available_bytes: 0,
+            total_bytes: 0,
         })
     }
 
@@ -207,6 +237,8 @@ impl MemorySnapshot {
                 .as_secs(),
             rss_bytes: 0,
             vms_bytes: 0,
+            available_bytes: 0,
+            total_bytes: 0,
         })
     }
 
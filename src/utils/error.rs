@@ -1,7 +1,7 @@
 //! Error types for Exsa-Engine
 
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -33,8 +33,19 @@ pub enum ExsaError {
     #[error("Request timeout")]
     Timeout,
 
-    #[error("Queue is full")]
-    QueueFull,
+    /// Per-client limit reached (fair-share cap); retry after `retry_after_secs`
+    #[error("Too many requests: {message}")]
+    TooManyRequests {
+        message: String,
+        retry_after_secs: u64,
+    },
+
+    /// Load shed: queue full or predicted wait too long; retry after `retry_after_secs`
+    #[error("Server overloaded: {message}")]
+    Overloaded {
+        message: String,
+        retry_after_secs: u64,
+    },
 
     #[error("Service unavailable: {0}")]
     ServiceUnavailable(String),
@@ -49,8 +60,25 @@ pub enum ExsaError {
     Io(#[from] std::io::Error),
 }
 
+impl ExsaError {
+    /// Seconds a client should wait before retrying (sent as `Retry-After`)
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            ExsaError::TooManyRequests {
+                retry_after_secs, ..
+            }
+            | ExsaError::Overloaded {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+            _ => None,
+        }
+    }
+}
+
 impl IntoResponse for ExsaError {
     fn into_response(self) -> Response {
+        let retry_after = self.retry_after_secs();
+
         let (status, error_message) = match self {
             ExsaError::ModelError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             ExsaError::InferenceError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
@@ -59,10 +87,8 @@ impl IntoResponse for ExsaError {
             ExsaError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             ExsaError::InvalidParameters(msg) => (StatusCode::BAD_REQUEST, msg),
             ExsaError::Timeout => (StatusCode::REQUEST_TIMEOUT, "Request timeout".to_string()),
-            ExsaError::QueueFull => (
-                StatusCode::SERVICE_UNAVAILABLE,
-                "Server is at capacity".to_string(),
-            ),
+            ExsaError::TooManyRequests { message, .. } => (StatusCode::TOO_MANY_REQUESTS, message),
+            ExsaError::Overloaded { message, .. } => (StatusCode::SERVICE_UNAVAILABLE, message),
             ExsaError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
             ExsaError::ModelNotLoaded => (
                 StatusCode::SERVICE_UNAVAILABLE,
@@ -79,7 +105,10 @@ impl IntoResponse for ExsaError {
             "error": error_message,
         }));
 
-        (status, body).into_response()
+        match retry_after {
+            Some(secs) => (status, [(RETRY_AFTER, secs.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
     }
 }
 